@@ -0,0 +1,206 @@
+//! Throughput benchmarks for the engine
+//!
+//! Inputs are generated with `WorkloadGenerator`, so every run measures the same streams.
+//! Row counts default to 100000, set BENCH_ROWS to a comma separated list to change them, e.g.:
+//! ```
+//! BENCH_ROWS=100000,1000000 cargo bench --bench engine
+//! ```
+//!
+//! The end-to-end group writes to SQLite files, which syncs every statement to disk. It is
+//! orders of magnitude slower than the in-memory groups, leave it out of quick runs with a filter:
+//! ```
+//! cargo bench --bench engine -- '^(parse|engine|dispute)'
+//! ```
+use criterion::{
+    BatchSize, BenchmarkId, Criterion, SamplingMode, Throughput, criterion_group, criterion_main,
+};
+use rust_payment_engine::csv_processor::{
+    ProcessOptions, TransactionReader, get_all_accounts_formatted, process_csv, process_transaction,
+};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::Transaction;
+use rust_payment_engine::output::OutputOptions;
+use rust_payment_engine::workload::{WorkloadConfig, WorkloadGenerator, WorkloadWriter};
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::{env, fs};
+
+fn row_counts() -> Vec<usize> {
+    env::var("BENCH_ROWS")
+        .ok()
+        .map(|value| {
+            value
+                .split(',')
+                .map(|rows| rows.trim().parse().expect("BENCH_ROWS must be numbers"))
+                .collect()
+        })
+        .unwrap_or_else(|| vec![100_000])
+}
+
+fn default_workload(rows: usize) -> WorkloadConfig {
+    WorkloadConfig {
+        seed: 1,
+        rows,
+        clients: 1000,
+        duplicate_rate: 0.01,
+        invalid_rate: 0.01,
+        unknown_tx_rate: 0.01,
+        ..WorkloadConfig::default()
+    }
+}
+
+fn dispute_heavy_workload(rows: usize) -> WorkloadConfig {
+    WorkloadConfig {
+        mix: "deposit=40,withdrawal=5,dispute=35,resolve=15,chargeback=5"
+            .parse()
+            .unwrap(),
+        ..default_workload(rows)
+    }
+}
+
+fn generate_csv(config: WorkloadConfig) -> Vec<u8> {
+    let mut writer = WorkloadWriter::new(vec![]);
+    for tx in WorkloadGenerator::new(config) {
+        writer.write(&tx).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+// Parsed the same way process_csv does, so engine benchmarks exclude the CSV reader
+fn parse(input: &[u8]) -> Vec<Transaction> {
+    TransactionReader::new(input)
+        .unwrap()
+        .map(|result| result.unwrap().1)
+        .collect()
+}
+
+fn memory_dbs() -> (TransactionDB, ClientAccountDB) {
+    (
+        TransactionDB::new(":memory:").unwrap(),
+        ClientAccountDB::new(":memory:").unwrap(),
+    )
+}
+
+fn file_dbs(dir: &PathBuf) -> (TransactionDB, ClientAccountDB) {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    (
+        TransactionDB::new(&path("transactions.db")).unwrap(),
+        ClientAccountDB::new(&path("client_accounts.db")).unwrap(),
+    )
+}
+
+fn apply_all(txs: &[Transaction], dbs: &(TransactionDB, ClientAccountDB)) {
+    let options = ProcessOptions::default();
+    for tx in txs {
+        black_box(process_transaction(tx, &dbs.0, &dbs.1, &options).unwrap());
+    }
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_only");
+    group.sample_size(10);
+    for rows in row_counts() {
+        let input = generate_csv(default_workload(rows));
+        group.throughput(Throughput::Elements(rows as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &input, |b, input| {
+            b.iter(|| {
+                for result in TransactionReader::new(input.as_slice()).unwrap() {
+                    black_box(result.unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_engine(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engine_in_memory");
+    group.sample_size(10);
+    for rows in row_counts() {
+        let txs = parse(&generate_csv(default_workload(rows)));
+        group.throughput(Throughput::Elements(rows as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &txs, |b, txs| {
+            b.iter_batched(
+                memory_dbs,
+                |dbs| apply_all(txs, &dbs),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_end_to_end(c: &mut Criterion) {
+    let mut group = c.benchmark_group("end_to_end_sqlite");
+    group.sample_size(10);
+    group.sampling_mode(SamplingMode::Flat);
+    let dir = env::temp_dir().join(format!("engine_bench_{}", std::process::id()));
+    for rows in row_counts() {
+        let input = generate_csv(default_workload(rows));
+        group.throughput(Throughput::Elements(rows as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &input, |b, input| {
+            b.iter_batched(
+                || file_dbs(&dir),
+                |(transaction_db, client_account_db)| {
+                    process_csv(input.as_slice(), &transaction_db, &client_account_db).unwrap();
+                    black_box(
+                        get_all_accounts_formatted(&client_account_db, &OutputOptions::default())
+                            .unwrap(),
+                    );
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+    let _ = fs::remove_dir_all(dir);
+}
+
+fn bench_dispute_heavy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispute_heavy");
+    group.sample_size(10);
+    for rows in row_counts() {
+        let txs = parse(&generate_csv(dispute_heavy_workload(rows)));
+        group.throughput(Throughput::Elements(rows as u64));
+        group.bench_with_input(BenchmarkId::new("engine", rows), &txs, |b, txs| {
+            b.iter_batched(
+                memory_dbs,
+                |dbs| apply_all(txs, &dbs),
+                BatchSize::PerIteration,
+            )
+        });
+
+        // Lookups of the referred transactions alone, over a store holding the whole stream
+        let dbs = memory_dbs();
+        apply_all(&txs, &dbs);
+        let referred: Vec<u32> = txs
+            .iter()
+            .filter(|tx| tx.amount.is_none())
+            .map(|tx| tx.id)
+            .collect();
+        group.throughput(Throughput::Elements(referred.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("get_transaction_state", rows),
+            &referred,
+            |b, ids| {
+                b.iter(|| {
+                    for id in ids {
+                        black_box(dbs.0.get_transaction_state(*id).unwrap());
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_engine,
+    bench_end_to_end,
+    bench_dispute_heavy
+);
+criterion_main!(benches);
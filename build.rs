@@ -0,0 +1,15 @@
+// Generates the gRPC messages and service from proto/payment_engine.proto, only with the grpc feature.
+// protoc is vendored, so no system install is needed
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "grpc")]
+    {
+        let mut config = tonic_prost_build::Config::new();
+        config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+        tonic_prost_build::configure().compile_with_config(
+            config,
+            &["proto/payment_engine.proto"],
+            &["proto"],
+        )?;
+    }
+    Ok(())
+}
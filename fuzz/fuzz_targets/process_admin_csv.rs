@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_payment_engine::csv_processor::{process_admin_csv, process_csv};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::verify::verify;
+
+// Funded accounts with an open dispute, so administrative operations have state to work on
+const SEED_INPUT: &[u8] = b"type,client,tx,amount
+deposit,1,1,10.0
+deposit,2,2,5.0
+dispute,2,2,
+";
+
+fuzz_target!(|data: &[u8]| {
+    let transaction_db = TransactionDB::new(":memory:").unwrap();
+    let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+    process_csv(SEED_INPUT, &transaction_db, &client_account_db).unwrap();
+
+    let _ = process_admin_csv(data, &transaction_db, &client_account_db);
+    verify(&transaction_db, &client_account_db).unwrap();
+});
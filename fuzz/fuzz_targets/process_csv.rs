@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_payment_engine::csv_processor::{ProcessOptions, process_csv_with_options};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::verify::verify;
+
+fuzz_target!(|data: &[u8]| {
+    let transaction_db = TransactionDB::new(":memory:").unwrap();
+    let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+    let options = ProcessOptions {
+        paranoid: true,
+        ..ProcessOptions::default()
+    };
+
+    // Malformed input is reported as an error, only panics and broken invariants are bugs.
+    // Rows applied before the error must still leave a consistent state
+    let _ = process_csv_with_options(data, &transaction_db, &client_account_db, &options);
+    verify(&transaction_db, &client_account_db).unwrap();
+});
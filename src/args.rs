@@ -0,0 +1,152 @@
+use crate::csv_processor::ProcessOptions;
+use crate::events::sink_from_spec;
+use crate::fees::FeeSchedule;
+use crate::rates::RateTable;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Name and inline value of a command line option, None for positional arguments
+///
+/// # Notes:
+///
+/// - Options accept both "--name value" and "--name=value", e.g. "--rows=10" is ("--rows", Some("10"))
+pub fn split_option(arg: &OsString) -> Option<(String, Option<String>)> {
+    let flag = arg.to_str().filter(|arg| arg.starts_with("--"))?;
+    Some(match flag.split_once('=') {
+        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+        None => (flag.to_string(), None),
+    })
+}
+
+/// Value of an option, given inline or as the next argument
+pub fn take_value(
+    name: &str,
+    inline: Option<String>,
+    rest: &mut impl Iterator<Item = OsString>,
+) -> Result<String, Box<dyn Error>> {
+    if let Some(value) = inline {
+        return Ok(value);
+    }
+    match rest.next() {
+        Some(value) => value
+            .into_string()
+            .map_err(|_| format!("value for {} is not valid UTF-8", name).into()),
+        None => Err(format!("expected a value for {}", name).into()),
+    }
+}
+
+pub fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, Box<dyn Error>> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, name).into())
+}
+
+/// Arguments accepted by the HTTP and gRPC servers
+///
+/// # Options:
+///
+/// - --listen: Address to listen on (default the one of the server)
+/// - --data-dir: Directory of the SQLite files, kept between restarts. Required unless --memory is given
+/// - --memory: Keep state in memory only, lost on shutdown
+/// - --locked-allow: Comma separated transaction types still accepted on locked accounts (default none)
+/// - --events: Publish domain events to jsonl:<path> or webhook:<url>, can be repeated
+/// - --rates: Exchange rates used by conversions, a CSV or JSON file
+/// - --fee-schedule: Fees charged per transaction type and client tier, a JSON file
+/// - --admin-token-file: File with the token required by administrative operations, which are disabled without it
+pub struct ServerArgs {
+    pub listen: String,
+    pub data_dir: Option<PathBuf>,
+    pub options: ProcessOptions,
+    pub admin_token: Option<String>,
+}
+
+pub fn parse_server_args(
+    args: impl IntoIterator<Item = OsString>,
+    default_listen: &str,
+) -> Result<ServerArgs, Box<dyn Error>> {
+    let mut args = args.into_iter();
+    let mut listen = default_listen.to_string();
+    let mut data_dir = None;
+    let mut memory = false;
+    let mut options = ProcessOptions::default();
+    let mut admin_token = None;
+
+    while let Some(arg) = args.next() {
+        let Some((name, inline)) = split_option(&arg) else {
+            return Err(format!("unexpected argument {}", arg.to_string_lossy()).into());
+        };
+        match name.as_str() {
+            "--memory" => memory = true,
+            "--listen" => listen = take_value(&name, inline, &mut args)?,
+            "--data-dir" => data_dir = Some(take_value(&name, inline, &mut args)?.into()),
+            "--locked-allow" => {
+                options.lock_policy = take_value(&name, inline, &mut args)?.parse()?
+            }
+            "--rates" => options.rates = RateTable::load(&take_value(&name, inline, &mut args)?)?,
+            "--fee-schedule" => {
+                options.fees = FeeSchedule::load(&take_value(&name, inline, &mut args)?)?
+            }
+            "--events" => options
+                .events
+                .add_sink(sink_from_spec(&take_value(&name, inline, &mut args)?)?),
+            // Read from a file, so the token does not show up in the process list
+            "--admin-token-file" => {
+                let path = take_value(&name, inline, &mut args)?;
+                let token = fs::read_to_string(&path)?.trim().to_string();
+                if token.is_empty() {
+                    return Err(format!("admin token file {} is empty", path).into());
+                }
+                admin_token = Some(token);
+            }
+            other => return Err(format!("unknown option {}", other).into()),
+        }
+    }
+    // No default directory, state is only kept where it was asked for
+    if !memory && data_dir.is_none() {
+        return Err(From::from(
+            "expected --data-dir <dir>, or --memory to keep state in memory only",
+        ));
+    }
+    Ok(ServerArgs {
+        listen,
+        data_dir: data_dir.filter(|_| !memory),
+        options,
+        admin_token,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_options_take_inline_or_next_values() {
+        let parsed = parse_server_args(
+            args(&["--listen=0.0.0.0:9000", "--data-dir", "/tmp/payments"]),
+            "127.0.0.1:8080",
+        )
+        .unwrap();
+        assert_eq!(parsed.listen, "0.0.0.0:9000");
+        assert_eq!(parsed.data_dir, Some(PathBuf::from("/tmp/payments")));
+
+        let parsed = parse_server_args(args(&["--memory"]), "127.0.0.1:8080").unwrap();
+        assert_eq!(
+            (parsed.listen.as_str(), parsed.data_dir),
+            ("127.0.0.1:8080", None)
+        );
+    }
+
+    #[test]
+    fn test_servers_require_a_place_for_state() {
+        assert!(parse_server_args(args(&[]), "127.0.0.1:8080").is_err());
+        assert!(parse_server_args(args(&["--memory", "extra"]), "127.0.0.1:8080").is_err());
+        assert!(parse_server_args(args(&["--data-dir"]), "127.0.0.1:8080").is_err());
+    }
+}
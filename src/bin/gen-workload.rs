@@ -0,0 +1,120 @@
+//! Synthetic workload generator
+//!
+//! Writes a reproducible transaction stream, in the same CSV format the engine reads
+//!
+//! ## Usage:
+//! ```
+//! cargo run --bin gen-workload -- --rows 100000 --seed 7 > workload.csv
+//! cargo run --bin gen-workload -- --rows 1000 --mix deposit=50,withdrawal=20,dispute=20,resolve=5,chargeback=5 --output workload.csv --expected accounts.csv
+//! ```
+//!
+//! ## Options:
+//! - --seed: Random seed, same seed and options always produce the same stream (default 0)
+//! - --rows: Number of rows (default 1000)
+//! - --clients: Number of distinct clients (default 100)
+//! - --mix: Weights per transaction type (default deposit=60,withdrawal=25,dispute=8,resolve=5,chargeback=2)
+//! - --duplicate-rate: Share of rows repeating an earlier deposit or withdrawal (default 0)
+//! - --invalid-rate: Share of rows with a missing, zero, negative or NaN amount (default 0)
+//! - --unknown-tx-rate: Share of disputes, resolves and chargebacks referring to non existent tx IDs (default 0)
+//! - --max-amount: Largest generated amount (default 1000)
+//! - --output: Write the stream to this file instead of Std Out
+//! - --expected: Write the final accounts expected by the reference model to this file
+use rust_payment_engine::args::{parse_number, split_option, take_value};
+use rust_payment_engine::model::ReferenceModel;
+use rust_payment_engine::output::{OutputOptions, format_records};
+use rust_payment_engine::workload::{WorkloadConfig, WorkloadGenerator, WorkloadWriter};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::{env, process};
+
+struct Args {
+    config: WorkloadConfig,
+    output: Option<String>,
+    expected: Option<String>,
+}
+
+fn parse_rate(name: &str, value: &str) -> Result<f64, Box<dyn Error>> {
+    let rate: f64 = parse_number(name, value)?;
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(format!("{} must be between 0 and 1", name).into());
+    }
+    Ok(rate)
+}
+
+fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Args, Box<dyn Error>> {
+    let mut args = args.into_iter();
+    let mut config = WorkloadConfig::default();
+    let mut output = None;
+    let mut expected = None;
+
+    while let Some(arg) = args.next() {
+        let Some((name, inline)) = split_option(&arg) else {
+            return Err(format!("unexpected argument {}", arg.to_string_lossy()).into());
+        };
+        let value = take_value(&name, inline, &mut args)?;
+        match name.as_str() {
+            "--seed" => config.seed = parse_number(&name, &value)?,
+            "--rows" => config.rows = parse_number(&name, &value)?,
+            "--clients" => config.clients = parse_number(&name, &value)?,
+            "--mix" => config.mix = value.parse()?,
+            "--duplicate-rate" => config.duplicate_rate = parse_rate(&name, &value)?,
+            "--invalid-rate" => config.invalid_rate = parse_rate(&name, &value)?,
+            "--unknown-tx-rate" => config.unknown_tx_rate = parse_rate(&name, &value)?,
+            "--max-amount" => config.max_amount = parse_number(&name, &value)?,
+            "--output" => output = Some(value),
+            "--expected" => expected = Some(value),
+            other => return Err(format!("unknown option {}", other).into()),
+        }
+    }
+
+    if config.clients == 0 {
+        return Err(From::from("--clients must be at least 1"));
+    }
+    if config.duplicate_rate + config.invalid_rate > 1.0 {
+        return Err(From::from(
+            "--duplicate-rate and --invalid-rate cannot add up to more than 1",
+        ));
+    }
+    if config.max_amount.is_nan() || config.max_amount < 0.0001 {
+        return Err(From::from("--max-amount must be at least 0.0001"));
+    }
+    Ok(Args {
+        config,
+        output,
+        expected,
+    })
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = parse_args(env::args_os().skip(1))?;
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = WorkloadWriter::new(BufWriter::new(output));
+    // The model only runs when expected accounts are requested, it keeps every tx in memory
+    let mut model = args.expected.as_ref().map(|_| ReferenceModel::default());
+
+    for tx in WorkloadGenerator::new(args.config) {
+        writer.write(&tx)?;
+        if let Some(model) = model.as_mut() {
+            model.apply(&tx);
+        }
+    }
+    writer.finish()?.flush()?;
+
+    if let (Some(path), Some(model)) = (args.expected, model) {
+        let accounts = format_records(&model.accounts(), &OutputOptions::default())?;
+        fs::write(path, accounts)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
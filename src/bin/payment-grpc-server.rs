@@ -0,0 +1,57 @@
+//! gRPC API for the payment engine
+//!
+//! Same engine as payment-server, with unary and streaming calls defined in proto/payment_engine.proto
+//!
+//! ## Usage:
+//! ```
+//! cargo run --bin payment-grpc-server -- --listen 127.0.0.1:50051 --data-dir /var/lib/payments
+//! grpcurl -plaintext -import-path proto -proto payment_engine.proto \
+//!     -d '{"type": "TRANSACTION_TYPE_DEPOSIT", "client": 1, "tx": 1, "amount": 2.5}' \
+//!     127.0.0.1:50051 payment_engine.v1.PaymentEngine/Submit
+//! ```
+//!
+//! ## Options:
+//! - --listen: Address to listen on (default 127.0.0.1:50051)
+//! - --data-dir: Directory of the SQLite files, kept between restarts. Required unless --memory is given
+//! - --memory: Keep state in memory only, lost on shutdown
+//! - --locked-allow: Comma separated transaction types still accepted on locked accounts (default none)
+//! - --events: Publish domain events to jsonl:<path> or webhook:<url>, can be repeated
+//! - --rates: Exchange rates used by conversions, a CSV or JSON file (from, to, rate, effective)
+//! - --fee-schedule: Fees charged per transaction type and client tier, a JSON file
+//! - --admin-token-file: File with the token required by administrative operations, which are disabled without it
+use rust_payment_engine::args::parse_server_args;
+use rust_payment_engine::grpc::{PaymentEngineServer, PaymentEngineService};
+use rust_payment_engine::server::Engine;
+use std::error::Error;
+use std::{env, process};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:50051";
+
+async fn run() -> Result<(), Box<dyn Error>> {
+    let mut args = parse_server_args(env::args_os().skip(1), DEFAULT_LISTEN)?;
+    args.options
+        .events
+        .on_failure(|event, err| eprintln!("event {:?} was not published: {}", event, err));
+    let engine = Engine::open(args.data_dir.as_deref(), args.options)?;
+    let listen = args.listen.parse()?;
+    eprintln!("listening on {}", listen);
+    let mut service = PaymentEngineService::new(engine);
+    if let Some(token) = &args.admin_token {
+        service = service.with_admin_token(token);
+    }
+    tonic::transport::Server::builder()
+        .add_service(PaymentEngineServer::new(service))
+        .serve_with_shutdown(listen, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(err) = run().await {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,55 @@
+//! HTTP API for the payment engine
+//!
+//! Applies transactions in real time with the same rules as the CLI, keeping state in SQLite files
+//!
+//! ## Usage:
+//! ```
+//! cargo run --bin payment-server -- --listen 127.0.0.1:8080 --data-dir /var/lib/payments
+//! curl -X POST localhost:8080/transactions -H 'content-type: application/json' \
+//!     -d '{"type": "deposit", "client": 1, "tx": 1, "amount": 2.5}'
+//! curl localhost:8080/accounts/1
+//! ```
+//!
+//! ## Options:
+//! - --listen: Address to listen on (default 127.0.0.1:8080)
+//! - --data-dir: Directory of the SQLite files, kept between restarts. Required unless --memory is given
+//! - --memory: Keep state in memory only, lost on shutdown
+//! - --locked-allow: Comma separated transaction types still accepted on locked accounts (default none)
+//! - --events: Publish domain events to jsonl:<path> or webhook:<url>, can be repeated
+//! - --rates: Exchange rates used by conversions, a CSV or JSON file (from, to, rate, effective)
+//! - --fee-schedule: Fees charged per transaction type and client tier, a JSON file
+//! - --admin-token-file: File with the token required by administrative operations, which are disabled without it
+use rust_payment_engine::args::parse_server_args;
+use rust_payment_engine::server::{AppState, Engine, router};
+use std::error::Error;
+use std::{env, process};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+async fn run() -> Result<(), Box<dyn Error>> {
+    let mut args = parse_server_args(env::args_os().skip(1), DEFAULT_LISTEN)?;
+    args.options
+        .events
+        .on_failure(|event, err| eprintln!("event {:?} was not published: {}", event, err));
+    let engine = Engine::open(args.data_dir.as_deref(), args.options)?;
+    let listener = tokio::net::TcpListener::bind(&args.listen).await?;
+    eprintln!("listening on {}", listener.local_addr()?);
+    let mut state = AppState::new(engine);
+    if let Some(token) = &args.admin_token {
+        state = state.with_admin_token(token);
+    }
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(err) = run().await {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,233 @@
+use rust_payment_engine::args::{parse_number, split_option, take_value};
+use rust_payment_engine::csv_processor::ProcessOptions;
+use rust_payment_engine::domain::{TransactionType, parse_duration, parse_timestamp};
+use rust_payment_engine::events::sink_from_spec;
+use rust_payment_engine::fees::FeeSchedule;
+use rust_payment_engine::output::OutputOptions;
+use rust_payment_engine::rates::RateTable;
+use rust_payment_engine::replay::AsOf;
+use std::error::Error;
+use std::ffi::OsString;
+
+/// Commands accepted by the CLI, given before the input file
+///
+/// # Commands:
+///
+/// - Accounts: Output the final state of all accounts (default, no command name needed)
+/// - History: Output the ledger of the client given by --client
+/// - Replay: Rebuild accounts from the event log, up to --up-to-seq if given, and output them
+/// - Balance: Output the accounts given by --client as they were at --as-of-tx, --as-of-line or --as-of-time
+/// - Fees: Output the fees charged, totalled per client, currency and transaction type
+/// - NegativeBalance: Output the accounts with negative Available funds, owed by their client
+pub enum Command {
+    Accounts,
+    History { client_id: u16 },
+    Replay { up_to_seq: Option<i64> },
+    Balance { client_id: u16, as_of: AsOf },
+    Fees,
+    NegativeBalance,
+}
+
+/// Arguments accepted by the CLI
+///
+/// # Options:
+///
+/// - --output-format: csv (default), json, jsonl or table
+/// - --sort: client (default), total or available. Amount orders are descending
+/// - --precision: Decimal places for amounts (default the ones of their currency, 4 without currency)
+/// - --columns: Comma separated list of columns to output (default all)
+/// - --client: Client ID, required by the history and balance commands
+/// - --up-to-seq: Last event log sequence number applied by the replay command
+/// - --as-of-tx / --as-of-line / --as-of-time: Point of the balance command, right after a tx ID, input line
+///   or timestamp (RFC 3339 or epoch milliseconds) was applied
+/// - --event-time: Apply rows in timestamp order, waiting up to this lateness (e.g. 30s or 5m) for out of order rows
+/// - --dispute-window: Reject disputes of transactions older than this, a duration (e.g. 30d) or row distance (e.g. 5000rows)
+/// - --dispute-expiry: Close disputes open longer than this, same format as --dispute-window
+/// - --dispute-expiry-action: resolve (default) or chargeback, applied to expired disputes
+/// - --allow-negative: Let disputes hold funds the client already spent, leaving Available negative
+/// - --paranoid: Verify account invariants after every transaction
+/// - --admin: Privileged CSV with administrative operations, applied after the input file
+/// - --locked-allow: Comma separated transaction types still accepted on locked accounts (default none)
+/// - --rejections: Write rows that were not applied, and why, to this CSV file
+/// - --events: Publish domain events to jsonl:<path> or webhook:<url>, can be repeated
+/// - --rates: Exchange rates used by conversions, a CSV or JSON file
+/// - --fee-schedule: Fees charged per transaction type and client tier, a JSON file
+/// - --chargeback-fee: Flat fee charged on every chargeback, replacing the chargeback rule without tier of the schedule
+pub struct Args {
+    pub command: Command,
+    pub input: OsString,
+    pub admin_input: Option<OsString>,
+    pub rejections_output: Option<OsString>,
+    pub process: ProcessOptions,
+    pub output: OutputOptions,
+}
+
+pub fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Args, Box<dyn Error>> {
+    let mut args = args.into_iter();
+    let mut positionals = Vec::new();
+    let mut process = ProcessOptions::default();
+    let mut output = OutputOptions::default();
+    let mut client_id = None;
+    let mut up_to_seq = None;
+    let mut as_of = None;
+    let mut admin_input = None;
+    let mut rejections_output = None;
+    let mut chargeback_fee = None;
+
+    while let Some(arg) = args.next() {
+        let Some((name, inline)) = split_option(&arg) else {
+            positionals.push(arg);
+            continue;
+        };
+
+        match name.as_str() {
+            "--paranoid" => {
+                if inline.is_some() {
+                    return Err(From::from("--paranoid does not take a value"));
+                }
+                process.paranoid = true;
+            }
+            "--allow-negative" => {
+                if inline.is_some() {
+                    return Err(From::from("--allow-negative does not take a value"));
+                }
+                process.disputes.allow_negative = true;
+            }
+            "--output-format" => {
+                output.format = take_value(&name, inline, &mut args)?.parse()?;
+            }
+            "--sort" => {
+                output.order = take_value(&name, inline, &mut args)?.parse()?;
+            }
+            "--precision" => {
+                output.precision =
+                    Some(parse_number(&name, &take_value(&name, inline, &mut args)?)?);
+            }
+            "--columns" => {
+                let value = take_value(&name, inline, &mut args)?;
+                output.columns = Some(value.split(',').map(|c| c.trim().to_string()).collect());
+            }
+            "--admin" => {
+                admin_input = Some(OsString::from(take_value(&name, inline, &mut args)?));
+            }
+            "--locked-allow" => {
+                process.lock_policy = take_value(&name, inline, &mut args)?.parse()?;
+            }
+            "--rejections" => {
+                rejections_output = Some(OsString::from(take_value(&name, inline, &mut args)?));
+            }
+            "--events" => {
+                process
+                    .events
+                    .add_sink(sink_from_spec(&take_value(&name, inline, &mut args)?)?);
+            }
+            "--rates" => {
+                process.rates = RateTable::load(&take_value(&name, inline, &mut args)?)?;
+            }
+            "--fee-schedule" => {
+                process.fees = FeeSchedule::load(&take_value(&name, inline, &mut args)?)?;
+            }
+            "--chargeback-fee" => {
+                chargeback_fee = Some(parse_number(&name, &take_value(&name, inline, &mut args)?)?);
+            }
+            "--client" => {
+                client_id = Some(parse_number(&name, &take_value(&name, inline, &mut args)?)?);
+            }
+            "--up-to-seq" => {
+                up_to_seq = Some(parse_number(&name, &take_value(&name, inline, &mut args)?)?);
+            }
+            "--as-of-tx" | "--as-of-line" | "--as-of-time" if as_of.is_some() => {
+                return Err(From::from(
+                    "only one of --as-of-tx, --as-of-line or --as-of-time can be given",
+                ));
+            }
+            "--as-of-tx" => {
+                as_of = Some(AsOf::Tx(parse_number(
+                    &name,
+                    &take_value(&name, inline, &mut args)?,
+                )?));
+            }
+            "--as-of-line" => {
+                as_of = Some(AsOf::Line(parse_number(
+                    &name,
+                    &take_value(&name, inline, &mut args)?,
+                )?));
+            }
+            "--as-of-time" => {
+                as_of = Some(AsOf::Time(parse_timestamp(&take_value(
+                    &name, inline, &mut args,
+                )?)?));
+            }
+            "--event-time" => {
+                process.event_time = Some(parse_duration(&take_value(&name, inline, &mut args)?)?);
+            }
+            "--dispute-window" => {
+                process.disputes.filing_window =
+                    Some(take_value(&name, inline, &mut args)?.parse()?);
+            }
+            "--dispute-expiry" => {
+                process.disputes.expire_after =
+                    Some(take_value(&name, inline, &mut args)?.parse()?);
+            }
+            "--dispute-expiry-action" => {
+                process.disputes.expiry_action = take_value(&name, inline, &mut args)?.parse()?;
+            }
+            other => return Err(format!("unknown option {}", other).into()),
+        }
+    }
+
+    // Applied once every option is read, so it overrides the schedule whatever the order
+    if let Some(fee) = chargeback_fee {
+        process
+            .fees
+            .set_flat_fee(TransactionType::Chargeback, fee)?;
+    }
+
+    // A leading command name is only taken as such when an input file follows it
+    let command = match positionals.first().and_then(|arg| arg.to_str()) {
+        Some("history") if positionals.len() > 1 => {
+            positionals.remove(0);
+            match client_id {
+                Some(client_id) => Command::History { client_id },
+                None => return Err(From::from("history requires --client")),
+            }
+        }
+        Some("replay") if positionals.len() > 1 => {
+            positionals.remove(0);
+            Command::Replay { up_to_seq }
+        }
+        Some("balance") if positionals.len() > 1 => {
+            positionals.remove(0);
+            match (client_id, as_of) {
+                (Some(client_id), Some(as_of)) => Command::Balance { client_id, as_of },
+                _ => {
+                    return Err(From::from(
+                        "balance requires --client and --as-of-tx, --as-of-line or --as-of-time",
+                    ));
+                }
+            }
+        }
+        Some("fees") if positionals.len() > 1 => {
+            positionals.remove(0);
+            Command::Fees
+        }
+        Some("negative_balance") if positionals.len() > 1 => {
+            positionals.remove(0);
+            Command::NegativeBalance
+        }
+        _ => Command::Accounts,
+    };
+
+    match positionals.len() {
+        0 => Err(From::from("expected 1 argument, but got none")),
+        1 => Ok(Args {
+            command,
+            input: positionals.remove(0),
+            admin_input,
+            rejections_output,
+            process,
+            output,
+        }),
+        _ => Err(From::from("expected only 1 input file")),
+    }
+}
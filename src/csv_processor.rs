@@ -1,104 +1,1167 @@
+use crate::db::TransactionState;
 use crate::db::{ClientAccountDB, TransactionDB};
-use crate::domain::{ClientAccount, Transaction, TransactionType};
-use csv::{ReaderBuilder, Trim, WriterBuilder};
-use std::{error::Error, fs::File};
+use crate::domain::{
+    BALANCE_TOLERANCE, ClientAccount, ClockPoint, DisputePolicy, DisputeState, DomainEvent,
+    JournalEntry, LockPolicy, Rejection, RejectionReason, Transaction, TransactionType,
+    fits_currency_precision, is_valid_amount, round_currency_amount,
+};
+use crate::event_time::EventTimeBuffer;
+use crate::events::EventPublisher;
+use crate::fees::FeeSchedule;
+use crate::output::{OutputOptions, format_accounts, format_history};
+use crate::rates::RateTable;
+use crate::verify::{verify, verify_account, verify_locked_unchanged};
+use csv::{Reader, ReaderBuilder, StringRecord, Trim};
+use std::{error::Error, io::Read};
 
-// Only keep track of Deposit and Withdrawal, as other operations interact with those two
-fn add_transaction_to_db(tx: &Transaction, db: &TransactionDB) -> Result<(), Box<dyn Error>> {
-    db.include_transaction(tx)?;
+// Only keep track of Deposit, Withdrawal and administrative operations, as disputes interact with those
+fn add_transaction_to_db(
+    tx: &Transaction,
+    account: &ClientAccount,
+    db: &TransactionDB,
+) -> Result<(), Box<dyn Error>> {
+    db.include_transaction(tx, account)?;
     Ok(())
 }
 
-fn process_transaction(
+// Amount of a dispute, resolve, chargeback or refund: the one of the row, up to the limit left on the referred tx,
+// or all of that limit when the row has no amount
+fn get_referred_amount(
+    tx: &Transaction,
+    state: &TransactionState,
+    limit: f64,
+) -> Result<f64, RejectionReason> {
+    let limit = round_currency_amount(limit, state.currency.as_deref());
+    let Some(amount) = tx.amount else {
+        return if limit > 0.0 {
+            Ok(limit)
+        } else {
+            Err(RejectionReason::ExceedsRemainingAmount)
+        };
+    };
+    if !is_valid_amount(amount) || !fits_currency_precision(amount, state.currency.as_deref()) {
+        return Err(RejectionReason::InvalidAmount);
+    }
+    if amount > limit + BALANCE_TOLERANCE {
+        return Err(RejectionReason::ExceedsRemainingAmount);
+    }
+    Ok(amount)
+}
+
+// Amount moved by a dispute, resolve or chargeback, and the dispute state the referred transaction moves to.
+// Only found if it belongs to the same client and can be disputed. Disputes take up to the amount not disputed,
+// charged back or refunded yet, and can overlap. Resolves and chargebacks take up to the amount under dispute
+fn get_dispute_transition(
     tx: &Transaction,
     transaction_db: &TransactionDB,
+) -> Result<Result<(f64, DisputeState), RejectionReason>, Box<dyn Error>> {
+    let state = match transaction_db.get_transaction_state(tx.id)? {
+        Some(state) if state.client_id == tx.client_id => state,
+        _ => return Ok(Err(RejectionReason::TransactionNotFound)),
+    };
+    // Debits without enough funds are stored, but were never applied nor logged, so replays would not find them
+    if transaction_db.get_event_point_of_tx(tx.id)?.is_none() {
+        return Ok(Err(RejectionReason::TransactionNotFound));
+    }
+    if !state.transaction_type.is_disputable() {
+        return Ok(Err(RejectionReason::NotDisputable));
+    }
+    let limit = match tx.transaction_type {
+        TransactionType::Dispute => state.remaining(),
+        _ => state.in_dispute(),
+    };
+    // Nothing left to dispute, or no dispute to close
+    if state.amount.is_none() || limit < BALANCE_TOLERANCE {
+        return Ok(Err(RejectionReason::InvalidDisputeState));
+    }
+    let amount = match get_referred_amount(tx, &state, limit) {
+        Ok(amount) => amount,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    let in_dispute = match tx.transaction_type {
+        TransactionType::Dispute => state.in_dispute() + amount,
+        _ => state.in_dispute() - amount,
+    };
+    Ok(Ok((
+        amount,
+        DisputeState::after(tx.transaction_type, in_dispute),
+    )))
+}
+
+// Amount returned by a refund, only found if the referred tx is a deposit of the same client
+fn get_refund_amount(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+) -> Result<Result<f64, RejectionReason>, Box<dyn Error>> {
+    let state = match transaction_db.get_transaction_state(tx.id)? {
+        Some(state) if state.client_id == tx.client_id => state,
+        _ => return Ok(Err(RejectionReason::TransactionNotFound)),
+    };
+    if state.transaction_type != TransactionType::Deposit {
+        return Ok(Err(RejectionReason::NotRefundable));
+    }
+    Ok(get_referred_amount(tx, &state, state.remaining()))
+}
+
+// Amount of a deposit or withdrawal, only found if it is valid in its currency and the tx id was never used before
+fn get_funding_amount(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+) -> Result<Result<f64, RejectionReason>, Box<dyn Error>> {
+    let Some(amount) = tx.amount else {
+        return Ok(Err(RejectionReason::MissingAmount));
+    };
+    if !is_valid_amount(amount) || !fits_currency_precision(amount, tx.currency.as_deref()) {
+        return Ok(Err(RejectionReason::InvalidAmount));
+    }
+    if transaction_db.get_transaction_state(tx.id)?.is_some() {
+        return Ok(Err(RejectionReason::DuplicateTransaction));
+    }
+    Ok(Ok(amount))
+}
+
+// Currency of the account a transaction applies to. Disputes, resolves, chargebacks and refunds use the one of the
+// referred transaction, falling back to their own when it is not found
+fn get_account_currency(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+) -> Result<String, Box<dyn Error>> {
+    if matches!(
+        tx.transaction_type,
+        TransactionType::Dispute
+            | TransactionType::Resolve
+            | TransactionType::Chargeback
+            | TransactionType::Refund
+    ) && let Some(state) = transaction_db.get_transaction_state(tx.id)?
+        && state.client_id == tx.client_id
+    {
+        return Ok(state.currency.unwrap_or_default());
+    }
+    Ok(tx.currency.clone().unwrap_or_default())
+}
+
+// Client whose funds a transaction moves, and the other client of a transfer. Disputes, resolves and chargebacks
+// of a transfer hold the funds where they went, so they apply to the receiving client with the sender on the other side
+fn get_transfer_sides(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+) -> Result<(u16, Option<u16>), Box<dyn Error>> {
+    match tx.transaction_type {
+        TransactionType::Transfer => Ok((tx.client_id, tx.to_client)),
+        TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+            match transaction_db.get_transaction_state(tx.id)? {
+                Some(state)
+                    if state.client_id == tx.client_id
+                        && state.transaction_type == TransactionType::Transfer
+                        && let Some(receiver) = state.to_client =>
+                {
+                    Ok((receiver, Some(tx.client_id)))
+                }
+                _ => Ok((tx.client_id, None)),
+            }
+        }
+        _ => Ok((tx.client_id, None)),
+    }
+}
+
+/// Clients whose accounts a transaction can change, the sending client first
+///
+/// # Notes:
+///
+/// - Transfers and their disputes, resolves and chargebacks change both the sending and the receiving client
+pub fn get_involved_clients(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+) -> Result<Vec<u16>, Box<dyn Error>> {
+    let (client_id, counterpart_id) = get_transfer_sides(tx, transaction_db)?;
+    let mut clients = vec![tx.client_id];
+    for id in std::iter::once(client_id).chain(counterpart_id) {
+        if !clients.contains(&id) {
+            clients.push(id);
+        }
+    }
+    Ok(clients)
+}
+
+fn get_or_create_account(
+    client_id: u16,
+    currency: &str,
     client_account_db: &ClientAccountDB,
-) -> Result<(), Box<dyn Error>> {
-    let account_exists = client_account_db.does_account_exist(tx.client_id)?;
+) -> Result<ClientAccount, Box<dyn Error>> {
+    if let Some(account) = client_account_db.get_currency_account(client_id, currency)? {
+        return Ok(account);
+    }
+    // Locks apply to the client, so a new currency of a locked client starts locked
+    let mut new_account = ClientAccount::with_currency(client_id, currency);
+    if client_account_db
+        .get_client_accounts(client_id)?
+        .iter()
+        .any(ClientAccount::is_locked)
+    {
+        new_account.lock_account();
+    }
+    client_account_db.include_client_account(&new_account)?;
+    Ok(new_account)
+}
+
+/// Apply a single client transaction
+///
+/// Returns the rejection reason when the transaction was not applied.
+/// Errors are only returned for storage failures
+pub fn process_transaction(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+) -> Result<Option<RejectionReason>, Box<dyn Error>> {
+    process_transaction_at_line(tx, None, transaction_db, client_account_db, options)
+}
+
+/// Same as `process_transaction`, for a transaction read from the given CSV line.
+/// The line is kept in the event log, for point-in-time queries
+pub fn process_transaction_at_line(
+    tx: &Transaction,
+    line: Option<u64>,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+) -> Result<Option<RejectionReason>, Box<dyn Error>> {
+    expire_disputes(tx, transaction_db, client_account_db, options)?;
+    apply_input(tx, line, transaction_db, client_account_db, options)
+}
+
+// Operator recorded on the resolves and chargebacks that close expired disputes
+const EXPIRY_OPERATOR: &str = "dispute-expiry";
+
+// Point of an input on the dispute clock. It gets the next sequence number of the event log if accepted
+fn get_clock_point(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+) -> Result<ClockPoint, Box<dyn Error>> {
+    Ok(ClockPoint {
+        seq: transaction_db.get_last_event_seq()?.unwrap_or(0) + 1,
+        timestamp: tx.timestamp,
+    })
+}
 
-    if !account_exists {
-        let new_account = ClientAccount::new(tx.client_id);
-        client_account_db.include_client_account(&new_account)?;
+/// Close the disputes open longer than `DisputePolicy::expire_after`, as seen from the input about to be processed
+///
+/// Returns the synthetic resolves or chargebacks that were applied, oldest dispute first
+///
+/// Each partial dispute of a tx expires on its own, releasing only the amount it still holds
+///
+/// # Notes:
+///
+/// - Synthetic inputs are recorded in the journal and the event log like any other, with operator "dispute-expiry"
+///   and the timestamp of the input that moved the clock
+/// - They are applied even on locked accounts, so held funds are never stranded by an expired dispute
+pub fn expire_disputes(
+    now: &Transaction,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+) -> Result<Vec<Transaction>, Box<dyn Error>> {
+    let Some(window) = options.disputes.expire_after else {
+        return Ok(Vec::new());
+    };
+    let now_point = get_clock_point(now, transaction_db)?;
+    let expiry_options = ProcessOptions {
+        lock_policy: LockPolicy::allow(&[TransactionType::Resolve, TransactionType::Chargeback]),
+        ..options.clone()
+    };
+
+    let mut expired = Vec::new();
+    for dispute in transaction_db.get_open_disputes()? {
+        if !window.is_exceeded(dispute.opened, now_point) {
+            continue;
+        }
+        let expiry = Transaction {
+            transaction_type: options.disputes.expiry_action.transaction_type(),
+            client_id: dispute.client_id,
+            id: dispute.id,
+            amount: Some(dispute.amount),
+            operator: Some(EXPIRY_OPERATOR.to_string()),
+            reason: Some("dispute expired".to_string()),
+            timestamp: now.timestamp,
+            currency: None,
+            to_currency: None,
+            rate: None,
+            to_client: None,
+            fee: None,
+        };
+        if let Some(reason) = apply_input(
+            &expiry,
+            None,
+            transaction_db,
+            client_account_db,
+            &expiry_options,
+        )? {
+            return Err(format!(
+                "expiry of the dispute on tx {} was rejected: {}",
+                dispute.id, reason
+            )
+            .into());
+        }
+        expired.push(expiry);
     }
+    Ok(expired)
+}
 
-    let mut account = client_account_db.get_account(tx.client_id)?;
+// Fee the schedule sets for an input, rounded to the currency of its account. Disputes, resolves, chargebacks and
+// refunds without amount are priced on the amount of the tx they refer to
+fn get_scheduled_fee(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+    fees: &FeeSchedule,
+) -> Result<Option<f64>, Box<dyn Error>> {
+    if !fees.has_fees(tx.transaction_type) {
+        return Ok(None);
+    }
+    let amount = match (tx.transaction_type, tx.amount) {
+        (
+            TransactionType::Dispute
+            | TransactionType::Resolve
+            | TransactionType::Chargeback
+            | TransactionType::Refund,
+            None,
+        ) => transaction_db
+            .get_transaction_state(tx.id)?
+            .filter(|state| state.client_id == tx.client_id)
+            .and_then(|state| state.amount),
+        (_, amount) => amount.filter(|amount| is_valid_amount(*amount)),
+    };
+    let Some(fee) =
+        amount.and_then(|amount| fees.get_fee(tx.transaction_type, tx.client_id, amount))
+    else {
+        return Ok(None);
+    };
+    let currency = get_account_currency(tx, transaction_db)?;
+    Ok(Some(round_currency_amount(fee, Some(&currency))).filter(|fee| *fee > 0.0))
+}
 
-    // Skip any transaction if account is locked
-    if account.is_locked() {
-        return Ok(());
+// Input with the rate and fee the engine sets on it: conversions get the rate of the rate table in effect at their
+// timestamp, and any type the fee of the fee schedule. None when nothing changes, e.g. for inputs replayed from the
+// event log, which already have them
+fn price_transaction(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+    options: &ProcessOptions,
+) -> Result<Option<Transaction>, Box<dyn Error>> {
+    let rate = match (tx.transaction_type, tx.rate, &tx.currency, &tx.to_currency) {
+        (TransactionType::Convert, None, Some(from), Some(to)) => {
+            options.rates.get_rate(from, to, tx.timestamp)
+        }
+        _ => tx.rate,
+    };
+    // Partial disputes, resolves, chargebacks and refunds repeat the tx and type, only the first one charged pays
+    let fee = if transaction_db.has_fee(tx)? {
+        None
+    } else {
+        match tx.fee {
+            Some(fee) => Some(fee),
+            None => get_scheduled_fee(tx, transaction_db, &options.fees)?,
+        }
+    };
+    if rate == tx.rate && fee == tx.fee {
+        return Ok(None);
+    }
+    Ok(Some(Transaction {
+        rate,
+        fee,
+        ..tx.clone()
+    }))
+}
+
+// Apply an input without advancing the dispute clock, logging it if accepted.
+// Inputs are applied and logged with their rate and fee, so replays do not need the rate table or fee schedule.
+// Events are only published once the input is applied, charged and logged
+fn apply_input(
+    tx: &Transaction,
+    line: Option<u64>,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+) -> Result<Option<RejectionReason>, Box<dyn Error>> {
+    let priced = price_transaction(tx, transaction_db, options)?;
+    let tx = priced.as_ref().unwrap_or(tx);
+    let mut events = Vec::new();
+    let rejection = apply_transaction(tx, transaction_db, client_account_db, options, &mut events)?;
+    if rejection.is_none() {
+        match tx.fee {
+            // Logged with the fee actually charged, which replays charge in full
+            Some(fee) => {
+                let charged = charge_fee(
+                    tx,
+                    fee,
+                    transaction_db,
+                    client_account_db,
+                    options,
+                    &mut events,
+                )?;
+                transaction_db.append_to_event_log(
+                    &Transaction {
+                        fee: charged,
+                        ..tx.clone()
+                    },
+                    line,
+                )?;
+            }
+            None => {
+                transaction_db.append_to_event_log(tx, line)?;
+            }
+        }
+    }
+    if let Some(reason) = rejection
+        && tx.transaction_type == TransactionType::Withdrawal
+    {
+        events.push(DomainEvent::WithdrawalRejected {
+            client: tx.client_id,
+            tx: tx.id,
+            amount: tx.amount,
+            currency: tx.currency.clone().unwrap_or_default(),
+            reason,
+        });
+    }
+    for event in &events {
+        options.events.publish(event);
+    }
+    Ok(rejection)
+}
+
+// Debits need Available funds for their fee too, which is charged once they are applied
+fn withdraw_with_fee(account: &mut ClientAccount, amount: f64, fee: Option<f64>) -> bool {
+    let needed = round_currency_amount(amount + fee.unwrap_or(0.0), Some(account.currency()));
+    account.available() >= needed && account.withdraw_funds(amount).is_ok()
+}
+
+// Fees are charged to the client that sent the tx, once the tx was applied. Other than debits, which already checked
+// their fee is covered, transactions are only charged up to the Available funds. Only chargeback fees can make them
+// negative, when the dispute policy allows negative balances. Returns the fee charged, if any
+fn charge_fee(
+    tx: &Transaction,
+    fee: f64,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+    events: &mut Vec<DomainEvent>,
+) -> Result<Option<f64>, Box<dyn Error>> {
+    let currency = get_account_currency(tx, transaction_db)?;
+    let mut account = get_or_create_account(tx.client_id, &currency, client_account_db)?;
+    let charged =
+        if tx.transaction_type == TransactionType::Chargeback && options.disputes.allow_negative {
+            fee
+        } else {
+            round_currency_amount(fee.min(account.available()), Some(&currency))
+        };
+    if charged <= 0.0 {
+        return Ok(None);
+    }
+    account.charge_funds(charged);
+    client_account_db.update_client_account(&account)?;
+    transaction_db.record_fee(tx, tx.client_id, &currency, charged)?;
+    transaction_db.record_journal_entry(
+        &JournalEntry::fee(tx.id, tx.transaction_type, tx.client_id, charged)
+            .in_currency(&currency),
+    )?;
+    events.push(DomainEvent::FeeCharged {
+        client: tx.client_id,
+        tx: tx.id,
+        amount: charged,
+        currency: currency.clone(),
+    });
+    Ok(Some(charged))
+}
+
+// Events of accepted transactions are collected here, once the change is stored
+fn apply_transaction(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+    events: &mut Vec<DomainEvent>,
+) -> Result<Option<RejectionReason>, Box<dyn Error>> {
+    // Administrative operations are only accepted through process_admin_operation
+    if tx.transaction_type.is_admin() {
+        return Ok(Some(RejectionReason::AdminOnly));
+    }
+
+    let (client_id, counterpart_id) = get_transfer_sides(tx, transaction_db)?;
+    if tx.transaction_type == TransactionType::Transfer
+        && counterpart_id.is_none_or(|to_client| to_client == client_id)
+    {
+        return Ok(Some(RejectionReason::InvalidTransfer));
+    }
+
+    let currency = get_account_currency(tx, transaction_db)?;
+    let mut account = get_or_create_account(client_id, &currency, client_account_db)?;
+    let mut counterpart = counterpart_id
+        .map(|id| get_or_create_account(id, &currency, client_account_db))
+        .transpose()?;
+
+    // Skip transactions on locked accounts, unless the lock policy allows the type. Transfers check both clients
+    let locked = account.is_locked() || counterpart.as_ref().is_some_and(ClientAccount::is_locked);
+    if locked && !options.lock_policy.allows(tx.transaction_type) {
+        return Ok(Some(RejectionReason::AccountLocked));
     }
 
     match tx.transaction_type {
         TransactionType::Deposit => {
-            if let Some(amount) = tx.amount {
-                account.add_funds(amount);
-                client_account_db.update_client_account(&account)?;
-                add_transaction_to_db(&tx, transaction_db)?;
-            }
+            let amount = match get_funding_amount(tx, transaction_db)? {
+                Ok(amount) => amount,
+                Err(reason) => return Ok(Some(reason)),
+            };
+            account.add_funds(amount);
+            client_account_db.update_client_account(&account)?;
+            add_transaction_to_db(tx, &account, transaction_db)?;
+            transaction_db.record_journal_entry(
+                &JournalEntry::deposit(tx.id, tx.client_id, amount).in_currency(&currency),
+            )?;
+            events.push(DomainEvent::FundsDeposited {
+                client: tx.client_id,
+                tx: tx.id,
+                amount,
+                currency: currency.clone(),
+            });
         }
         TransactionType::Withdrawal => {
-            if let Some(amount) = tx.amount {
-                if account.withdraw_funds(amount).is_ok() {
-                    client_account_db.update_client_account(&account)?;
-                }
-                add_transaction_to_db(&tx, transaction_db)?;
+            let amount = match get_funding_amount(tx, transaction_db)? {
+                Ok(amount) => amount,
+                Err(reason) => return Ok(Some(reason)),
+            };
+            // Failed withdrawals are still stored, so their tx id cannot be reused
+            if !withdraw_with_fee(&mut account, amount, tx.fee) {
+                transaction_db.include_rejected_transaction(tx)?;
+                return Ok(Some(RejectionReason::InsufficientFunds));
             }
+            client_account_db.update_client_account(&account)?;
+            transaction_db.record_journal_entry(
+                &JournalEntry::withdrawal(tx.id, tx.client_id, amount).in_currency(&currency),
+            )?;
+            add_transaction_to_db(tx, &account, transaction_db)?;
         }
         TransactionType::Dispute => {
-            if let Some(amount) = transaction_db.get_amount(tx.id)? {
-                if account.hold_funds(amount).is_ok() {
-                    client_account_db.update_client_account(&account)?;
-                    transaction_db.mark_disputed(tx.id, true)?;
-                }
+            let (amount, next_state) = match get_dispute_transition(tx, transaction_db)? {
+                Ok(transition) => transition,
+                Err(reason) => return Ok(Some(reason)),
+            };
+            if let Some(window) = options.disputes.filing_window
+                && let Some(filed) = transaction_db.get_event_point_of_tx(tx.id)?
+                && window.is_exceeded(filed, get_clock_point(tx, transaction_db)?)
+            {
+                return Ok(Some(RejectionReason::DisputeWindowClosed));
+            }
+            if options.disputes.allow_negative {
+                account.hold_funds_as_debt(amount);
+            } else if account.hold_funds(amount).is_err() {
+                return Ok(Some(RejectionReason::InsufficientFunds));
             }
+            client_account_db.update_client_account(&account)?;
+            transaction_db.set_dispute_state(tx.id, next_state)?;
+            transaction_db.add_referred_amount(tx.id, tx.transaction_type, amount)?;
+            transaction_db.open_dispute_hold(
+                tx.id,
+                get_clock_point(tx, transaction_db)?,
+                amount,
+            )?;
+            transaction_db.record_history(tx, Some(amount), &account)?;
+            transaction_db.record_journal_entry(
+                &JournalEntry::dispute(tx.id, account.id(), amount).in_currency(&currency),
+            )?;
+            events.push(DomainEvent::DisputeOpened {
+                client: account.id(),
+                tx: tx.id,
+                amount,
+                currency: currency.clone(),
+            });
         }
         TransactionType::Resolve => {
-            if let Some(amount) = transaction_db.get_amount(tx.id)? {
-                if account.resolve_funds(amount).is_ok() {
-                    client_account_db.update_client_account(&account)?;
-                    transaction_db.mark_disputed(tx.id, false)?;
-                }
+            let (amount, next_state) = match get_dispute_transition(tx, transaction_db)? {
+                Ok(transition) => transition,
+                Err(reason) => return Ok(Some(reason)),
+            };
+            if account.resolve_funds(amount).is_err() {
+                return Ok(Some(RejectionReason::InsufficientHeldFunds));
             }
+            client_account_db.update_client_account(&account)?;
+            transaction_db.set_dispute_state(tx.id, next_state)?;
+            transaction_db.add_referred_amount(tx.id, tx.transaction_type, amount)?;
+            transaction_db.release_dispute_holds(tx.id, amount, &currency)?;
+            transaction_db.record_history(tx, Some(amount), &account)?;
+            transaction_db.record_journal_entry(
+                &JournalEntry::resolve(tx.id, account.id(), amount).in_currency(&currency),
+            )?;
+            events.push(DomainEvent::DisputeResolved {
+                client: account.id(),
+                tx: tx.id,
+                amount,
+                currency: currency.clone(),
+            });
         }
         TransactionType::Chargeback => {
-            if let Some(amount) = transaction_db.get_amount(tx.id)? {
-                if account.withdraw_from_held(amount).is_ok() {
-                    account.lock_account();
+            let (amount, next_state) = match get_dispute_transition(tx, transaction_db)? {
+                Ok(transition) => transition,
+                Err(reason) => return Ok(Some(reason)),
+            };
+            if account.withdraw_from_held(amount).is_err() {
+                return Ok(Some(RejectionReason::InsufficientHeldFunds));
+            }
+            // Already locked when the lock policy accepts chargebacks on locked accounts
+            let newly_locked = !account.is_locked();
+            account.lock_account();
+            let entry = match counterpart.as_mut() {
+                // A charged back transfer returns the funds to the sender
+                Some(sender) => {
+                    sender.add_funds(amount);
+                    client_account_db.update_client_accounts(&[&account, sender])?;
+                    JournalEntry::transfer_chargeback(tx.id, account.id(), sender.id(), amount)
+                }
+                None => {
                     client_account_db.update_client_account(&account)?;
+                    JournalEntry::chargeback(tx.id, account.id(), amount)
                 }
+            };
+            client_account_db.set_client_locked(account.id(), true)?;
+            transaction_db.set_dispute_state(tx.id, next_state)?;
+            transaction_db.add_referred_amount(tx.id, tx.transaction_type, amount)?;
+            transaction_db.release_dispute_holds(tx.id, amount, &currency)?;
+            transaction_db.record_history(tx, Some(amount), &account)?;
+            // The sender of a charged back transfer lists the funds it got back
+            if let Some(sender) = counterpart.as_ref() {
+                transaction_db.record_history(tx, Some(amount), sender)?;
+            }
+            transaction_db.record_journal_entry(&entry.in_currency(&currency))?;
+            events.push(DomainEvent::ChargebackApplied {
+                client: account.id(),
+                tx: tx.id,
+                amount,
+                currency: currency.clone(),
+            });
+            if newly_locked {
+                events.push(DomainEvent::AccountLocked {
+                    client: account.id(),
+                    tx: tx.id,
+                });
+            }
+        }
+        TransactionType::Convert => {
+            let amount = match get_funding_amount(tx, transaction_db)? {
+                Ok(amount) => amount,
+                Err(reason) => return Ok(Some(reason)),
+            };
+            let Some(to_currency) = tx
+                .to_currency
+                .as_deref()
+                .filter(|to_currency| !currency.is_empty() && *to_currency != currency)
+            else {
+                return Ok(Some(RejectionReason::InvalidConversion));
+            };
+            let Some(rate) = tx.rate else {
+                return Ok(Some(RejectionReason::RateNotFound));
+            };
+            let converted = round_currency_amount(amount * rate, Some(to_currency));
+            if !is_valid_amount(converted) {
+                return Ok(Some(RejectionReason::InvalidAmount));
+            }
+            // Same as withdrawals, a conversion without enough funds is still stored
+            if !withdraw_with_fee(&mut account, amount, tx.fee) {
+                transaction_db.include_rejected_transaction(tx)?;
+                return Ok(Some(RejectionReason::InsufficientFunds));
+            }
+            let mut target = get_or_create_account(tx.client_id, to_currency, client_account_db)?;
+            target.add_funds(converted);
+            // Both currencies are updated together, then the conversion and its journal entry
+            client_account_db.update_client_accounts(&[&account, &target])?;
+            transaction_db.include_transaction_with_entry(
+                tx,
+                &account,
+                &JournalEntry::conversion(
+                    tx.id,
+                    tx.client_id,
+                    (&currency, amount),
+                    (to_currency, converted),
+                ),
+            )?;
+            events.push(DomainEvent::FundsConverted {
+                client: tx.client_id,
+                tx: tx.id,
+                from: currency.clone(),
+                to: to_currency.to_string(),
+                amount,
+                converted,
+                rate,
+            });
+        }
+        TransactionType::Transfer => {
+            let amount = match get_funding_amount(tx, transaction_db)? {
+                Ok(amount) => amount,
+                Err(reason) => return Ok(Some(reason)),
+            };
+            let Some(receiver) = counterpart.as_mut() else {
+                return Ok(Some(RejectionReason::InvalidTransfer));
+            };
+            // Same as withdrawals, a transfer without enough funds is still stored
+            if !withdraw_with_fee(&mut account, amount, tx.fee) {
+                transaction_db.include_rejected_transaction(tx)?;
+                return Ok(Some(RejectionReason::InsufficientFunds));
+            }
+            receiver.add_funds(amount);
+            client_account_db.update_client_accounts(&[&account, receiver])?;
+            transaction_db.record_journal_entry(
+                &JournalEntry::client_transfer(tx.id, account.id(), receiver.id(), amount)
+                    .in_currency(&currency),
+            )?;
+            add_transaction_to_db(tx, &account, transaction_db)?;
+            // The receiving client lists the transfer too, with its own balances
+            transaction_db.record_history(tx, Some(amount), receiver)?;
+            events.push(DomainEvent::FundsTransferred {
+                client: tx.client_id,
+                to_client: receiver.id(),
+                tx: tx.id,
+                amount,
+                currency: currency.clone(),
+            });
+        }
+        TransactionType::Refund => {
+            let amount = match get_refund_amount(tx, transaction_db)? {
+                Ok(amount) => amount,
+                Err(reason) => return Ok(Some(reason)),
+            };
+            if !withdraw_with_fee(&mut account, amount, tx.fee) {
+                return Ok(Some(RejectionReason::InsufficientFunds));
+            }
+            client_account_db.update_client_account(&account)?;
+            transaction_db.add_referred_amount(tx.id, tx.transaction_type, amount)?;
+            transaction_db.record_history(tx, Some(amount), &account)?;
+            transaction_db.record_journal_entry(
+                &JournalEntry::refund(tx.id, tx.client_id, amount).in_currency(&currency),
+            )?;
+            events.push(DomainEvent::FundsRefunded {
+                client: tx.client_id,
+                tx: tx.id,
+                amount,
+                currency: currency.clone(),
+            });
+        }
+        TransactionType::Lock | TransactionType::Unlock | TransactionType::Adjustment => {}
+    }
+
+    Ok(None)
+}
+
+/// Apply an administrative operation (lock, unlock or adjustment) from a privileged source
+///
+/// # Notes:
+///
+/// - Applied even if the account is locked, as unlocking and correcting frozen accounts is their purpose
+/// - Always recorded in `TransactionDB` and its event log, together with the operator and reason
+/// - Adjustments are never disputable, and do not change the dispute state of other transactions
+pub fn process_admin_operation(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+) -> Result<(), Box<dyn Error>> {
+    process_admin_operation_with_options(
+        tx,
+        transaction_db,
+        client_account_db,
+        &ProcessOptions::default(),
+    )
+}
+
+/// Why an administrative operation cannot be applied, or Ok when it can
+///
+/// # Notes:
+///
+/// - The outer error is a storage failure, the inner one describes the invalid operation
+pub fn check_admin_operation(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+) -> Result<Result<(), String>, Box<dyn Error>> {
+    if !tx.transaction_type.is_admin() {
+        return Ok(Err(format!(
+            "tx {} is not an administrative operation, expected lock, unlock or adjustment",
+            tx.id
+        )));
+    }
+    if tx.operator.as_deref().is_none_or(str::is_empty) {
+        return Ok(Err(format!(
+            "administrative tx {} requires an operator",
+            tx.id
+        )));
+    }
+    if transaction_db.get_transaction_state(tx.id)?.is_some() {
+        return Ok(Err(format!(
+            "administrative tx {} reuses an existing tx id",
+            tx.id
+        )));
+    }
+    if tx.transaction_type == TransactionType::Adjustment {
+        let currency = tx.currency.as_deref().unwrap_or_default();
+        match tx.amount {
+            None => return Ok(Err(format!("adjustment tx {} requires an amount", tx.id))),
+            Some(amount)
+                if !is_valid_amount(amount.abs())
+                    || !fits_currency_precision(amount, Some(currency)) =>
+            {
+                return Ok(Err(format!(
+                    "adjustment tx {} has an invalid amount",
+                    tx.id
+                )));
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// Same as `process_admin_operation`, publishing the locks and unlocks to the event sinks of the options
+pub fn process_admin_operation_with_options(
+    tx: &Transaction,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+) -> Result<(), Box<dyn Error>> {
+    if let Err(message) = check_admin_operation(tx, transaction_db)? {
+        return Err(message.into());
+    }
+
+    let currency = tx.currency.clone().unwrap_or_default();
+    let mut account = get_or_create_account(tx.client_id, &currency, client_account_db)?;
+
+    // Locks apply to every currency account of the client
+    let mut event = None;
+    let mut applied = true;
+    match tx.transaction_type {
+        TransactionType::Lock => {
+            account.lock_account();
+            client_account_db.set_client_locked(tx.client_id, true)?;
+            event = Some(DomainEvent::AccountLocked {
+                client: tx.client_id,
+                tx: tx.id,
+            });
+        }
+        TransactionType::Unlock => {
+            account.unlock_account();
+            client_account_db.set_client_locked(tx.client_id, false)?;
+            event = Some(DomainEvent::AccountUnlocked {
+                client: tx.client_id,
+                tx: tx.id,
+            });
+        }
+        TransactionType::Adjustment => {
+            // Checked by check_admin_operation
+            let amount = tx.amount.unwrap_or_default();
+            applied = account.adjust_funds(amount).is_ok();
+            if applied {
+                client_account_db.update_client_account(&account)?;
+                transaction_db.record_journal_entry(
+                    &JournalEntry::adjustment(tx.id, tx.client_id, amount).in_currency(&currency),
+                )?;
+            }
+        }
+        _ => {}
+    }
+    if applied {
+        add_transaction_to_db(tx, &account, transaction_db)?;
+    } else {
+        transaction_db.include_rejected_transaction(tx)?;
+    }
+    transaction_db.append_to_event_log(tx, None)?;
+    if let Some(event) = event {
+        options.events.publish(&event);
+    }
+
+    Ok(())
+}
+
+/// Options that change how transactions are processed
+///
+/// # Options:
+///
+/// - paranoid: Verify the account invariants after every transaction, failing on the first violating row
+/// - lock_policy: Transaction types still accepted on locked accounts (default none)
+/// - events: Sinks notified of every account change, e.g. for notification or fraud systems (default none)
+/// - event_time: Apply CSV rows in timestamp order, waiting up to this lateness in milliseconds for out of order rows.
+///   Rows arriving later than that are rejected. Every row needs a timestamp (default off, rows applied as read)
+/// - disputes: Filing window and expiry of disputes (default none, disputes can refer to any tx and stay open)
+/// - rates: Exchange rates used by conversions (default empty, conversions are rejected)
+/// - fees: Fees charged per transaction type and client tier, posted to the house account (default none)
+#[derive(Debug, Clone, Default)]
+pub struct ProcessOptions {
+    pub paranoid: bool,
+    pub lock_policy: LockPolicy,
+    pub events: EventPublisher,
+    pub event_time: Option<i64>,
+    pub disputes: DisputePolicy,
+    pub rates: RateTable,
+    pub fees: FeeSchedule,
+}
+
+/// Reads client transactions from a CSV, one row at a time
+///
+/// # Notes:
+///
+/// - Yields the CSV line of each row together with its transaction
+/// - Amounts are rounded to 4 decimal places, same as the stored balances
+pub struct TransactionReader<R: Read> {
+    rdr: Reader<R>,
+    headers: StringRecord,
+    row: StringRecord,
+}
+
+impl<R: Read> TransactionReader<R> {
+    pub fn new(reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut rdr = ReaderBuilder::new().trim(Trim::All).from_reader(reader); // Use Trim::All to remove possible whitespaces
+        let headers = rdr.headers()?.clone();
+        Ok(TransactionReader {
+            rdr,
+            headers,
+            row: StringRecord::new(),
+        })
+    }
+}
+
+impl<R: Read> Iterator for TransactionReader<R> {
+    type Item = Result<(Option<u64>, Transaction), Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.rdr.read_record(&mut self.row) {
+            Ok(false) => None,
+            Err(err) => Some(Err(err.into())),
+            Ok(true) => {
+                let line = self.row.position().map(|position| position.line());
+                let result = self
+                    .row
+                    .deserialize::<Transaction>(Some(&self.headers))
+                    .map(|record| (line, record));
+                Some(result.map_err(Into::into))
             }
         }
     }
+}
 
+pub fn process_csv<R: Read>(
+    reader: R,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+) -> Result<Vec<Rejection>, Box<dyn Error>> {
+    process_csv_with_options(
+        reader,
+        transaction_db,
+        client_account_db,
+        &ProcessOptions::default(),
+    )
+}
+
+// Account states before the transaction, only needed to check that locked accounts do not change
+// when the lock policy blocks the transaction type. Only accounts of locked clients are kept
+fn get_locked_accounts(
+    clients: &[u16],
+    client_account_db: &ClientAccountDB,
+) -> Result<Vec<ClientAccount>, Box<dyn Error>> {
+    let mut locked = Vec::new();
+    for &client_id in clients {
+        let accounts = client_account_db.get_client_accounts(client_id)?;
+        if accounts.iter().any(ClientAccount::is_locked) {
+            locked.extend(accounts);
+        }
+    }
+    Ok(locked)
+}
+
+fn check_invariants(
+    clients: &[u16],
+    locked_before: Vec<ClientAccount>,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+) -> Result<(), Box<dyn Error>> {
+    // Skipped rows may not have created an account, then there is nothing to check
+    for &client_id in clients {
+        for account in client_account_db.get_client_accounts(client_id)? {
+            if let Some(before) = locked_before.iter().find(|before| {
+                before.id() == account.id() && before.currency() == account.currency()
+            }) {
+                verify_locked_unchanged(before, &account)?;
+            }
+            verify_account(&account, transaction_db)?;
+        }
+    }
     Ok(())
 }
 
-pub fn process_csv(
-    file: File,
+pub fn process_csv_with_options<R: Read>(
+    reader: R,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+) -> Result<Vec<Rejection>, Box<dyn Error>> {
+    let mut rejections = Vec::new();
+    let mut buffer = options.event_time.map(EventTimeBuffer::new);
+    for result in TransactionReader::new(reader)? {
+        let (line, record) = result?;
+
+        let Some(buffer) = buffer.as_mut() else {
+            apply_row(
+                &record,
+                line,
+                transaction_db,
+                client_account_db,
+                options,
+                &mut rejections,
+            )?;
+            continue;
+        };
+        let Some(timestamp) = record.timestamp else {
+            return Err(format!(
+                "line {}: event time mode requires a timestamp",
+                line.unwrap_or(0)
+            )
+            .into());
+        };
+        if let Err(late) = buffer.push(timestamp, line, record) {
+            let (line, record) = *late;
+            rejections.push(Rejection {
+                line,
+                transaction_type: record.transaction_type,
+                client_id: record.client_id,
+                id: record.id,
+                reason: RejectionReason::LateArrival,
+            });
+        }
+        while let Some((line, record)) = buffer.pop_ready() {
+            apply_row(
+                &record,
+                line,
+                transaction_db,
+                client_account_db,
+                options,
+                &mut rejections,
+            )?;
+        }
+    }
+    if let Some(buffer) = buffer.as_mut() {
+        while let Some((line, record)) = buffer.pop() {
+            apply_row(
+                &record,
+                line,
+                transaction_db,
+                client_account_db,
+                options,
+                &mut rejections,
+            )?;
+        }
+    }
+    verify(transaction_db, client_account_db)?;
+    Ok(rejections)
+}
+
+// Apply one CSV row, collecting its rejection and checking the invariants in paranoid mode
+fn apply_row(
+    record: &Transaction,
+    line: Option<u64>,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+    rejections: &mut Vec<Rejection>,
+) -> Result<(), Box<dyn Error>> {
+    // Expired disputes are closed first, so the invariants only see the changes of the row itself
+    expire_disputes(record, transaction_db, client_account_db, options)?;
+
+    let clients = if options.paranoid {
+        get_involved_clients(record, transaction_db)?
+    } else {
+        Vec::new()
+    };
+    let locked_before = if options.paranoid && !options.lock_policy.allows(record.transaction_type)
+    {
+        get_locked_accounts(&clients, client_account_db)?
+    } else {
+        Vec::new()
+    };
+
+    if let Some(reason) = apply_input(record, line, transaction_db, client_account_db, options)? {
+        rejections.push(Rejection {
+            line,
+            transaction_type: record.transaction_type,
+            client_id: record.client_id,
+            id: record.id,
+            reason,
+        });
+    }
+
+    if options.paranoid
+        && let Err(err) =
+            check_invariants(&clients, locked_before, transaction_db, client_account_db)
+    {
+        return Err(format!(
+            "invariant violated at line {} (tx {}): {}",
+            line.unwrap_or(0),
+            record.id,
+            err
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Process a privileged CSV of administrative operations
+///
+/// Expected fields: type (lock, unlock or adjustment), client, tx, amount, operator, reason
+pub fn process_admin_csv<R: Read>(
+    reader: R,
     transaction_db: &TransactionDB,
     client_account_db: &ClientAccountDB,
 ) -> Result<(), Box<dyn Error>> {
-    let mut rdr = ReaderBuilder::new().trim(Trim::All).from_reader(file); // Use Trim::All to remove possible whitespaces
+    process_admin_csv_with_options(
+        reader,
+        transaction_db,
+        client_account_db,
+        &ProcessOptions::default(),
+    )
+}
+
+/// Same as `process_admin_csv`, publishing the locks and unlocks to the event sinks of the options
+pub fn process_admin_csv_with_options<R: Read>(
+    reader: R,
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+    options: &ProcessOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().trim(Trim::All).from_reader(reader);
     for result in rdr.deserialize() {
         let record: Transaction = result?;
-        process_transaction(&record, transaction_db, client_account_db)?;
+        process_admin_operation_with_options(&record, transaction_db, client_account_db, options)?;
     }
+    verify(transaction_db, client_account_db)?;
     Ok(())
 }
 
 pub fn get_all_accounts_as_csv(
     client_account_db: &ClientAccountDB,
 ) -> Result<String, Box<dyn Error>> {
-    let mut wtr = WriterBuilder::new().from_writer(vec![]);
-    for account in client_account_db.get_all_accounts()? {
-        wtr.serialize(account)?;
-    }
-    let data = String::from_utf8(wtr.into_inner()?)?;
-    Ok(data)
+    get_all_accounts_formatted(client_account_db, &OutputOptions::default())
 }
 
+pub fn get_all_accounts_formatted(
+    client_account_db: &ClientAccountDB,
+    options: &OutputOptions,
+) -> Result<String, Box<dyn Error>> {
+    format_accounts(
+        &client_account_db.get_all_accounts_ordered(options.order)?,
+        options,
+    )
+}
+
+pub fn get_client_history_formatted(
+    transaction_db: &TransactionDB,
+    client_id: u16,
+    options: &OutputOptions,
+) -> Result<String, Box<dyn Error>> {
+    format_history(&transaction_db.get_client_history(client_id)?, options)
+}
 
 #[cfg(test)]
+#[allow(clippy::collapsible_if, clippy::type_complexity)]
 mod tests {
     use super::*;
     use crate::domain::{TransactionType};
@@ -171,7 +1234,7 @@ mod tests {
     }
 
     fn make_tx(id: u32, client_id: u16, tx_type: TransactionType, amount: Option<f64>) -> Transaction {
-        Transaction { id, client_id, transaction_type: tx_type, amount, disputed: Some(false) }
+        Transaction { id, client_id, transaction_type: tx_type, amount, operator: None, reason: None, timestamp: None, currency: None, to_currency: None, rate: None, to_client: None, fee: None }
     }
 
     #[test]
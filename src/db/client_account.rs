@@ -1,7 +1,55 @@
-use crate::domain::ClientAccount;
+use super::check_schema_version;
+use crate::domain::{BALANCE_TOLERANCE, ClientAccount};
 use rusqlite::{Connection, params};
 use serde_rusqlite::{from_rows, to_params_named};
 use std::error::Error;
+use std::str::FromStr;
+
+/// Ordering used when listing all accounts
+///
+/// # Orders:
+///
+/// - ClientId: Ascending by client ID (default)
+/// - TotalDesc: Highest total first, ties broken by client ID
+/// - AvailableDesc: Highest available first, ties broken by client ID
+///
+/// Accounts of the same client in different currencies are ordered by currency code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccountOrder {
+    #[default]
+    ClientId,
+    TotalDesc,
+    AvailableDesc,
+}
+
+impl AccountOrder {
+    fn order_by(&self) -> &'static str {
+        match self {
+            AccountOrder::ClientId => "client ASC, currency ASC",
+            AccountOrder::TotalDesc => "total DESC, client ASC, currency ASC",
+            AccountOrder::AvailableDesc => "available DESC, client ASC, currency ASC",
+        }
+    }
+}
+
+impl FromStr for AccountOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "client" => Ok(AccountOrder::ClientId),
+            "total" => Ok(AccountOrder::TotalDesc),
+            "available" => Ok(AccountOrder::AvailableDesc),
+            other => Err(format!(
+                "unknown sort order '{}', expected one of: client, total, available",
+                other
+            )),
+        }
+    }
+}
+
+// Bumped whenever the client_accounts table changes
+const SCHEMA_VERSION: i64 = 1;
 
 pub struct ClientAccountDB {
     conn: Connection,
@@ -10,14 +58,18 @@ pub struct ClientAccountDB {
 impl ClientAccountDB {
     pub fn new(path: &str) -> Result<Self, Box<dyn Error>> {
         let conn = Connection::open(path)?;
+        check_schema_version(&conn, path, SCHEMA_VERSION)?;
 
+        // One row per client and currency, the implicit currency being the empty code
         conn.execute(
             "CREATE TABLE IF NOT EXISTS client_accounts (
-                client INTEGER PRIMARY KEY,
+                client INTEGER NOT NULL,
+                currency TEXT NOT NULL DEFAULT '',
                 available REAL NOT NULL,
                 held REAL NOT NULL,
                 total REAL NOT NULL,
-                locked BOOL
+                locked BOOL,
+                PRIMARY KEY (client, currency)
             )",
             [],
         )?;
@@ -25,6 +77,7 @@ impl ClientAccountDB {
         Ok(ClientAccountDB { conn })
     }
 
+    // True if the client has an account in any currency
     pub fn does_account_exist(&self, client_id: u16) -> Result<bool, Box<dyn Error>> {
         let mut stmt = self
             .conn
@@ -34,13 +87,11 @@ impl ClientAccountDB {
     }
 
     pub fn include_client_account(&self, account: &ClientAccount) -> Result<(), Box<dyn Error>> {
-        self.conn
-            .execute(
-                "INSERT INTO client_accounts (client, available, held, total, locked)
-             VALUES (:client, :available, :held, :total, :locked)",
-                to_params_named(&account).unwrap().to_slice().as_slice(),
-            )
-            .unwrap();
+        self.conn.execute(
+            "INSERT INTO client_accounts (client, currency, available, held, total, locked)
+             VALUES (:client, :currency, :available, :held, :total, :locked)",
+            to_params_named(account)?.to_slice().as_slice(),
+        )?;
 
         Ok(())
     }
@@ -48,26 +99,99 @@ impl ClientAccountDB {
     pub fn update_client_account(&self, account: &ClientAccount) -> Result<(), Box<dyn Error>> {
         self.conn.execute(
             "UPDATE client_accounts SET available = :available, held = :held, total = :total, locked = :locked
-             WHERE client = :client",
-            to_params_named(&account).unwrap().to_slice().as_slice()).unwrap();
+             WHERE client = :client AND currency = :currency",
+            to_params_named(account)?.to_slice().as_slice())?;
+
+        Ok(())
+    }
+
+    // Several accounts changed by one transaction, updated together or not at all
+    pub fn update_client_accounts(
+        &self,
+        accounts: &[&ClientAccount],
+    ) -> Result<(), Box<dyn Error>> {
+        let db_tx = self.conn.unchecked_transaction()?;
+        for account in accounts {
+            db_tx.execute(
+                "UPDATE client_accounts SET available = :available, held = :held, total = :total, locked = :locked
+                 WHERE client = :client AND currency = :currency",
+                to_params_named(account)?.to_slice().as_slice(),
+            )?;
+        }
+        db_tx.commit()?;
+        Ok(())
+    }
 
+    // Lock or unlock every currency account of a client
+    pub fn set_client_locked(&self, client_id: u16, locked: bool) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "UPDATE client_accounts SET locked = ? WHERE client = ?",
+            params![locked, client_id],
+        )?;
         Ok(())
     }
 
+    // Account of a client in the implicit currency
     pub fn get_account(&self, client_id: u16) -> Result<ClientAccount, Box<dyn Error>> {
+        self.get_currency_account(client_id, "")?
+            .ok_or_else(|| format!("client {} not found", client_id).into())
+    }
+
+    pub fn get_currency_account(
+        &self,
+        client_id: u16,
+        currency: &str,
+    ) -> Result<Option<ClientAccount>, Box<dyn Error>> {
         let mut stmt = self
             .conn
-            .prepare("SELECT * FROM client_accounts WHERE client = ?")?;
-        let account = from_rows::<ClientAccount>(stmt.query(params![client_id]).unwrap())
+            .prepare("SELECT * FROM client_accounts WHERE client = ? AND currency = ?")?;
+        let account = from_rows::<ClientAccount>(stmt.query(params![client_id, currency])?)
             .next()
-            .unwrap()?;
+            .transpose()?;
         Ok(account)
     }
 
-    // Get all client accounts. As Client ID is u16, everything can be loaded to memory safely
+    // Accounts of a client in every currency, ordered by currency code
+    pub fn get_client_accounts(
+        &self,
+        client_id: u16,
+    ) -> Result<Vec<ClientAccount>, Box<dyn Error>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT * FROM client_accounts WHERE client = ? ORDER BY currency")?;
+        let accounts = from_rows::<ClientAccount>(stmt.query(params![client_id])?)
+            .collect::<Result<Vec<ClientAccount>, _>>()?;
+        Ok(accounts)
+    }
+
+    // Get all client accounts. As Client ID is u16 and currencies are few, everything can be loaded to memory safely
     pub fn get_all_accounts(&self) -> Result<Vec<ClientAccount>, Box<dyn Error>> {
-        let mut stmt = self.conn.prepare("SELECT * FROM client_accounts")?;
-        let accounts = from_rows::<ClientAccount>(stmt.query([]).unwrap())
+        self.get_all_accounts_ordered(AccountOrder::default())
+    }
+
+    pub fn get_all_accounts_ordered(
+        &self,
+        order: AccountOrder,
+    ) -> Result<Vec<ClientAccount>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT * FROM client_accounts ORDER BY {}",
+            order.order_by()
+        ))?;
+        let accounts = from_rows::<ClientAccount>(stmt.query([])?)
+            .collect::<Result<Vec<ClientAccount>, _>>()?;
+        Ok(accounts)
+    }
+
+    // Accounts whose Available funds are negative, i.e. clients owing money after a dispute or chargeback
+    pub fn get_negative_accounts(
+        &self,
+        order: AccountOrder,
+    ) -> Result<Vec<ClientAccount>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT * FROM client_accounts WHERE available <= ? ORDER BY {}",
+            order.order_by()
+        ))?;
+        let accounts = from_rows::<ClientAccount>(stmt.query(params![-BALANCE_TOLERANCE])?)
             .collect::<Result<Vec<ClientAccount>, _>>()?;
         Ok(accounts)
     }
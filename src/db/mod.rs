@@ -1,5 +1,32 @@
 mod client_account;
 mod transaction;
 
-pub use client_account::ClientAccountDB;
-pub use transaction::TransactionDB;
+pub use client_account::{AccountOrder, ClientAccountDB};
+pub use transaction::{LoggedInput, OpenDispute, TransactionDB, TransactionState};
+
+use rusqlite::Connection;
+use std::error::Error;
+
+/// Check the schema version of a database before creating its tables
+///
+/// # Notes:
+///
+/// - New databases get `version`, stored as the SQLite user_version
+/// - Tables are created with IF NOT EXISTS and never upgraded, so a database written by another version is refused
+///   instead of failing later on a missing column
+fn check_schema_version(conn: &Connection, path: &str, version: i64) -> Result<(), Box<dyn Error>> {
+    let current: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if current == version {
+        return Ok(());
+    }
+    let tables: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get(0))?;
+    if current != 0 || tables != 0 {
+        return Err(format!(
+            "database {} has schema version {}, expected {}. Remove it or use another path",
+            path, current, version
+        )
+        .into());
+    }
+    conn.pragma_update(None, "user_version", version)?;
+    Ok(())
+}
@@ -1,8 +1,127 @@
-use crate::domain::Transaction;
-use rusqlite::{Connection, params};
-use serde_rusqlite::to_params_named;
+use super::check_schema_version;
+use crate::domain::{
+    BALANCE_TOLERANCE, ClientAccount, ClockPoint, DisputeState, FeeTotal, HistoryEntry,
+    JournalEntry, Transaction, TransactionType, round_currency_amount,
+};
+use rusqlite::{Connection, OptionalExtension, ToSql, params};
+use serde::{Deserialize, Serialize};
+use serde_rusqlite::{from_rows, to_params_named, to_params_named_with_fields};
 use std::error::Error;
 
+/// Stored state of a transaction, as seen by disputes, resolves, chargebacks and refunds referring to it
+///
+/// # Notes:
+///
+/// - Disputed, resolved, charged back and refunded are the cumulative amounts of each step on the transaction
+#[derive(Debug, Deserialize)]
+pub struct TransactionState {
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    #[serde(rename = "type")]
+    pub transaction_type: TransactionType,
+    pub amount: Option<f64>,
+    pub currency: Option<String>,
+    // Rate a conversion was applied at, or rejected with for insufficient funds
+    pub rate: Option<f64>,
+    pub to_client: Option<u16>,
+    pub dispute_state: DisputeState,
+    pub disputed: f64,
+    pub resolved: f64,
+    pub charged_back: f64,
+    pub refunded: f64,
+}
+
+impl TransactionState {
+    // Amount held by the open dispute, if any
+    pub fn in_dispute(&self) -> f64 {
+        self.disputed - self.resolved - self.charged_back
+    }
+
+    // Amount that can still be disputed or refunded
+    pub fn remaining(&self) -> f64 {
+        self.amount.unwrap_or(0.0) - self.in_dispute() - self.charged_back - self.refunded
+    }
+}
+
+/// Accepted input, as stored in the event log
+///
+/// # Notes:
+///
+/// - Line is the CSV line of the input, when it came from a file
+#[derive(Debug, Clone)]
+pub struct LoggedInput {
+    pub seq: i64,
+    pub line: Option<u64>,
+    pub transaction: Transaction,
+}
+
+/// Disputed amount still held, with the point of the dispute input that opened it
+///
+/// # Notes:
+///
+/// - Each partial dispute of a tx is a separate hold, so each one expires from its own opening point
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct OpenDispute {
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    #[serde(rename = "tx")]
+    pub id: u32,
+    #[serde(flatten)]
+    pub opened: ClockPoint,
+    pub amount: f64,
+}
+
+#[derive(Deserialize)]
+struct EventLogRow {
+    seq: i64,
+    line: Option<u64>,
+    #[serde(rename = "type")]
+    transaction_type: TransactionType,
+    client: u16,
+    tx: u32,
+    amount: Option<f64>,
+    operator: Option<String>,
+    reason: Option<String>,
+    timestamp: Option<i64>,
+    currency: Option<String>,
+    to_currency: Option<String>,
+    rate: Option<f64>,
+    to_client: Option<u16>,
+    fee: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct DisputeStateUpdate {
+    tx: u32,
+    dispute_state: DisputeState,
+}
+
+#[derive(Serialize)]
+struct HistoryRecord<'a> {
+    tx: u32,
+    #[serde(rename = "type")]
+    transaction_type: TransactionType,
+    client: u16,
+    amount: Option<f64>,
+    currency: &'a str,
+    available: f64,
+    held: f64,
+    total: f64,
+}
+
+#[derive(Serialize)]
+struct FeeRecord<'a> {
+    tx: u32,
+    #[serde(rename = "type")]
+    transaction_type: TransactionType,
+    client: u16,
+    currency: &'a str,
+    amount: f64,
+}
+
+// Bumped whenever a table of the transaction database changes
+const SCHEMA_VERSION: i64 = 4;
+
 pub struct TransactionDB {
     conn: Connection,
 }
@@ -10,46 +129,615 @@ pub struct TransactionDB {
 impl TransactionDB {
     pub fn new(path: &str) -> Result<Self, Box<dyn Error>> {
         let conn = Connection::open(path)?;
+        check_schema_version(&conn, path, SCHEMA_VERSION)?;
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS transactions (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                type TEXT NOT NULL,
+                client INTEGER NOT NULL,
+                tx INTEGER NOT NULL UNIQUE,
+                amount REAL,
+                dispute_state TEXT NOT NULL DEFAULT 'undisputed',
+                operator TEXT,
+                reason TEXT,
+                timestamp INTEGER,
+                currency TEXT,
+                to_currency TEXT,
+                rate REAL,
+                to_client INTEGER,
+                fee REAL,
+                disputed REAL NOT NULL DEFAULT 0,
+                resolved REAL NOT NULL DEFAULT 0,
+                charged_back REAL NOT NULL DEFAULT 0,
+                refunded REAL NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        // Ledger of every client, with its balances right after each entry. Besides the transactions of the client,
+        // it lists the transfers it received and the refunds of its deposits
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS history (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                tx INTEGER NOT NULL,
+                type TEXT NOT NULL,
+                client INTEGER NOT NULL,
+                amount REAL,
+                currency TEXT NOT NULL DEFAULT '',
+                available REAL NOT NULL,
+                held REAL NOT NULL,
+                total REAL NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS history_client ON history (client, seq)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS transactions_dispute_state ON transactions (dispute_state)",
+            [],
+        )?;
+
+        // Append-only double-entry journal. Each entry groups postings that must sum to zero
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS journal_entries (
+                entry INTEGER PRIMARY KEY AUTOINCREMENT,
+                tx INTEGER NOT NULL,
+                type TEXT NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS postings (
+                entry INTEGER NOT NULL REFERENCES journal_entries(entry),
+                account TEXT NOT NULL,
+                client INTEGER NOT NULL,
+                currency TEXT NOT NULL DEFAULT '',
+                amount REAL NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS postings_client ON postings (client, currency, account)",
+            [],
+        )?;
+
+        // Fees charged, linked to the tx they were charged on. A tx has at most one fee per type,
+        // e.g. the dispute and the chargeback of a deposit are charged separately, but partial disputes only once
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS fees (
+                tx INTEGER NOT NULL,
+                type TEXT NOT NULL,
+                client INTEGER NOT NULL,
+                currency TEXT NOT NULL DEFAULT '',
+                amount REAL NOT NULL,
+                UNIQUE (tx, type)
+            )",
+            [],
+        )?;
+
+        // Amounts held by each dispute of a tx. Resolves and chargebacks release the oldest holds first
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS dispute_holds (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                tx INTEGER NOT NULL,
+                opened_seq INTEGER NOT NULL,
+                timestamp INTEGER,
+                open REAL NOT NULL
+            )",
+            [],
+        )?;
+
+        // Every accepted input, including disputes, resolves and chargebacks, in the order it was applied.
+        // Rows can only be appended, so the log can always rebuild the accounts
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS event_log (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                line INTEGER,
                 type TEXT NOT NULL,
                 client INTEGER NOT NULL,
-                tx INTEGER PRIMARY KEY,
+                tx INTEGER NOT NULL,
                 amount REAL,
-                disputed BOOLEAN
+                operator TEXT,
+                reason TEXT,
+                timestamp INTEGER,
+                currency TEXT,
+                to_currency TEXT,
+                rate REAL,
+                to_client INTEGER,
+                fee REAL
             )",
             [],
         )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS event_log_client ON event_log (client, seq)",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS event_log_tx ON event_log (tx, seq)",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS event_log_no_update BEFORE UPDATE ON event_log
+             BEGIN SELECT RAISE(ABORT, 'event log is append-only'); END",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS event_log_no_delete BEFORE DELETE ON event_log
+             BEGIN SELECT RAISE(ABORT, 'event log is append-only'); END",
+            [],
+        )?;
 
         Ok(TransactionDB { conn })
     }
 
-    pub fn include_transaction(&self, tx: &Transaction) -> Result<(), Box<dyn Error>> {
-        self.conn
-            .execute(
-                "INSERT INTO transactions (type, client, tx, amount, disputed)
-             VALUES (:type, :client, :tx, :amount, :disputed)",
-                to_params_named(&tx).unwrap().to_slice().as_slice(),
-            )
-            .unwrap();
+    // Returns the sequence number of the logged input
+    pub fn append_to_event_log(
+        &self,
+        tx: &Transaction,
+        line: Option<u64>,
+    ) -> Result<i64, Box<dyn Error>> {
+        let tx_params = to_params_named(tx)?;
+        let mut params = tx_params.to_slice();
+        params.push((":line", &line as &dyn ToSql));
+        self.conn.execute(
+            "INSERT INTO event_log (line, type, client, tx, amount, operator, reason, timestamp, currency, to_currency, rate, to_client, fee)
+             VALUES (:line, :type, :client, :tx, :amount, :operator, :reason, :timestamp, :currency, :to_currency, :rate, :to_client, :fee)",
+            params.as_slice(),
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    fn query_event_log(
+        &self,
+        condition: &str,
+        params: &[&dyn ToSql],
+    ) -> Result<Vec<LoggedInput>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT seq, line, type, client, tx, amount, operator, reason, timestamp, currency, to_currency, rate, to_client, fee
+             FROM event_log WHERE {} ORDER BY seq",
+            condition
+        ))?;
+        let rows = from_rows::<EventLogRow>(stmt.query(params)?)
+            .collect::<Result<Vec<EventLogRow>, _>>()?;
+        Ok(rows
+            .into_iter()
+            .map(|row| LoggedInput {
+                seq: row.seq,
+                line: row.line,
+                transaction: Transaction {
+                    transaction_type: row.transaction_type,
+                    client_id: row.client,
+                    id: row.tx,
+                    amount: row.amount,
+                    operator: row.operator,
+                    reason: row.reason,
+                    timestamp: row.timestamp,
+                    currency: row.currency,
+                    to_currency: row.to_currency,
+                    rate: row.rate,
+                    to_client: row.to_client,
+                    fee: row.fee,
+                },
+            })
+            .collect())
+    }
+
+    // Logged inputs in the order they were applied, optionally only up to a sequence number
+    pub fn get_event_log(
+        &self,
+        up_to_seq: Option<i64>,
+    ) -> Result<Vec<LoggedInput>, Box<dyn Error>> {
+        self.query_event_log("seq <= ?", &[&up_to_seq.unwrap_or(i64::MAX)])
+    }
 
+    // Same as get_event_log, only for inputs of a single client
+    pub fn get_client_event_log(
+        &self,
+        client_id: u16,
+        up_to_seq: Option<i64>,
+    ) -> Result<Vec<LoggedInput>, Box<dyn Error>> {
+        self.query_event_log(
+            "client = ? AND seq <= ?",
+            &[&client_id, &up_to_seq.unwrap_or(i64::MAX)],
+        )
+    }
+
+    // Sequence number of the input that introduced a tx id, not the disputes, resolves and chargebacks reusing it
+    pub fn get_event_seq_of_tx(&self, id: u32) -> Result<Option<i64>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT MIN(seq) FROM event_log
+             WHERE tx = ? AND type NOT IN ('dispute', 'resolve', 'chargeback')",
+        )?;
+        let seq: Option<i64> = stmt.query_row(params![id], |row| row.get(0))?;
+        Ok(seq)
+    }
+
+    // Sequence number of the last input read up to a CSV line, inputs without line are ignored
+    pub fn get_event_seq_of_line(&self, line: u64) -> Result<Option<i64>, Box<dyn Error>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT MAX(seq) FROM event_log WHERE line <= ?")?;
+        let seq: Option<i64> = stmt.query_row(params![line], |row| row.get(0))?;
+        Ok(seq)
+    }
+
+    // Sequence number of the last input applied with a timestamp up to the given one, inputs without timestamp are ignored
+    pub fn get_event_seq_of_time(&self, timestamp: i64) -> Result<Option<i64>, Box<dyn Error>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT MAX(seq) FROM event_log WHERE timestamp <= ?")?;
+        let seq: Option<i64> = stmt.query_row(params![timestamp], |row| row.get(0))?;
+        Ok(seq)
+    }
+
+    // True if any transfer was logged up to a sequence number
+    pub fn has_transfers(&self, up_to_seq: Option<i64>) -> Result<bool, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT EXISTS(SELECT 1 FROM event_log WHERE type = 'transfer' AND seq <= ?)",
+        )?;
+        let found: bool =
+            stmt.query_row(params![up_to_seq.unwrap_or(i64::MAX)], |row| row.get(0))?;
+        Ok(found)
+    }
+
+    pub fn get_last_event_seq(&self) -> Result<Option<i64>, Box<dyn Error>> {
+        let seq: Option<i64> =
+            self.conn
+                .query_row("SELECT MAX(seq) FROM event_log", [], |row| row.get(0))?;
+        Ok(seq)
+    }
+
+    // Point of the input that introduced a tx id, same as get_event_seq_of_tx
+    pub fn get_event_point_of_tx(&self, id: u32) -> Result<Option<ClockPoint>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT seq, timestamp FROM event_log
+             WHERE tx = ? AND type NOT IN ('dispute', 'resolve', 'chargeback')
+             ORDER BY seq LIMIT 1",
+        )?;
+        let point = from_rows::<ClockPoint>(stmt.query(params![id])?)
+            .next()
+            .transpose()?;
+        Ok(point)
+    }
+
+    // Disputed amounts still held, oldest dispute first
+    pub fn get_open_disputes(&self) -> Result<Vec<OpenDispute>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.client, h.tx, h.opened_seq AS seq, h.timestamp, h.open AS amount
+             FROM dispute_holds h JOIN transactions t ON t.tx = h.tx
+             WHERE h.open > 0
+             ORDER BY h.opened_seq",
+        )?;
+        let disputes =
+            from_rows::<OpenDispute>(stmt.query([])?).collect::<Result<Vec<OpenDispute>, _>>()?;
+        Ok(disputes)
+    }
+
+    // Account balances are stored as they were right after the transaction was applied
+    // Stores the transaction, and adds it to the history of its client with the balances of the account
+    pub fn include_transaction(
+        &self,
+        tx: &Transaction,
+        account: &ClientAccount,
+    ) -> Result<(), Box<dyn Error>> {
+        let db_tx = self.conn.unchecked_transaction()?;
+        insert_transaction(&db_tx, tx)?;
+        insert_history(&db_tx, tx, tx.amount, account)?;
+        db_tx.commit()?;
+
+        Ok(())
+    }
+
+    // Stores a transaction that was not applied, e.g. a withdrawal without enough funds, so its tx id stays taken.
+    // It is left out of the history, which only lists what changed the account
+    pub fn include_rejected_transaction(&self, tx: &Transaction) -> Result<(), Box<dyn Error>> {
+        insert_transaction(&self.conn, tx)
+    }
+
+    // Same as include_transaction, together with the journal entry the transaction posted. Either both are stored or none
+    pub fn include_transaction_with_entry(
+        &self,
+        tx: &Transaction,
+        account: &ClientAccount,
+        entry: &JournalEntry,
+    ) -> Result<(), Box<dyn Error>> {
+        check_balanced(entry)?;
+        let db_tx = self.conn.unchecked_transaction()?;
+        insert_transaction(&db_tx, tx)?;
+        insert_history(&db_tx, tx, tx.amount, account)?;
+        insert_journal_entry(&db_tx, entry)?;
+        db_tx.commit()?;
+
+        Ok(())
+    }
+
+    // Adds an entry of the tx to the history of the client of the account, with its balances right after it
+    pub fn record_history(
+        &self,
+        tx: &Transaction,
+        amount: Option<f64>,
+        account: &ClientAccount,
+    ) -> Result<(), Box<dyn Error>> {
+        insert_history(&self.conn, tx, amount, account)
+    }
+
+    pub fn set_dispute_state(
+        &self,
+        id: u32,
+        dispute_state: DisputeState,
+    ) -> Result<(), Box<dyn Error>> {
+        let update = DisputeStateUpdate {
+            tx: id,
+            dispute_state,
+        };
+        self.conn.execute(
+            "UPDATE transactions SET dispute_state = :dispute_state WHERE tx = :tx",
+            to_params_named(&update)?.to_slice().as_slice(),
+        )?;
         Ok(())
     }
 
-    pub fn mark_disputed(&self, id: u32, is_disputed: bool) -> Result<(), Box<dyn Error>> {
+    // Adds the amount of a dispute, resolve, chargeback or refund to its cumulative total on the referred tx
+    pub fn add_referred_amount(
+        &self,
+        id: u32,
+        transaction_type: TransactionType,
+        amount: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let column = match transaction_type {
+            TransactionType::Dispute => "disputed",
+            TransactionType::Resolve => "resolved",
+            TransactionType::Chargeback => "charged_back",
+            TransactionType::Refund => "refunded",
+            other => return Err(format!("{:?} does not refer to another tx", other).into()),
+        };
         self.conn.execute(
-            "UPDATE transactions SET disputed = ? WHERE tx = ?",
-            params![is_disputed, id],
+            &format!("UPDATE transactions SET {0} = {0} + ? WHERE tx = ?", column),
+            params![amount, id],
         )?;
         Ok(())
     }
 
-    pub fn get_amount(&self, id: u32) -> Result<Option<f64>, Box<dyn Error>> {
-        let mut stmt = self
+    // Holds the amount of a dispute from the point of the input that opened it
+    pub fn open_dispute_hold(
+        &self,
+        id: u32,
+        opened: ClockPoint,
+        amount: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "INSERT INTO dispute_holds (tx, opened_seq, timestamp, open) VALUES (?, ?, ?, ?)",
+            params![id, opened.seq, opened.timestamp, amount],
+        )?;
+        Ok(())
+    }
+
+    // Releases the amount of a resolve or chargeback from the holds of the tx, oldest first
+    pub fn release_dispute_holds(
+        &self,
+        id: u32,
+        amount: f64,
+        currency: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT seq, open FROM dispute_holds WHERE tx = ? AND open > 0 ORDER BY seq",
+        )?;
+        let holds = stmt
+            .query_map(params![id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?))
+            })?
+            .collect::<Result<Vec<(i64, f64)>, _>>()?;
+        let mut left = amount;
+        for (seq, open) in holds {
+            if left < BALANCE_TOLERANCE {
+                break;
+            }
+            let released = open.min(left);
+            left = round_currency_amount(left - released, Some(currency));
+            self.conn.execute(
+                "UPDATE dispute_holds SET open = ? WHERE seq = ?",
+                params![round_currency_amount(open - released, Some(currency)), seq],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn get_transaction_state(
+        &self,
+        id: u32,
+    ) -> Result<Option<TransactionState>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT client, type, amount, currency, rate, to_client, dispute_state, disputed, resolved, charged_back, refunded
+             FROM transactions WHERE tx = ?",
+        )?;
+        let state = from_rows::<TransactionState>(stmt.query(params![id])?)
+            .next()
+            .transpose()?;
+        Ok(state)
+    }
+
+    // Sum of amounts currently under dispute for a client in a currency, should always match its held funds.
+    // Disputed transfers are held by the receiving client
+    pub fn get_disputed_total(
+        &self,
+        client_id: u16,
+        currency: &str,
+    ) -> Result<f64, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT COALESCE(SUM(disputed - resolved - charged_back), 0) FROM transactions
+             WHERE COALESCE(to_client, client) = ? AND COALESCE(currency, '') = ? AND dispute_state = 'disputed'",
+        )?;
+        let total: f64 = stmt.query_row(params![client_id, currency], |row| row.get(0))?;
+        Ok(total)
+    }
+
+    // Ledger of a single client, in the same order transactions were processed.
+    // Disputes, resolves and chargebacks take the dispute state of the tx they refer to
+    pub fn get_client_history(&self, client_id: u16) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT h.tx, h.type, h.amount, h.currency, COALESCE(t.dispute_state, 'undisputed') AS dispute_state,
+                CASE WHEN t.type = h.type THEN t.operator END AS operator, h.available, h.held, h.total
+             FROM history h LEFT JOIN transactions t ON t.tx = h.tx
+                 AND (t.type = h.type OR h.type IN ('dispute', 'resolve', 'chargeback'))
+             WHERE h.client = ? ORDER BY h.seq",
+        )?;
+        let history = from_rows::<HistoryEntry>(stmt.query(params![client_id])?)
+            .collect::<Result<Vec<HistoryEntry>, _>>()?;
+        Ok(history)
+    }
+
+    pub fn record_journal_entry(&self, entry: &JournalEntry) -> Result<(), Box<dyn Error>> {
+        check_balanced(entry)?;
+        let db_tx = self.conn.unchecked_transaction()?;
+        insert_journal_entry(&db_tx, entry)?;
+        db_tx.commit()?;
+
+        Ok(())
+    }
+
+    // Available and held balances of a client in a currency, derived from the journal
+    pub fn get_journal_balances(
+        &self,
+        client_id: u16,
+        currency: &str,
+    ) -> Result<(f64, f64), Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                COALESCE(SUM(CASE WHEN account = 'client_available' THEN amount END), 0),
+                COALESCE(SUM(CASE WHEN account = 'client_held' THEN amount END), 0)
+             FROM postings WHERE client = ? AND currency = ?",
+        )?;
+        let balances = stmt.query_row(params![client_id, currency], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        Ok(balances)
+    }
+
+    pub fn record_fee(
+        &self,
+        tx: &Transaction,
+        client_id: u16,
+        currency: &str,
+        amount: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let fee = FeeRecord {
+            tx: tx.id,
+            transaction_type: tx.transaction_type,
+            client: client_id,
+            currency,
+            amount,
+        };
+        self.conn.execute(
+            "INSERT INTO fees (tx, type, client, currency, amount) VALUES (:tx, :type, :client, :currency, :amount)",
+            to_params_named(&fee)?.to_slice().as_slice(),
+        )?;
+        Ok(())
+    }
+
+    // Whether a fee was already charged on the tx for its type
+    pub fn has_fee(&self, tx: &Transaction) -> Result<bool, Box<dyn Error>> {
+        let charged = self
             .conn
-            .prepare("SELECT amount FROM transactions WHERE tx = ?")?;
-        let amount: Option<f64> = stmt.query_row(params![id], |row| row.get(0))?;
-        Ok(amount)
+            .query_row(
+                "SELECT 1 FROM fees WHERE tx = :tx AND type = :type",
+                to_params_named_with_fields(tx, &["tx", "type"])?
+                    .to_slice()
+                    .as_slice(),
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(charged.is_some())
+    }
+
+    // Charged fees summed per client, currency and transaction type
+    pub fn get_fee_totals(&self) -> Result<Vec<FeeTotal>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT client, currency, type, COUNT(*) AS count, SUM(amount) AS total
+             FROM fees GROUP BY client, currency, type ORDER BY client, currency, type",
+        )?;
+        let totals =
+            from_rows::<FeeTotal>(stmt.query([])?).collect::<Result<Vec<FeeTotal>, _>>()?;
+        Ok(totals)
+    }
+
+    // Journal entries whose postings do not sum to zero in some currency. Should always be empty
+    pub fn get_unbalanced_entries(&self) -> Result<Vec<i64>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT entry FROM (
+                 SELECT entry FROM postings GROUP BY entry, currency HAVING ABS(SUM(amount)) >= ?
+             ) ORDER BY entry",
+        )?;
+        let entries = stmt
+            .query_map(params![BALANCE_TOLERANCE], |row| row.get(0))?
+            .collect::<Result<Vec<i64>, _>>()?;
+        Ok(entries)
+    }
+}
+
+fn check_balanced(entry: &JournalEntry) -> Result<(), Box<dyn Error>> {
+    if let Some((currency, sum)) = entry.unbalanced_currency() {
+        return Err(format!(
+            "journal entry for tx {} is not balanced, postings in '{}' sum to {}",
+            entry.tx, currency, sum
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn insert_transaction(conn: &Connection, tx: &Transaction) -> Result<(), Box<dyn Error>> {
+    conn.execute(
+        "INSERT INTO transactions (type, client, tx, amount, operator, reason, timestamp, currency, to_currency, rate, to_client, fee)
+         VALUES (:type, :client, :tx, :amount, :operator, :reason, :timestamp, :currency, :to_currency, :rate, :to_client, :fee)",
+        to_params_named(tx)?.to_slice().as_slice(),
+    )?;
+    Ok(())
+}
+
+fn insert_history(
+    conn: &Connection,
+    tx: &Transaction,
+    amount: Option<f64>,
+    account: &ClientAccount,
+) -> Result<(), Box<dyn Error>> {
+    let entry = HistoryRecord {
+        tx: tx.id,
+        transaction_type: tx.transaction_type,
+        client: account.id(),
+        amount,
+        currency: account.currency(),
+        available: account.available(),
+        held: account.held(),
+        total: account.total(),
+    };
+    conn.execute(
+        "INSERT INTO history (tx, type, client, amount, currency, available, held, total)
+         VALUES (:tx, :type, :client, :amount, :currency, :available, :held, :total)",
+        to_params_named(&entry)?.to_slice().as_slice(),
+    )?;
+    Ok(())
+}
+
+// Postings are stored with the entry, in the database transaction of the caller
+fn insert_journal_entry(conn: &Connection, entry: &JournalEntry) -> Result<(), Box<dyn Error>> {
+    conn.execute(
+        "INSERT INTO journal_entries (tx, type) VALUES (:tx, :type)",
+        to_params_named(entry)?.to_slice().as_slice(),
+    )?;
+    let entry_id = conn.last_insert_rowid();
+    for posting in &entry.postings {
+        let posting_params = to_params_named(posting)?;
+        let mut params = posting_params.to_slice();
+        params.push((":entry", &entry_id as &dyn ToSql));
+        conn.execute(
+            "INSERT INTO postings (entry, account, client, currency, amount)
+             VALUES (:entry, :account, :client, :currency, :amount)",
+            params.as_slice(),
+        )?;
     }
+    Ok(())
 }
@@ -1,22 +1,34 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Balances of a client in one currency
+///
+/// # Notes:
+///
+/// - Currency is empty for the implicit currency, used by inputs without a currency column
+/// - Locking applies to the client, so every currency account of a client has the same locked flag
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ClientAccount {
     #[serde(rename = "client")]
     id: u16,
-    #[serde(serialize_with = "crate::domain::serialize_f64_4")] 
+    currency: String,
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
     available: f64,
-    #[serde(serialize_with = "crate::domain::serialize_f64_4")] 
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
     held: f64,
-    #[serde(serialize_with = "crate::domain::serialize_f64_4")] 
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
     total: f64, // Available + Held
     locked: bool,
 }
 
 impl ClientAccount {
     pub fn new(id: u16) -> Self {
+        Self::with_currency(id, "")
+    }
+
+    pub fn with_currency(id: u16, currency: &str) -> Self {
         ClientAccount {
             id,
+            currency: currency.to_string(),
             available: 0.0,
             held: 0.0,
             total: 0.0,
@@ -24,6 +36,26 @@ impl ClientAccount {
         }
     }
 
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    pub fn available(&self) -> f64 {
+        self.available
+    }
+
+    pub fn held(&self) -> f64 {
+        self.held
+    }
+
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
     pub fn is_locked(&self) -> bool {
         self.locked
     }
@@ -53,6 +85,18 @@ impl ClientAccount {
         }
     }
 
+    // Same as hold_funds, letting Available go negative when the client already spent the funds
+    pub fn hold_funds_as_debt(&mut self, amount: f64) {
+        self.available -= amount;
+        self.held += amount;
+    }
+
+    // Debits Available without checking it, for amounts already capped or owed by the client
+    pub fn charge_funds(&mut self, amount: f64) {
+        self.available -= amount;
+        self.total -= amount;
+    }
+
     pub fn resolve_funds(&mut self, amount: f64) -> Result<(), String> {
         if self.held >= amount {
             self.held -= amount;
@@ -73,7 +117,21 @@ impl ClientAccount {
         }
     }
 
+    // Positive amounts credit, negative amounts debit Available funds
+    pub fn adjust_funds(&mut self, amount: f64) -> Result<(), String> {
+        if amount < 0.0 && self.available < -amount {
+            return Err("Insufficient funds".to_string());
+        }
+        self.available += amount;
+        self.total += amount;
+        Ok(())
+    }
+
     pub fn lock_account(&mut self) {
         self.locked = true;
     }
+
+    pub fn unlock_account(&mut self) {
+        self.locked = false;
+    }
 }
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Deserializer, de::Error};
+
+// ISO 4217 currencies without minor units, and with 3 or 4 decimal places. Everything else uses 2
+const ZERO_DECIMALS: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "ISK", "JPY", "KMF", "KRW", "PYG", "RWF", "UGX", "UYI", "VND",
+    "VUV", "XAF", "XOF", "XPF",
+];
+const THREE_DECIMALS: &[&str] = &["BHD", "IQD", "JOD", "KWD", "LYD", "OMR", "TND"];
+const FOUR_DECIMALS: &[&str] = &["CLF", "UYW"];
+// Floating point noise of amounts parsed from decimal text, far below the smallest minor unit
+const PRECISION_TOLERANCE: f64 = 1e-7;
+
+/// Decimal places amounts are kept with in a currency
+///
+/// # Notes:
+///
+/// - The implicit currency of inputs without a currency column (empty code) keeps the original 4 decimal places
+/// - Other currencies use their ISO 4217 minor units, 2 for codes not in the list
+pub fn currency_precision(currency: &str) -> u32 {
+    if currency.is_empty() {
+        4
+    } else if ZERO_DECIMALS.contains(&currency) {
+        0
+    } else if THREE_DECIMALS.contains(&currency) {
+        3
+    } else if FOUR_DECIMALS.contains(&currency) {
+        4
+    } else {
+        2
+    }
+}
+
+/// Round an amount to the decimal places of its currency, None being the implicit one
+pub fn round_currency_amount(amount: f64, currency: Option<&str>) -> f64 {
+    let scale = 10f64.powi(currency_precision(currency.unwrap_or_default()) as i32);
+    (amount * scale).round() / scale
+}
+
+/// Whether an amount has no more decimal places than its currency allows, None being the implicit currency
+///
+/// # Notes:
+///
+/// - Amounts with more decimals are rejected instead of rounded, rounding would create or destroy money
+pub fn fits_currency_precision(amount: f64, currency: Option<&str>) -> bool {
+    (amount - round_currency_amount(amount, currency)).abs() < PRECISION_TOLERANCE
+}
+
+/// Parse an ISO 4217 code, three ASCII letters in any case, returned in upper case
+pub fn parse_currency(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.len() != 3 || !value.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!(
+            "invalid currency '{}', expected an ISO 4217 code such as USD",
+            value
+        ));
+    }
+    Ok(value.to_ascii_uppercase())
+}
+
+// Empty CSV fields and missing JSON fields are the implicit currency, anything else must be a valid code
+pub fn deserialize_currency<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(code) if !code.trim().is_empty() => {
+            parse_currency(&code).map(Some).map_err(D::Error::custom)
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_currency_codes_are_parsed() {
+        assert_eq!(parse_currency("usd"), Ok("USD".to_string()));
+        assert_eq!(parse_currency(" EUR "), Ok("EUR".to_string()));
+        assert!(parse_currency("EURO").is_err());
+        assert!(parse_currency("U$D").is_err());
+    }
+
+    #[test]
+    fn test_amounts_are_rounded_to_currency_precision() {
+        assert_eq!(round_currency_amount(1.23456, None), 1.2346);
+        assert_eq!(round_currency_amount(1.23456, Some("USD")), 1.23);
+        assert_eq!(round_currency_amount(1.23456, Some("KWD")), 1.235);
+        assert_eq!(round_currency_amount(1500.6, Some("JPY")), 1501.0);
+    }
+
+    #[test]
+    fn test_amounts_must_fit_currency_precision() {
+        assert!(fits_currency_precision(1500.0, Some("JPY")));
+        assert!(!fits_currency_precision(1500.6, Some("JPY")));
+        assert!(fits_currency_precision(0.07, Some("USD")));
+        assert!(!fits_currency_precision(0.075, Some("USD")));
+        assert!(fits_currency_precision(123456789.1234, None));
+        assert!(!fits_currency_precision(1.23456, None));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::domain::{TransactionType, parse_duration};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Position of an input on the dispute clock
+///
+/// # Notes:
+///
+/// - seq is the event log sequence number of the input, used for row distances
+/// - timestamp is its event time, when the input had one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ClockPoint {
+    pub seq: i64,
+    pub timestamp: Option<i64>,
+}
+
+/// How long a dispute can be filed, or stay open
+///
+/// # Windows:
+///
+/// - Time: Milliseconds between the timestamps of both inputs. Not checked if either has no timestamp
+/// - Rows: Number of accepted inputs in between, as counted by the event log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeWindow {
+    Time(i64),
+    Rows(i64),
+}
+
+impl DisputeWindow {
+    pub fn is_exceeded(&self, since: ClockPoint, now: ClockPoint) -> bool {
+        match *self {
+            DisputeWindow::Time(millis) => match (since.timestamp, now.timestamp) {
+                (Some(since), Some(now)) => now - since > millis,
+                _ => false,
+            },
+            DisputeWindow::Rows(rows) => now.seq - since.seq > rows,
+        }
+    }
+}
+
+// A duration such as "30d", or a row distance such as "5000rows". Windows must be positive
+impl FromStr for DisputeWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let window = match s.strip_suffix("rows") {
+            Some(rows) => rows
+                .parse()
+                .map(DisputeWindow::Rows)
+                .map_err(|_| format!("invalid row count in dispute window '{}'", s))?,
+            None => parse_duration(s).map(DisputeWindow::Time)?,
+        };
+        match window {
+            DisputeWindow::Time(length) | DisputeWindow::Rows(length) if length <= 0 => {
+                Err(format!("dispute window '{}' must be positive", s))
+            }
+            window => Ok(window),
+        }
+    }
+}
+
+/// What happens to a dispute left open longer than the expiry window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpiryAction {
+    Resolve,
+    Chargeback,
+}
+
+impl ExpiryAction {
+    pub fn transaction_type(&self) -> TransactionType {
+        match self {
+            ExpiryAction::Resolve => TransactionType::Resolve,
+            ExpiryAction::Chargeback => TransactionType::Chargeback,
+        }
+    }
+}
+
+impl FromStr for ExpiryAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "resolve" => Ok(ExpiryAction::Resolve),
+            "chargeback" => Ok(ExpiryAction::Chargeback),
+            other => Err(format!(
+                "unknown expiry action '{}', expected resolve or chargeback",
+                other
+            )),
+        }
+    }
+}
+
+/// Limits on how old disputes can be, and what they can do to the account
+///
+/// # Notes:
+///
+/// - filing_window: Disputes of a transaction older than this are rejected (default none)
+/// - expire_after: Disputes open longer than this are closed with expiry_action, as synthetic
+///   resolve or chargeback inputs recorded in the journal and event log (default none)
+/// - allow_negative: Disputes hold the full amount even when the client already spent it, leaving Available
+///   negative as a debt. Chargeback fees can then add to the debt too (default off, such disputes are rejected)
+/// - expiry_action: Resolve by default, so expired disputes give the funds back to the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisputePolicy {
+    pub filing_window: Option<DisputeWindow>,
+    pub expire_after: Option<DisputeWindow>,
+    pub expiry_action: ExpiryAction,
+    pub allow_negative: bool,
+}
+
+impl Default for DisputePolicy {
+    fn default() -> Self {
+        DisputePolicy {
+            filing_window: None,
+            expire_after: None,
+            expiry_action: ExpiryAction::Resolve,
+            allow_negative: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(seq: i64, timestamp: Option<i64>) -> ClockPoint {
+        ClockPoint { seq, timestamp }
+    }
+
+    #[test]
+    fn test_parse_windows() {
+        assert_eq!("30d".parse(), Ok(DisputeWindow::Time(2_592_000_000)));
+        assert_eq!("500rows".parse(), Ok(DisputeWindow::Rows(500)));
+        assert!("rows".parse::<DisputeWindow>().is_err());
+        assert!("30".parse::<DisputeWindow>().is_err());
+        assert!("-5rows".parse::<DisputeWindow>().is_err());
+        assert!("0rows".parse::<DisputeWindow>().is_err());
+        assert!("0s".parse::<DisputeWindow>().is_err());
+        assert_eq!("Chargeback".parse(), Ok(ExpiryAction::Chargeback));
+        assert!("refund".parse::<ExpiryAction>().is_err());
+    }
+
+    #[test]
+    fn test_window_is_exceeded() {
+        let window = DisputeWindow::Time(1_000);
+        assert!(!window.is_exceeded(point(1, Some(0)), point(9, Some(1_000))));
+        assert!(window.is_exceeded(point(1, Some(0)), point(2, Some(1_001))));
+        assert!(!window.is_exceeded(point(1, None), point(2, Some(5_000))));
+
+        let window = DisputeWindow::Rows(3);
+        assert!(!window.is_exceeded(point(1, Some(0)), point(4, None)));
+        assert!(window.is_exceeded(point(1, Some(0)), point(5, None)));
+    }
+}
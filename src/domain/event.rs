@@ -0,0 +1,115 @@
+use crate::domain::RejectionReason;
+use serde::{Deserialize, Serialize};
+
+/// Changes to accounts, published while transactions are processed
+///
+/// # Events:
+///
+/// - FundsDeposited: Deposit was applied
+/// - WithdrawalRejected: Withdrawal was not applied, e.g. because of insufficient funds
+/// - DisputeOpened: Funds of the disputed tx moved from Available to Held
+/// - DisputeResolved: Funds of the disputed tx released back to Available
+/// - ChargebackApplied: Held funds of the disputed tx withdrawn
+/// - AccountLocked: Account was frozen by a chargeback or an administrative lock
+/// - AccountUnlocked: Account was unfrozen by an administrative unlock
+/// - FundsConverted: Amount left the account in one currency and the converted amount entered the one in another
+/// - FundsTransferred: Amount moved from the client to another client
+/// - FundsRefunded: Part of a deposit returned to its source from the Available funds
+/// - FeeCharged: Fee of the tx moved from the Available funds of the client to the house account
+///
+/// Disputes, resolves and chargebacks of transfers are published for the receiving client, whose funds are held
+///
+/// Events with an amount carry the currency of the account it moved, empty for the implicit currency. Disputes,
+/// resolves, chargebacks, refunds and fees use the currency of the referred transaction
+///
+/// Serialized with the event name in the `event` field, e.g.
+/// {"event":"FundsDeposited","client":1,"tx":1,"amount":2.5,"currency":"USD"}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum DomainEvent {
+    FundsDeposited {
+        client: u16,
+        tx: u32,
+        amount: f64,
+        currency: String,
+    },
+    WithdrawalRejected {
+        client: u16,
+        tx: u32,
+        amount: Option<f64>,
+        currency: String,
+        reason: RejectionReason,
+    },
+    DisputeOpened {
+        client: u16,
+        tx: u32,
+        amount: f64,
+        currency: String,
+    },
+    DisputeResolved {
+        client: u16,
+        tx: u32,
+        amount: f64,
+        currency: String,
+    },
+    ChargebackApplied {
+        client: u16,
+        tx: u32,
+        amount: f64,
+        currency: String,
+    },
+    AccountLocked {
+        client: u16,
+        tx: u32,
+    },
+    AccountUnlocked {
+        client: u16,
+        tx: u32,
+    },
+    FundsConverted {
+        client: u16,
+        tx: u32,
+        from: String,
+        to: String,
+        amount: f64,
+        converted: f64,
+        rate: f64,
+    },
+    FundsTransferred {
+        client: u16,
+        to_client: u16,
+        tx: u32,
+        amount: f64,
+        currency: String,
+    },
+    FundsRefunded {
+        client: u16,
+        tx: u32,
+        amount: f64,
+        currency: String,
+    },
+    FeeCharged {
+        client: u16,
+        tx: u32,
+        amount: f64,
+        currency: String,
+    },
+}
+
+impl DomainEvent {
+    pub fn client_id(&self) -> u16 {
+        match self {
+            DomainEvent::FundsDeposited { client, .. }
+            | DomainEvent::WithdrawalRejected { client, .. }
+            | DomainEvent::DisputeOpened { client, .. }
+            | DomainEvent::DisputeResolved { client, .. }
+            | DomainEvent::ChargebackApplied { client, .. }
+            | DomainEvent::AccountLocked { client, .. }
+            | DomainEvent::AccountUnlocked { client, .. }
+            | DomainEvent::FundsConverted { client, .. }
+            | DomainEvent::FundsTransferred { client, .. }
+            | DomainEvent::FundsRefunded { client, .. }
+            | DomainEvent::FeeCharged { client, .. } => *client,
+        }
+    }
+}
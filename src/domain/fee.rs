@@ -0,0 +1,19 @@
+use crate::domain::TransactionType;
+use serde::{Deserialize, Serialize};
+
+/// Fees charged to a client in a currency for one transaction type, as returned by `TransactionDB::get_fee_totals`
+///
+/// # Notes:
+///
+/// - Count is the number of transactions a fee was charged on, total is the sum of the charged fees
+/// - Currency is empty for the implicit currency
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FeeTotal {
+    pub client: u16,
+    pub currency: String,
+    #[serde(rename = "type")]
+    pub transaction_type: TransactionType,
+    pub count: u32,
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
+    pub total: f64,
+}
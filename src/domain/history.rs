@@ -0,0 +1,36 @@
+use crate::domain::{DisputeState, TransactionType};
+use serde::{Deserialize, Serialize};
+
+/// One row of a client's ledger, as returned by `TransactionDB::get_client_history`
+///
+/// # Notes:
+///
+/// - Balances are the account state right after the transaction was processed
+/// - Only applied inputs are listed. Rejected ones, e.g. a withdrawal without enough funds, are left out even when
+///   they are stored to keep their tx id taken
+/// - Disputes, resolves and chargebacks are listed with the tx they refer to and the amount they moved. A charged back
+///   transfer is listed for the sender too, with the funds it got back
+/// - Dispute state is the current one of the transaction, not the one at the time
+/// - Amount is empty for lock and unlock operations
+/// - Transfers are listed for both clients, each one with the balances of its own account
+/// - Refunds are listed with the tx of the deposit they refer to and the amount refunded. Their dispute state is
+///   always undisputed, refunds cannot be disputed
+/// - Currency is empty for the implicit currency, balances are the ones of the account in that currency
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    #[serde(rename = "tx")]
+    pub id: u32,
+    #[serde(rename = "type")]
+    pub transaction_type: TransactionType,
+    #[serde(serialize_with = "crate::domain::serialize_option_f64_4")]
+    pub amount: Option<f64>,
+    pub currency: String,
+    pub dispute_state: DisputeState,
+    pub operator: Option<String>,
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
+    pub available: f64,
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
+    pub held: f64,
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
+    pub total: f64,
+}
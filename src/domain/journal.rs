@@ -0,0 +1,315 @@
+use crate::domain::TransactionType;
+use serde::{Deserialize, Serialize};
+
+// Amounts are kept with 4 decimal places, same as the stored account balances
+const PRECISION: f64 = 10_000.0;
+// Tolerance for floating point noise when summing postings
+pub const BALANCE_TOLERANCE: f64 = 1e-6;
+
+// Largest amount accepted in a single transaction, keeps balances well within f64 precision
+pub const MAX_AMOUNT: f64 = 1_000_000_000.0;
+
+pub fn round_amount(amount: f64) -> f64 {
+    (amount * PRECISION).round() / PRECISION
+}
+
+// Amounts must be positive and finite, NaN and infinity parse fine as f64
+pub fn is_valid_amount(amount: f64) -> bool {
+    amount.is_finite() && amount > 0.0 && amount <= MAX_AMOUNT
+}
+
+/// Accounts that can receive postings in the journal
+///
+/// # Accounts:
+///
+/// - ClientAvailable: Funds the client can use. Mirrors `ClientAccount::available`
+/// - ClientHeld: Funds under dispute. Mirrors `ClientAccount::held`
+/// - ExternalFunding: Money entering (deposits) or leaving (withdrawals) the engine
+/// - ChargebackLoss: Money reversed by chargebacks
+/// - Adjustments: Counterpart of administrative corrections to client balances
+/// - CurrencyExchange: Counterpart of conversions, receiving the source currency and paying out the target one
+/// - House: Fees charged to clients. Postings keep the client that paid them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerAccount {
+    ClientAvailable,
+    ClientHeld,
+    ExternalFunding,
+    ChargebackLoss,
+    Adjustments,
+    CurrencyExchange,
+    House,
+}
+
+/// Single movement of funds on a ledger account. Positive amounts increase the account balance
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Posting {
+    pub account: LedgerAccount,
+    pub client: u16,
+    pub currency: String,
+    pub amount: f64,
+}
+
+/// Set of postings generated by a single transaction, which must sum to zero in each currency
+///
+/// # Notes:
+///
+/// - Entries are append-only. Resolves and chargebacks create new entries instead of changing the dispute one
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalEntry {
+    pub tx: u32,
+    #[serde(rename = "type")]
+    pub transaction_type: TransactionType,
+    #[serde(skip)]
+    pub postings: Vec<Posting>,
+}
+
+impl JournalEntry {
+    // Moves amount from one ledger account to another, for the same client
+    fn transfer(
+        tx: u32,
+        transaction_type: TransactionType,
+        client: u16,
+        from: LedgerAccount,
+        to: LedgerAccount,
+        amount: f64,
+    ) -> Self {
+        let amount = round_amount(amount);
+        JournalEntry {
+            tx,
+            transaction_type,
+            postings: vec![
+                Posting {
+                    account: from,
+                    client,
+                    currency: String::new(),
+                    amount: -amount,
+                },
+                Posting {
+                    account: to,
+                    client,
+                    currency: String::new(),
+                    amount,
+                },
+            ],
+        }
+    }
+
+    pub fn deposit(tx: u32, client: u16, amount: f64) -> Self {
+        Self::transfer(
+            tx,
+            TransactionType::Deposit,
+            client,
+            LedgerAccount::ExternalFunding,
+            LedgerAccount::ClientAvailable,
+            amount,
+        )
+    }
+
+    pub fn withdrawal(tx: u32, client: u16, amount: f64) -> Self {
+        Self::transfer(
+            tx,
+            TransactionType::Withdrawal,
+            client,
+            LedgerAccount::ClientAvailable,
+            LedgerAccount::ExternalFunding,
+            amount,
+        )
+    }
+
+    pub fn dispute(tx: u32, client: u16, amount: f64) -> Self {
+        Self::transfer(
+            tx,
+            TransactionType::Dispute,
+            client,
+            LedgerAccount::ClientAvailable,
+            LedgerAccount::ClientHeld,
+            amount,
+        )
+    }
+
+    pub fn resolve(tx: u32, client: u16, amount: f64) -> Self {
+        Self::transfer(
+            tx,
+            TransactionType::Resolve,
+            client,
+            LedgerAccount::ClientHeld,
+            LedgerAccount::ClientAvailable,
+            amount,
+        )
+    }
+
+    pub fn chargeback(tx: u32, client: u16, amount: f64) -> Self {
+        Self::transfer(
+            tx,
+            TransactionType::Chargeback,
+            client,
+            LedgerAccount::ClientHeld,
+            LedgerAccount::ChargebackLoss,
+            amount,
+        )
+    }
+
+    // Part of a deposit leaving the engine again, back to where it came from
+    pub fn refund(tx: u32, client: u16, amount: f64) -> Self {
+        Self::transfer(
+            tx,
+            TransactionType::Refund,
+            client,
+            LedgerAccount::ClientAvailable,
+            LedgerAccount::ExternalFunding,
+            amount,
+        )
+    }
+
+    // Negative amounts move funds from the client back to the adjustments account
+    pub fn adjustment(tx: u32, client: u16, amount: f64) -> Self {
+        Self::transfer(
+            tx,
+            TransactionType::Adjustment,
+            client,
+            LedgerAccount::Adjustments,
+            LedgerAccount::ClientAvailable,
+            amount,
+        )
+    }
+
+    // Moves available funds from one client to another
+    pub fn client_transfer(tx: u32, from_client: u16, to_client: u16, amount: f64) -> Self {
+        let mut entry = Self::transfer(
+            tx,
+            TransactionType::Transfer,
+            from_client,
+            LedgerAccount::ClientAvailable,
+            LedgerAccount::ClientAvailable,
+            amount,
+        );
+        entry.postings[1].client = to_client;
+        entry
+    }
+
+    // Reverses a transfer, from the held funds of the receiving client back to the sending one
+    pub fn transfer_chargeback(tx: u32, receiver: u16, sender: u16, amount: f64) -> Self {
+        let mut entry = Self::transfer(
+            tx,
+            TransactionType::Chargeback,
+            receiver,
+            LedgerAccount::ClientHeld,
+            LedgerAccount::ClientAvailable,
+            amount,
+        );
+        entry.postings[1].client = sender;
+        entry
+    }
+
+    // Fee charged to the client for a transaction, keeping the type of the transaction it was charged on
+    pub fn fee(tx: u32, transaction_type: TransactionType, client: u16, amount: f64) -> Self {
+        Self::transfer(
+            tx,
+            transaction_type,
+            client,
+            LedgerAccount::ClientAvailable,
+            LedgerAccount::House,
+            amount,
+        )
+    }
+
+    // Source and target legs go through the exchange account, so each currency balances on its own
+    pub fn conversion(
+        tx: u32,
+        client: u16,
+        (from, amount): (&str, f64),
+        (to, converted): (&str, f64),
+    ) -> Self {
+        let source = Self::transfer(
+            tx,
+            TransactionType::Convert,
+            client,
+            LedgerAccount::ClientAvailable,
+            LedgerAccount::CurrencyExchange,
+            amount,
+        )
+        .in_currency(from);
+        let target = Self::transfer(
+            tx,
+            TransactionType::Convert,
+            client,
+            LedgerAccount::CurrencyExchange,
+            LedgerAccount::ClientAvailable,
+            converted,
+        )
+        .in_currency(to);
+        JournalEntry {
+            tx,
+            transaction_type: TransactionType::Convert,
+            postings: [source.postings, target.postings].concat(),
+        }
+    }
+
+    // Entries are in the implicit currency unless moved to another one
+    pub fn in_currency(mut self, currency: &str) -> Self {
+        for posting in &mut self.postings {
+            posting.currency = currency.to_string();
+        }
+        self
+    }
+
+    // Amounts of different currencies cannot be added up
+    pub fn sum(&self, currency: &str) -> f64 {
+        self.postings
+            .iter()
+            .filter(|posting| posting.currency == currency)
+            .map(|posting| posting.amount)
+            .sum()
+    }
+
+    // First currency whose postings do not sum to zero, with their sum
+    pub fn unbalanced_currency(&self) -> Option<(&str, f64)> {
+        self.postings
+            .iter()
+            .map(|posting| (posting.currency.as_str(), self.sum(&posting.currency)))
+            .find(|(_, sum)| sum.abs() >= BALANCE_TOLERANCE)
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.unbalanced_currency().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_entry_types_are_balanced() {
+        let entries = [
+            JournalEntry::deposit(1, 1, 10.5),
+            JournalEntry::withdrawal(2, 1, 0.1234),
+            JournalEntry::dispute(1, 1, 10.5),
+            JournalEntry::resolve(1, 1, 10.5),
+            JournalEntry::chargeback(1, 1, 10.5),
+            JournalEntry::adjustment(3, 1, -2.5),
+            JournalEntry::conversion(4, 1, ("USD", 10.0), ("JPY", 1500.0)),
+            JournalEntry::client_transfer(5, 1, 2, 3.5),
+            JournalEntry::transfer_chargeback(5, 2, 1, 3.5),
+            JournalEntry::fee(2, TransactionType::Withdrawal, 1, 0.25),
+            JournalEntry::refund(1, 1, 2.5),
+        ];
+        for entry in entries {
+            assert!(entry.is_balanced(), "{:?} is not balanced", entry);
+        }
+    }
+
+    #[test]
+    fn test_unbalanced_entry_is_detected() {
+        let mut entry = JournalEntry::deposit(1, 1, 10.0);
+        entry.postings[0].amount = -9.0;
+        assert!(!entry.is_balanced());
+    }
+
+    #[test]
+    fn test_amounts_are_rounded_to_4_decimals() {
+        let entry = JournalEntry::deposit(1, 1, 1.23456);
+        assert_eq!(entry.postings[1].amount, 1.2346);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::domain::TransactionType;
+use std::str::FromStr;
+
+/// Transaction types still accepted on a locked account
+///
+/// # Notes:
+///
+/// - Default blocks everything, which was the only behaviour before policies existed
+/// - Allowing resolve and chargeback lets other open disputes finish, instead of stranding held funds
+/// - Administrative operations are not affected, they are always applied
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LockPolicy {
+    allowed: Vec<TransactionType>,
+}
+
+impl LockPolicy {
+    pub fn block_all() -> Self {
+        LockPolicy::default()
+    }
+
+    pub fn allow(allowed: &[TransactionType]) -> Self {
+        LockPolicy {
+            allowed: allowed.to_vec(),
+        }
+    }
+
+    pub fn allows(&self, transaction_type: TransactionType) -> bool {
+        self.allowed.contains(&transaction_type)
+    }
+}
+
+// Comma separated list of types, e.g. "resolve,chargeback", or "none"
+impl FromStr for LockPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("none") {
+            return Ok(LockPolicy::block_all());
+        }
+        let mut allowed = Vec::new();
+        for name in s.split(',') {
+            let transaction_type = match name.trim().to_ascii_lowercase().as_str() {
+                "deposit" => TransactionType::Deposit,
+                "withdrawal" => TransactionType::Withdrawal,
+                "dispute" => TransactionType::Dispute,
+                "resolve" => TransactionType::Resolve,
+                "chargeback" => TransactionType::Chargeback,
+                "convert" => TransactionType::Convert,
+                "transfer" => TransactionType::Transfer,
+                "refund" => TransactionType::Refund,
+                other => {
+                    return Err(format!(
+                        "unknown transaction type '{}' in lock policy, expected any of: deposit, withdrawal, dispute, resolve, chargeback, convert, transfer, refund",
+                        other
+                    ));
+                }
+            };
+            allowed.push(transaction_type);
+        }
+        Ok(LockPolicy { allowed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_blocks_everything() {
+        let policy = LockPolicy::default();
+        assert!(!policy.allows(TransactionType::Deposit));
+        assert!(!policy.allows(TransactionType::Chargeback));
+    }
+
+    #[test]
+    fn test_parse_allowed_types() {
+        let policy: LockPolicy = "resolve, chargeback".parse().unwrap();
+        assert!(policy.allows(TransactionType::Resolve));
+        assert!(policy.allows(TransactionType::Chargeback));
+        assert!(!policy.allows(TransactionType::Deposit));
+        assert!("rebate".parse::<LockPolicy>().is_err());
+    }
+}
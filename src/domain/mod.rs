@@ -1,8 +1,30 @@
 mod client_account;
+mod currency;
+mod dispute_policy;
+mod event;
+mod fee;
+mod history;
+mod journal;
+mod lock_policy;
+mod rejection;
+mod timestamp;
 mod transaction;
 
 pub use client_account::ClientAccount;
-pub use transaction::{Transaction, TransactionType};
+pub use currency::{
+	currency_precision, deserialize_currency, fits_currency_precision, parse_currency, round_currency_amount,
+};
+pub use dispute_policy::{ClockPoint, DisputePolicy, DisputeWindow, ExpiryAction};
+pub use event::DomainEvent;
+pub use fee::FeeTotal;
+pub use history::HistoryEntry;
+pub use journal::{
+	BALANCE_TOLERANCE, JournalEntry, LedgerAccount, MAX_AMOUNT, Posting, is_valid_amount, round_amount,
+};
+pub use lock_policy::LockPolicy;
+pub use rejection::{Rejection, RejectionReason};
+pub use timestamp::{deserialize_timestamp, parse_duration, parse_timestamp};
+pub use transaction::{DisputeState, Transaction, TransactionType};
 
 // Custom serializer for 4 decimal places
 use serde::Serializer;
@@ -12,3 +34,14 @@ where
 {
 	s.serialize_str(&format!("{:.4}", x))
 }
+
+// Same as serialize_f64_4, keeping missing amounts empty
+pub fn serialize_option_f64_4<S>(x: &Option<f64>, s: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+{
+	match x {
+		Some(x) => serialize_f64_4(x, s),
+		None => s.serialize_none(),
+	}
+}
@@ -0,0 +1,96 @@
+use crate::domain::TransactionType;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Reasons for a transaction not being applied
+///
+/// # Reasons:
+///
+/// - AccountLocked: Account is locked and the lock policy blocks this type
+/// - AdminOnly: Administrative operation received from a regular input
+/// - MissingAmount: Deposit or withdrawal without amount
+/// - InvalidAmount: Amount is not a positive number up to `MAX_AMOUNT`, or has more decimal places than its currency
+/// - DuplicateTransaction: Deposit or withdrawal reusing the tx id of a stored transaction
+/// - InsufficientFunds: Not enough Available funds
+/// - InsufficientHeldFunds: Not enough Held funds
+/// - TransactionNotFound: Referred tx does not exist, or belongs to another client
+/// - NotDisputable: Referred tx is not a deposit or withdrawal
+/// - InvalidDisputeState: Nothing of the referred tx is left to dispute, or nothing is under dispute to resolve or
+///   charge back
+/// - LateArrival: In event time mode, timestamp is older than the allowed lateness
+/// - DisputeWindowClosed: Referred tx is older than the dispute filing window
+/// - InvalidConversion: Conversion without a source currency, or without a different target currency
+/// - RateNotFound: Rate table has no rate for the currency pair at the time of the conversion
+/// - InvalidTransfer: Transfer without a receiving client, or to the sending client itself
+/// - NotRefundable: Referred tx of a refund is not a deposit
+/// - ExceedsRemainingAmount: Amount of a dispute or refund is above what is left of the referred tx, or the one of a
+///   resolve or chargeback above what is under dispute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReason {
+    AccountLocked,
+    AdminOnly,
+    MissingAmount,
+    InvalidAmount,
+    DuplicateTransaction,
+    InsufficientFunds,
+    InsufficientHeldFunds,
+    TransactionNotFound,
+    NotDisputable,
+    InvalidDisputeState,
+    LateArrival,
+    DisputeWindowClosed,
+    InvalidConversion,
+    RateNotFound,
+    InvalidTransfer,
+    NotRefundable,
+    ExceedsRemainingAmount,
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            RejectionReason::AccountLocked => "account is locked",
+            RejectionReason::AdminOnly => "administrative operations are not accepted here",
+            RejectionReason::MissingAmount => "amount is required",
+            RejectionReason::InvalidAmount => {
+                "amount must be positive, within limits and in the decimal places of its currency"
+            }
+            RejectionReason::DuplicateTransaction => "transaction id already used",
+            RejectionReason::InsufficientFunds => "insufficient funds",
+            RejectionReason::InsufficientHeldFunds => "insufficient held funds",
+            RejectionReason::TransactionNotFound => "referred transaction not found",
+            RejectionReason::NotDisputable => "referred transaction cannot be disputed",
+            RejectionReason::InvalidDisputeState => "invalid dispute state for this operation",
+            RejectionReason::LateArrival => "arrived later than the allowed lateness",
+            RejectionReason::DisputeWindowClosed => "referred transaction is too old to dispute",
+            RejectionReason::InvalidConversion => {
+                "conversion needs a currency and a different target currency"
+            }
+            RejectionReason::RateNotFound => "no exchange rate for the currency pair",
+            RejectionReason::InvalidTransfer => "transfer needs a different receiving client",
+            RejectionReason::NotRefundable => "only deposits can be refunded",
+            RejectionReason::ExceedsRemainingAmount => {
+                "amount is above what is left of the referred transaction"
+            }
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// Transaction that was not applied, and why
+///
+/// # Notes:
+///
+/// - Line is the CSV line of the row, when it came from a file
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Rejection {
+    pub line: Option<u64>,
+    #[serde(rename = "type")]
+    pub transaction_type: TransactionType,
+    #[serde(rename = "client")]
+    pub client_id: u16,
+    #[serde(rename = "tx")]
+    pub id: u32,
+    pub reason: RejectionReason,
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Deserializer, de::Error};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/// Parse a timestamp given as RFC 3339 (e.g. 2024-05-01T12:00:00Z) or epoch milliseconds, returning epoch milliseconds
+pub fn parse_timestamp(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    if let Ok(millis) = value.parse::<i64>() {
+        return Ok(millis);
+    }
+    let datetime = OffsetDateTime::parse(value, &Rfc3339).map_err(|_| {
+        format!(
+            "invalid timestamp '{}', expected RFC 3339 or epoch milliseconds",
+            value
+        )
+    })?;
+    Ok((datetime.unix_timestamp_nanos() / 1_000_000) as i64)
+}
+
+/// Parse a duration with a unit, e.g. 500ms, 30s, 5m, 2h or 7d, returning milliseconds
+pub fn parse_duration(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{}', expected e.g. 30s or 7d", value))?;
+    let unit_millis = match unit {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => {
+            return Err(format!(
+                "invalid duration unit in '{}', expected ms, s, m, h or d",
+                value
+            ));
+        }
+    };
+    amount
+        .checked_mul(unit_millis)
+        .ok_or_else(|| format!("duration '{}' is too long", value))
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTimestamp {
+    Millis(i64),
+    Text(String),
+}
+
+// Empty CSV fields and missing JSON fields are no timestamp, anything else must parse
+pub fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<RawTimestamp>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(RawTimestamp::Millis(millis)) => Ok(Some(millis)),
+        Some(RawTimestamp::Text(text)) if text.trim().is_empty() => Ok(None),
+        Some(RawTimestamp::Text(text)) => parse_timestamp(&text).map(Some).map_err(D::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamps_are_parsed() {
+        assert_eq!(parse_timestamp("1714564800000"), Ok(1_714_564_800_000));
+        assert_eq!(parse_timestamp("2024-05-01T12:00:00Z"), Ok(1_714_564_800_000));
+        assert_eq!(
+            parse_timestamp("2024-05-01T14:00:00.250+02:00"),
+            Ok(1_714_564_800_250)
+        );
+        assert!(parse_timestamp("2024-05-01").is_err());
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_durations_are_parsed() {
+        assert_eq!(parse_duration("500ms"), Ok(500));
+        assert_eq!(parse_duration("30s"), Ok(30_000));
+        assert_eq!(parse_duration("7d"), Ok(604_800_000));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+}
@@ -1,17 +1,22 @@
+use crate::domain::BALANCE_TOLERANCE;
 use serde::{Deserialize, Serialize};
 
-// TODO: Handle all transaction types
-
 /// Each transaction type corresponds to a specific action on the account
 ///
 /// # Types:
 ///
 /// - Deposit: Increase Available and Total funds of Account
 /// - Withdrawal: Decrease Available and Total funds from account
-/// - Dispute: Client claim that transaction needs to be reversed. Done by TX, not amount
-/// - Resolve: Resolve Dispute, releasing funds from Held to Available
-/// - Chargeback: Withdrawn of TX Held funds. Freeze account when this happens
-#[derive(Debug, Deserialize, Serialize)]
+/// - Dispute: Client claim that transaction needs to be reversed. Done by TX, for its remaining amount or part of it
+/// - Resolve: Resolve Dispute, releasing funds from Held to Available. All of the disputed amount or part of it
+/// - Chargeback: Withdrawn of TX Held funds. All of the disputed amount or part of it. Freeze account when this happens
+/// - Lock: Administrative. Freeze account
+/// - Unlock: Administrative. Unfreeze account, e.g. after a chargeback was investigated
+/// - Adjustment: Administrative. Credit (positive amount) or debit (negative amount) Available funds
+/// - Convert: Move Available funds from one currency account of the client to another, at the rate in effect
+/// - Transfer: Move Available funds from the client to another client, in the same currency
+/// - Refund: Return part of a deposit to its source, from Available funds. Done by the TX of the deposit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TransactionType {
     Deposit,
@@ -19,14 +24,81 @@ pub enum TransactionType {
     Dispute,
     Resolve,
     Chargeback,
+    Lock,
+    Unlock,
+    Adjustment,
+    Convert,
+    Transfer,
+    Refund,
+}
+
+impl TransactionType {
+    // Administrative types are only accepted from privileged inputs
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            self,
+            TransactionType::Lock | TransactionType::Unlock | TransactionType::Adjustment
+        )
+    }
+
+    // Only transactions started by the client can be disputed
+    pub fn is_disputable(&self) -> bool {
+        matches!(
+            self,
+            TransactionType::Deposit | TransactionType::Withdrawal | TransactionType::Transfer
+        )
+    }
+}
+
+/// Dispute state of a stored transaction, following the amounts its disputes act on
+///
+/// # Transitions:
+///
+/// - Disputed: on Dispute, and as long as any amount is under dispute. Disputes can overlap, each one adds to it
+/// - Resolved: on the Resolve releasing the last amount under dispute
+/// - ChargedBack: on the Chargeback withdrawing the last amount under dispute
+///
+/// Disputes are accepted while part of the transaction was not disputed, charged back or refunded yet, so ChargedBack
+/// is only final once nothing is left, even if the account is unlocked afterwards. Resolves and chargebacks need an
+/// amount under dispute
+///
+/// Lock, Unlock and Adjustment never change the dispute state of other transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisputeState {
+    Undisputed,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+impl DisputeState {
+    /// State after a dispute, resolve or chargeback, given the amount of the transaction still under dispute after it
+    pub fn after(transaction_type: TransactionType, in_dispute: f64) -> DisputeState {
+        match transaction_type {
+            _ if in_dispute >= BALANCE_TOLERANCE => DisputeState::Disputed,
+            TransactionType::Chargeback => DisputeState::ChargedBack,
+            _ => DisputeState::Resolved,
+        }
+    }
 }
 
 /// Transactions correspond to each row in the CSV
 ///
 /// # Notes:
 ///
-/// - The disputed field is not expected in the CSV, but is used to control eventual disputes
-#[derive(Debug, Deserialize, Serialize)]
+/// - Operator and reason are only expected in administrative inputs, and are required there
+/// - Amount can be left out entirely in JSON inputs, e.g. for disputes
+/// - Dispute state is not part of the CSV, it is kept by `TransactionDB`
+/// - Timestamp is optional, given as RFC 3339 or epoch milliseconds and kept as epoch milliseconds
+/// - Currency is an optional ISO 4217 code. Without it the transaction is in the implicit currency of the engine.
+///   Disputes, resolves and chargebacks always apply in the currency of the transaction they refer to
+/// - To currency is the target of a conversion, whose amount is in the source currency
+/// - Rate is the one a conversion was applied at. It is set by the engine from its rate table, never read from inputs
+/// - To client is the receiving client of a transfer. Only the sending client can dispute it
+/// - Fee is the one the fee schedule sets for the transaction. Same as the rate, it is set by the engine and never
+///   read from inputs. The event log keeps the fee actually charged, which can be capped to the Available funds
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Transaction {
     #[serde(rename = "type")]
     pub transaction_type: TransactionType,
@@ -34,8 +106,50 @@ pub struct Transaction {
     pub client_id: u16,
     #[serde(rename = "tx")]
     pub id: u32,
-    #[serde(deserialize_with = "csv::invalid_option")]
+    #[serde(deserialize_with = "csv::invalid_option", default)]
     pub amount: Option<f64>,
     #[serde(deserialize_with = "csv::invalid_option", default)]
-    pub disputed: Option<bool>,
+    pub operator: Option<String>,
+    #[serde(deserialize_with = "csv::invalid_option", default)]
+    pub reason: Option<String>,
+    #[serde(deserialize_with = "crate::domain::deserialize_timestamp", default)]
+    pub timestamp: Option<i64>,
+    #[serde(deserialize_with = "crate::domain::deserialize_currency", default)]
+    pub currency: Option<String>,
+    #[serde(deserialize_with = "crate::domain::deserialize_currency", default)]
+    pub to_currency: Option<String>,
+    #[serde(skip_deserializing)]
+    pub rate: Option<f64>,
+    #[serde(deserialize_with = "csv::invalid_option", default)]
+    pub to_client: Option<u16>,
+    #[serde(skip_deserializing)]
+    pub fee: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dispute_state_follows_amount_in_dispute() {
+        assert_eq!(
+            DisputeState::after(TransactionType::Dispute, 2.5),
+            DisputeState::Disputed
+        );
+        assert_eq!(
+            DisputeState::after(TransactionType::Resolve, 0.0),
+            DisputeState::Resolved
+        );
+        assert_eq!(
+            DisputeState::after(TransactionType::Chargeback, 0.0),
+            DisputeState::ChargedBack
+        );
+        // Closing part of the amount under dispute keeps the rest disputed
+        for transaction_type in [TransactionType::Resolve, TransactionType::Chargeback] {
+            assert_eq!(
+                DisputeState::after(transaction_type, 1.0),
+                DisputeState::Disputed
+            );
+        }
+    }
 }
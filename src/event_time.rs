@@ -0,0 +1,169 @@
+use crate::domain::Transaction;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+// Rows are ordered by timestamp, ties keep the input order
+struct Pending {
+    timestamp: i64,
+    arrival: u64,
+    line: Option<u64>,
+    transaction: Transaction,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.timestamp, self.arrival).cmp(&(other.timestamp, other.arrival))
+    }
+}
+
+/// Reorders transactions by timestamp, waiting up to a bounded lateness for out of order rows
+///
+/// # Notes:
+///
+/// - The watermark is the newest timestamp seen minus the lateness. Rows at or below it are released in timestamp order
+/// - Rows older than the watermark when they arrive are late, as newer rows may already have been released
+/// - A lateness of 0 only accepts rows in timestamp order
+pub struct EventTimeBuffer {
+    lateness: i64,
+    newest: Option<i64>,
+    arrivals: u64,
+    pending: BinaryHeap<Reverse<Pending>>,
+}
+
+impl EventTimeBuffer {
+    /// Lateness in milliseconds
+    pub fn new(lateness: i64) -> Self {
+        EventTimeBuffer {
+            lateness,
+            newest: None,
+            arrivals: 0,
+            pending: BinaryHeap::new(),
+        }
+    }
+
+    fn watermark(&self) -> Option<i64> {
+        self.newest
+            .map(|newest| newest.saturating_sub(self.lateness))
+    }
+
+    /// Buffer a row, or give it back if it is late
+    pub fn push(
+        &mut self,
+        timestamp: i64,
+        line: Option<u64>,
+        transaction: Transaction,
+    ) -> Result<(), Box<(Option<u64>, Transaction)>> {
+        if self
+            .watermark()
+            .is_some_and(|watermark| timestamp < watermark)
+        {
+            return Err(Box::new((line, transaction)));
+        }
+        self.newest = Some(
+            self.newest
+                .map_or(timestamp, |newest| newest.max(timestamp)),
+        );
+        self.arrivals += 1;
+        self.pending.push(Reverse(Pending {
+            timestamp,
+            arrival: self.arrivals,
+            line,
+            transaction,
+        }));
+        Ok(())
+    }
+
+    /// Next row that can be applied, if any is at or below the watermark
+    pub fn pop_ready(&mut self) -> Option<(Option<u64>, Transaction)> {
+        let watermark = self.watermark()?;
+        if self.pending.peek()?.0.timestamp > watermark {
+            return None;
+        }
+        self.pending
+            .pop()
+            .map(|Reverse(pending)| (pending.line, pending.transaction))
+    }
+
+    /// Next buffered row in timestamp order, used once the input is exhausted
+    pub fn pop(&mut self) -> Option<(Option<u64>, Transaction)> {
+        self.pending
+            .pop()
+            .map(|Reverse(pending)| (pending.line, pending.transaction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::TransactionType;
+
+    fn deposit(id: u32) -> Transaction {
+        Transaction {
+            transaction_type: TransactionType::Deposit,
+            client_id: 1,
+            id,
+            amount: Some(1.0),
+            operator: None,
+            reason: None,
+            timestamp: None,
+            currency: None,
+            to_currency: None,
+            rate: None,
+            to_client: None,
+            fee: None,
+        }
+    }
+
+    fn drain(buffer: &mut EventTimeBuffer, ready_only: bool) -> Vec<u32> {
+        let mut ids = Vec::new();
+        loop {
+            let next = if ready_only {
+                buffer.pop_ready()
+            } else {
+                buffer.pop()
+            };
+            match next {
+                Some((_, tx)) => ids.push(tx.id),
+                None => return ids,
+            }
+        }
+    }
+
+    #[test]
+    fn test_rows_are_reordered_within_lateness() {
+        let mut buffer = EventTimeBuffer::new(100);
+        buffer.push(1_000, Some(2), deposit(1)).unwrap();
+        buffer.push(950, Some(3), deposit(2)).unwrap();
+        assert_eq!(drain(&mut buffer, true), Vec::<u32>::new());
+
+        // Watermark moves to 1_000, releasing everything up to it in timestamp order
+        buffer.push(1_100, Some(4), deposit(3)).unwrap();
+        assert_eq!(drain(&mut buffer, true), vec![2, 1]);
+
+        buffer.push(1_000, Some(5), deposit(4)).unwrap();
+        assert_eq!(drain(&mut buffer, false), vec![4, 3]);
+    }
+
+    #[test]
+    fn test_late_rows_are_returned() {
+        let mut buffer = EventTimeBuffer::new(100);
+        buffer.push(1_000, Some(2), deposit(1)).unwrap();
+        let (line, tx) = *buffer.push(899, Some(3), deposit(2)).unwrap_err();
+        assert_eq!((line, tx.id), (Some(3), 2));
+        assert!(buffer.push(900, Some(4), deposit(3)).is_ok());
+    }
+}
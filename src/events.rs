@@ -0,0 +1,253 @@
+use crate::domain::DomainEvent;
+use std::error::Error;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "webhook")]
+use std::time::Duration;
+
+/// Destination of domain events
+///
+/// # Notes:
+///
+/// - Events are published once the change was stored and logged, in processing order
+/// - A failing sink does not fail the transaction, which is already applied. The failure goes to the failure handler
+///   of the publisher, and the change can still be found in the event log
+pub trait EventSink: Send + Sync {
+    fn publish(&self, event: &DomainEvent) -> Result<(), Box<dyn Error>>;
+}
+
+/// In-process sink, events are received from the paired `Receiver`
+pub struct ChannelSink {
+    sender: Sender<DomainEvent>,
+}
+
+impl ChannelSink {
+    pub fn new() -> (Self, Receiver<DomainEvent>) {
+        let (sender, receiver) = channel();
+        (ChannelSink { sender }, receiver)
+    }
+}
+
+impl EventSink for ChannelSink {
+    // A dropped receiver means nobody is listening anymore, not a failure
+    fn publish(&self, event: &DomainEvent) -> Result<(), Box<dyn Error>> {
+        let _ = self.sender.send(event.clone());
+        Ok(())
+    }
+}
+
+/// Writes one JSON object per event and line
+pub struct JsonlSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonlSink {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        JsonlSink {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Append to a file, created if it does not exist
+    pub fn append(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(JsonlSink::new(BufWriter::new(file)))
+    }
+}
+
+impl EventSink for JsonlSink {
+    fn publish(&self, event: &DomainEvent) -> Result<(), Box<dyn Error>> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| "event file is unavailable")?;
+        serde_json::to_writer(&mut *writer, event)?;
+        writeln!(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// POSTs every event as JSON to a URL, any response other than 2xx is an error
+#[cfg(feature = "webhook")]
+pub struct WebhookSink {
+    url: String,
+    agent: ureq::Agent,
+}
+
+// Slow receivers hold back processing, as events are published in order
+#[cfg(feature = "webhook")]
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[cfg(feature = "webhook")]
+impl WebhookSink {
+    pub fn new(url: &str) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(WEBHOOK_TIMEOUT))
+            .build()
+            .into();
+        WebhookSink {
+            url: url.to_string(),
+            agent,
+        }
+    }
+}
+
+#[cfg(feature = "webhook")]
+impl EventSink for WebhookSink {
+    fn publish(&self, event: &DomainEvent) -> Result<(), Box<dyn Error>> {
+        self.agent
+            .post(&self.url)
+            .send_json(event)
+            .map_err(|err| format!("webhook {} failed: {}", self.url, err))?;
+        Ok(())
+    }
+}
+
+/// Sink described as kind:target, e.g. "jsonl:events.jsonl" or "webhook:https://example.com/events"
+pub fn sink_from_spec(spec: &str) -> Result<Arc<dyn EventSink>, Box<dyn Error>> {
+    match spec.split_once(':') {
+        Some(("jsonl", path)) if !path.is_empty() => Ok(Arc::new(JsonlSink::append(path)?)),
+        #[cfg(feature = "webhook")]
+        Some(("webhook", url)) if !url.is_empty() => Ok(Arc::new(WebhookSink::new(url))),
+        #[cfg(not(feature = "webhook"))]
+        Some(("webhook", _)) => Err(From::from("webhook sinks require the webhook feature")),
+        _ => Err(format!(
+            "invalid event sink '{}', expected jsonl:<path> or webhook:<url>",
+            spec
+        )
+        .into()),
+    }
+}
+
+/// Called with the event and the error of a sink that failed to publish it
+pub type FailureHandler = Arc<dyn Fn(&DomainEvent, &dyn Error) + Send + Sync>;
+
+/// Sends every event to all the registered sinks, none by default
+///
+/// # Notes:
+///
+/// - Failures are ignored unless a failure handler is set, the binaries report them on stderr
+#[derive(Clone, Default)]
+pub struct EventPublisher {
+    sinks: Vec<Arc<dyn EventSink>>,
+    on_failure: Option<FailureHandler>,
+}
+
+impl EventPublisher {
+    pub fn add_sink(&mut self, sink: Arc<dyn EventSink>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn on_failure(
+        &mut self,
+        handler: impl Fn(&DomainEvent, &dyn Error) + Send + Sync + 'static,
+    ) {
+        self.on_failure = Some(Arc::new(handler));
+    }
+
+    // Every sink gets the event, even if a previous one failed
+    pub fn publish(&self, event: &DomainEvent) {
+        for sink in &self.sinks {
+            if let Err(err) = sink.publish(event)
+                && let Some(handler) = &self.on_failure
+            {
+                handler(event, err.as_ref());
+            }
+        }
+    }
+}
+
+impl fmt::Debug for EventPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EventPublisher({} sinks)", self.sinks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writer shared with the test, to read back what the sink wrote
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_publisher_sends_to_every_sink() {
+        let buffer = SharedBuffer::default();
+        let (channel, receiver) = ChannelSink::new();
+        let mut publisher = EventPublisher::default();
+        publisher.add_sink(Arc::new(channel));
+        publisher.add_sink(Arc::new(JsonlSink::new(buffer.clone())));
+
+        let event = DomainEvent::FundsDeposited {
+            client: 1,
+            tx: 2,
+            amount: 2.5,
+            currency: "USD".to_string(),
+        };
+        publisher.publish(&event);
+        publisher.publish(&DomainEvent::AccountLocked { client: 1, tx: 3 });
+
+        assert_eq!(receiver.try_recv().unwrap(), event);
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            written,
+            "{\"event\":\"FundsDeposited\",\"client\":1,\"tx\":2,\"amount\":2.5,\"currency\":\"USD\"}\n\
+             {\"event\":\"AccountLocked\",\"client\":1,\"tx\":3}\n"
+        );
+    }
+
+    struct FailingSink;
+
+    impl EventSink for FailingSink {
+        fn publish(&self, _event: &DomainEvent) -> Result<(), Box<dyn Error>> {
+            Err(From::from("sink is down"))
+        }
+    }
+
+    #[test]
+    fn test_failures_go_to_the_handler() {
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let store = failures.clone();
+        let (channel, receiver) = ChannelSink::new();
+        let mut publisher = EventPublisher::default();
+        publisher.add_sink(Arc::new(FailingSink));
+        publisher.add_sink(Arc::new(channel));
+        publisher.on_failure(move |event, err| {
+            store.lock().unwrap().push((event.clone(), err.to_string()));
+        });
+
+        let event = DomainEvent::AccountLocked { client: 1, tx: 3 };
+        publisher.publish(&event);
+
+        // The sink after the failing one still gets the event
+        assert_eq!(receiver.try_recv().unwrap(), event);
+        assert_eq!(
+            *failures.lock().unwrap(),
+            vec![(event, "sink is down".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_sink_spec_is_validated() {
+        #[cfg(feature = "webhook")]
+        assert!(sink_from_spec("webhook:http://127.0.0.1:9/events").is_ok());
+        assert!(sink_from_spec("webhook:").is_err());
+        assert!(sink_from_spec("kafka:events").is_err());
+        assert!(sink_from_spec("events.jsonl").is_err());
+    }
+}
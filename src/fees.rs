@@ -0,0 +1,223 @@
+use crate::domain::TransactionType;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+
+/// Amount range of a tiered fee, applied to amounts up to `up_to` (inclusive), or any amount when not set
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct FeeBand {
+    pub up_to: Option<f64>,
+    #[serde(default)]
+    pub flat: f64,
+    #[serde(default)]
+    pub percent: f64,
+}
+
+/// Fee of a transaction type, for a client tier or every tier without its own rule
+///
+/// # Notes:
+///
+/// - Fee is flat + percent of the amount, plus the flat and percent of the first band covering the amount
+/// - Min and max cap the result, a max of 0 waives the fee
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FeeRule {
+    #[serde(rename = "type")]
+    pub transaction_type: TransactionType,
+    pub tier: Option<String>,
+    #[serde(default)]
+    pub flat: f64,
+    #[serde(default)]
+    pub percent: f64,
+    #[serde(default)]
+    pub bands: Vec<FeeBand>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl FeeRule {
+    pub fn fee(&self, amount: f64) -> f64 {
+        let mut fee = self.flat + amount * self.percent / 100.0;
+        if let Some(band) = self
+            .bands
+            .iter()
+            .find(|band| band.up_to.is_none_or(|up_to| amount <= up_to))
+        {
+            fee += band.flat + amount * band.percent / 100.0;
+        }
+        if let Some(min) = self.min {
+            fee = fee.max(min);
+        }
+        if let Some(max) = self.max {
+            fee = fee.min(max);
+        }
+        fee
+    }
+}
+
+/// Fees charged per transaction type and client tier, loaded from a JSON file
+///
+/// # Notes:
+///
+/// - Format: {"tiers": {"<client>": "<tier>"}, "rules": [{"type": "withdrawal", "tier": "vip", "flat": 0.5, ...}]}
+/// - Clients not in tiers only get rules without tier. A rule for the tier of the client wins over those
+/// - Fees are in the currency of the account the transaction applies to, and charged to the client that sent it
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct FeeSchedule {
+    #[serde(default)]
+    pub tiers: HashMap<u16, String>,
+    #[serde(default)]
+    pub rules: Vec<FeeRule>,
+}
+
+fn is_valid_fee_value(value: f64) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+impl FeeSchedule {
+    pub fn new(schedule: FeeSchedule) -> Result<Self, Box<dyn Error>> {
+        for rule in &schedule.rules {
+            let values = [rule.flat, rule.percent]
+                .into_iter()
+                .chain(rule.min)
+                .chain(rule.max)
+                .chain(rule.bands.iter().flat_map(|band| [band.flat, band.percent]));
+            if !values.into_iter().all(is_valid_fee_value) {
+                return Err(format!(
+                    "fee rule for {:?} has a negative or invalid value",
+                    rule.transaction_type
+                )
+                .into());
+            }
+            if let (Some(min), Some(max)) = (rule.min, rule.max)
+                && min > max
+            {
+                return Err(format!(
+                    "fee rule for {:?} has a min above its max",
+                    rule.transaction_type
+                )
+                .into());
+            }
+            if rule.transaction_type.is_admin() {
+                return Err(From::from(
+                    "fees cannot be charged on administrative operations",
+                ));
+            }
+        }
+        Ok(schedule)
+    }
+
+    pub fn from_json<R: Read>(reader: R) -> Result<Self, Box<dyn Error>> {
+        Self::new(serde_json::from_reader(reader)?)
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)
+            .map_err(|err| format!("cannot open fee schedule {}: {}", path, err))?;
+        Self::from_json(file)
+    }
+
+    /// Charge a flat fee on every transaction of the type, replacing its rule without tier. Rules of tiers are kept
+    pub fn set_flat_fee(
+        &mut self,
+        transaction_type: TransactionType,
+        fee: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        if !is_valid_fee_value(fee) || transaction_type.is_admin() {
+            return Err(format!("invalid flat fee {} for {:?}", fee, transaction_type).into());
+        }
+        self.rules
+            .retain(|rule| rule.transaction_type != transaction_type || rule.tier.is_some());
+        self.rules.push(FeeRule {
+            transaction_type,
+            tier: None,
+            flat: fee,
+            percent: 0.0,
+            bands: Vec::new(),
+            min: None,
+            max: None,
+        });
+        Ok(())
+    }
+
+    // Lets the engine skip looking up the amount of transaction types without fees
+    pub fn has_fees(&self, transaction_type: TransactionType) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.transaction_type == transaction_type)
+    }
+
+    /// Fee of a transaction of the client, before rounding to its currency. None when no rule charges a fee
+    pub fn get_fee(
+        &self,
+        transaction_type: TransactionType,
+        client_id: u16,
+        amount: f64,
+    ) -> Option<f64> {
+        let tier = self.tiers.get(&client_id);
+        let rules = || {
+            self.rules
+                .iter()
+                .filter(move |rule| rule.transaction_type == transaction_type)
+        };
+        let rule = rules()
+            .find(|rule| tier.is_some() && rule.tier.as_ref() == tier)
+            .or_else(|| rules().find(|rule| rule.tier.is_none()))?;
+        Some(rule.fee(amount)).filter(|fee| *fee > 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEDULE: &str = r#"{
+        "tiers": {"7": "vip"},
+        "rules": [
+            {"type": "withdrawal", "flat": 0.5, "percent": 1.0, "min": 1.0, "max": 5.0},
+            {"type": "withdrawal", "tier": "vip", "max": 0},
+            {"type": "transfer", "bands": [{"up_to": 100.0, "flat": 0.25}, {"percent": 0.5}]}
+        ]
+    }"#;
+
+    #[test]
+    fn test_fee_kinds_and_caps() {
+        let schedule = FeeSchedule::from_json(SCHEDULE.as_bytes()).unwrap();
+        let fee = |transaction_type, client_id, amount| {
+            schedule.get_fee(transaction_type, client_id, amount)
+        };
+        assert_eq!(fee(TransactionType::Withdrawal, 1, 10.0), Some(1.0));
+        assert_eq!(fee(TransactionType::Withdrawal, 1, 200.0), Some(2.5));
+        assert_eq!(fee(TransactionType::Withdrawal, 1, 10_000.0), Some(5.0));
+        assert_eq!(fee(TransactionType::Withdrawal, 7, 200.0), None);
+        assert_eq!(fee(TransactionType::Transfer, 1, 100.0), Some(0.25));
+        assert_eq!(fee(TransactionType::Transfer, 1, 1000.0), Some(5.0));
+        assert_eq!(fee(TransactionType::Deposit, 1, 1000.0), None);
+    }
+
+    #[test]
+    fn test_flat_fee_replaces_rule_without_tier() {
+        let mut schedule = FeeSchedule::from_json(SCHEDULE.as_bytes()).unwrap();
+        schedule
+            .set_flat_fee(TransactionType::Withdrawal, 2.0)
+            .unwrap();
+        assert_eq!(
+            schedule.get_fee(TransactionType::Withdrawal, 1, 10_000.0),
+            Some(2.0)
+        );
+        assert_eq!(schedule.get_fee(TransactionType::Withdrawal, 7, 10.0), None);
+        assert!(schedule.set_flat_fee(TransactionType::Lock, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_invalid_schedules_are_rejected() {
+        for schedule in [
+            r#"{"rules": [{"type": "withdrawal", "flat": -1}]}"#,
+            r#"{"rules": [{"type": "withdrawal", "min": 2, "max": 1}]}"#,
+            r#"{"rules": [{"type": "adjustment", "flat": 1}]}"#,
+        ] {
+            assert!(FeeSchedule::from_json(schedule.as_bytes()).is_err());
+        }
+    }
+}
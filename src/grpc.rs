@@ -0,0 +1,336 @@
+use crate::csv_processor::get_involved_clients;
+use crate::domain::{self, RejectionReason, TransactionType, parse_currency};
+use crate::server::{Engine, token_matches};
+use proto::payment_engine_server::PaymentEngine;
+use std::error::Error;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::{Stream, StreamExt};
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status, Streaming};
+
+/// Messages and service generated from proto/payment_engine.proto
+pub mod proto {
+    tonic::include_proto!("payment_engine.v1");
+}
+
+pub use proto::payment_engine_client::PaymentEngineClient;
+pub use proto::payment_engine_server::PaymentEngineServer;
+
+// Account changes kept for slow watchers, older ones are dropped
+const FEED_CAPACITY: usize = 1024;
+
+impl From<TransactionType> for proto::TransactionType {
+    fn from(transaction_type: TransactionType) -> Self {
+        match transaction_type {
+            TransactionType::Deposit => proto::TransactionType::Deposit,
+            TransactionType::Withdrawal => proto::TransactionType::Withdrawal,
+            TransactionType::Dispute => proto::TransactionType::Dispute,
+            TransactionType::Resolve => proto::TransactionType::Resolve,
+            TransactionType::Chargeback => proto::TransactionType::Chargeback,
+            TransactionType::Lock => proto::TransactionType::Lock,
+            TransactionType::Unlock => proto::TransactionType::Unlock,
+            TransactionType::Adjustment => proto::TransactionType::Adjustment,
+            TransactionType::Convert => proto::TransactionType::Convert,
+            TransactionType::Transfer => proto::TransactionType::Transfer,
+            TransactionType::Refund => proto::TransactionType::Refund,
+        }
+    }
+}
+
+impl TryFrom<proto::TransactionType> for TransactionType {
+    type Error = Status;
+
+    fn try_from(transaction_type: proto::TransactionType) -> Result<Self, Status> {
+        match transaction_type {
+            proto::TransactionType::Unspecified => {
+                Err(Status::invalid_argument("transaction type is required"))
+            }
+            proto::TransactionType::Deposit => Ok(TransactionType::Deposit),
+            proto::TransactionType::Withdrawal => Ok(TransactionType::Withdrawal),
+            proto::TransactionType::Dispute => Ok(TransactionType::Dispute),
+            proto::TransactionType::Resolve => Ok(TransactionType::Resolve),
+            proto::TransactionType::Chargeback => Ok(TransactionType::Chargeback),
+            proto::TransactionType::Lock => Ok(TransactionType::Lock),
+            proto::TransactionType::Unlock => Ok(TransactionType::Unlock),
+            proto::TransactionType::Adjustment => Ok(TransactionType::Adjustment),
+            proto::TransactionType::Convert => Ok(TransactionType::Convert),
+            proto::TransactionType::Transfer => Ok(TransactionType::Transfer),
+            proto::TransactionType::Refund => Ok(TransactionType::Refund),
+        }
+    }
+}
+
+impl From<RejectionReason> for proto::RejectionReason {
+    fn from(reason: RejectionReason) -> Self {
+        match reason {
+            RejectionReason::AccountLocked => proto::RejectionReason::AccountLocked,
+            RejectionReason::AdminOnly => proto::RejectionReason::AdminOnly,
+            RejectionReason::MissingAmount => proto::RejectionReason::MissingAmount,
+            RejectionReason::InvalidAmount => proto::RejectionReason::InvalidAmount,
+            RejectionReason::DuplicateTransaction => proto::RejectionReason::DuplicateTransaction,
+            RejectionReason::InsufficientFunds => proto::RejectionReason::InsufficientFunds,
+            RejectionReason::InsufficientHeldFunds => proto::RejectionReason::InsufficientHeldFunds,
+            RejectionReason::TransactionNotFound => proto::RejectionReason::TransactionNotFound,
+            RejectionReason::NotDisputable => proto::RejectionReason::NotDisputable,
+            RejectionReason::InvalidDisputeState => proto::RejectionReason::InvalidDisputeState,
+            RejectionReason::LateArrival => proto::RejectionReason::LateArrival,
+            RejectionReason::DisputeWindowClosed => proto::RejectionReason::DisputeWindowClosed,
+            RejectionReason::InvalidConversion => proto::RejectionReason::InvalidConversion,
+            RejectionReason::RateNotFound => proto::RejectionReason::RateNotFound,
+            RejectionReason::InvalidTransfer => proto::RejectionReason::InvalidTransfer,
+            RejectionReason::NotRefundable => proto::RejectionReason::NotRefundable,
+            RejectionReason::ExceedsRemainingAmount => {
+                proto::RejectionReason::ExceedsRemainingAmount
+            }
+        }
+    }
+}
+
+impl From<&domain::ClientAccount> for proto::ClientAccount {
+    fn from(account: &domain::ClientAccount) -> Self {
+        proto::ClientAccount {
+            client: account.id() as u32,
+            available: account.available(),
+            held: account.held(),
+            total: account.total(),
+            locked: account.is_locked(),
+            currency: account.currency().to_string(),
+        }
+    }
+}
+
+impl TryFrom<proto::Transaction> for domain::Transaction {
+    type Error = Status;
+
+    fn try_from(tx: proto::Transaction) -> Result<Self, Status> {
+        let transaction_type = proto::TransactionType::try_from(tx.r#type)
+            .map_err(|_| {
+                Status::invalid_argument(format!("unknown transaction type {}", tx.r#type))
+            })?
+            .try_into()?;
+        let client_id = |client: u32| {
+            u16::try_from(client)
+                .map_err(|_| Status::invalid_argument(format!("client {} is out of range", client)))
+        };
+        let parse = |code: Option<&str>| {
+            code.filter(|code| !code.is_empty())
+                .map(parse_currency)
+                .transpose()
+                .map_err(Status::invalid_argument)
+        };
+        let currency = parse(tx.currency.as_deref())?;
+        let to_currency = parse(tx.to_currency.as_deref())?;
+        Ok(domain::Transaction {
+            transaction_type,
+            client_id: client_id(tx.client)?,
+            id: tx.tx,
+            amount: tx.amount,
+            operator: tx.operator,
+            reason: tx.reason,
+            timestamp: tx.timestamp,
+            currency,
+            to_currency,
+            rate: None,
+            to_client: tx.to_client.map(client_id).transpose()?,
+            fee: None,
+        })
+    }
+}
+
+fn internal(err: Box<dyn Error>) -> Status {
+    Status::internal(err.to_string())
+}
+
+/// gRPC service of the payment engine
+///
+/// # Notes:
+///
+/// - Transactions are applied one at a time, with the same rules as the CLI and HTTP API
+/// - Every accepted transaction publishes the new state of the accounts of its clients, one per currency, to the WatchAccounts feed.
+///   Transfers publish both the sending and the receiving client
+/// - Administrative operations sent to Submit are rejected as AdminOnly, same as regular CSV inputs. SubmitAdmin
+///   applies them for callers with the admin token
+#[derive(Clone)]
+pub struct PaymentEngineService {
+    engine: Arc<Mutex<Engine>>,
+    changes: broadcast::Sender<proto::ClientAccount>,
+    admin_token: Option<String>,
+}
+
+impl PaymentEngineService {
+    pub fn new(engine: Engine) -> Self {
+        let (changes, _) = broadcast::channel(FEED_CAPACITY);
+        PaymentEngineService {
+            engine: Arc::new(Mutex::new(engine)),
+            changes,
+            admin_token: None,
+        }
+    }
+
+    /// Accept SubmitAdmin calls with "authorization: Bearer <token>" metadata
+    pub fn with_admin_token(mut self, token: &str) -> Self {
+        self.admin_token = Some(token.to_string());
+        self
+    }
+
+    // SubmitAdmin is disabled when the server has no admin token
+    fn authorize(&self, metadata: &MetadataMap) -> Result<(), Status> {
+        let Some(expected) = &self.admin_token else {
+            return Err(Status::permission_denied(
+                "administrative operations are disabled",
+            ));
+        };
+        let given = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match given {
+            Some(token) if token_matches(expected, token) => Ok(()),
+            _ => Err(Status::unauthenticated("missing or invalid admin token")),
+        }
+    }
+
+    async fn apply_admin(&self, tx: proto::Transaction) -> Result<proto::SubmitResponse, Status> {
+        let tx = domain::Transaction::try_from(tx)?;
+        let service = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let engine = service
+                .engine
+                .lock()
+                .map_err(|_| Status::unavailable("storage is unavailable"))?;
+            engine
+                .submit_admin(&tx)
+                .map_err(internal)?
+                .map_err(Status::invalid_argument)?;
+            service.publish_changes(&engine, &tx)?;
+            Ok(proto::SubmitResponse {
+                tx: tx.id,
+                client: tx.client_id as u32,
+                accepted: true,
+                rejection: proto::RejectionReason::Unspecified.into(),
+                message: String::new(),
+            })
+        })
+        .await
+        .map_err(|_| Status::unavailable("storage is unavailable"))?
+    }
+
+    // Every currency of the clients, as chargebacks and locks apply to all of them. Nobody watching is not an error
+    fn publish_changes(&self, engine: &Engine, tx: &domain::Transaction) -> Result<(), Status> {
+        let clients = get_involved_clients(tx, &engine.transaction_db).map_err(internal)?;
+        for client_id in clients {
+            let accounts = engine
+                .client_account_db
+                .get_client_accounts(client_id)
+                .map_err(internal)?;
+            for account in &accounts {
+                let _ = self.changes.send(proto::ClientAccount::from(account));
+            }
+        }
+        Ok(())
+    }
+
+    // The engine blocks on SQLite and event sinks, so it runs on the blocking thread pool
+    async fn apply(&self, tx: proto::Transaction) -> Result<proto::SubmitResponse, Status> {
+        let tx = domain::Transaction::try_from(tx)?;
+        let service = self.clone();
+        tokio::task::spawn_blocking(move || service.apply_locked(&tx))
+            .await
+            .map_err(|_| Status::unavailable("storage is unavailable"))?
+    }
+
+    // Apply under the engine lock, so the feed sees changes in the order they were applied
+    fn apply_locked(&self, tx: &domain::Transaction) -> Result<proto::SubmitResponse, Status> {
+        let engine = self
+            .engine
+            .lock()
+            .map_err(|_| Status::unavailable("storage is unavailable"))?;
+        let reason = engine.submit(tx).map_err(internal)?;
+        match reason {
+            None => {
+                self.publish_changes(&engine, tx)?;
+                Ok(proto::SubmitResponse {
+                    tx: tx.id,
+                    client: tx.client_id as u32,
+                    accepted: true,
+                    rejection: proto::RejectionReason::Unspecified.into(),
+                    message: String::new(),
+                })
+            }
+            Some(reason) => Ok(proto::SubmitResponse {
+                tx: tx.id,
+                client: tx.client_id as u32,
+                accepted: false,
+                rejection: proto::RejectionReason::from(reason).into(),
+                message: reason.to_string(),
+            }),
+        }
+    }
+}
+
+type AccountStream = Pin<Box<dyn Stream<Item = Result<proto::ClientAccount, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl PaymentEngine for PaymentEngineService {
+    async fn submit(
+        &self,
+        request: Request<proto::Transaction>,
+    ) -> Result<Response<proto::SubmitResponse>, Status> {
+        self.apply(request.into_inner()).await.map(Response::new)
+    }
+
+    // Transactions applied before an invalid message stay applied, same as a CSV with a bad row
+    async fn bulk_submit(
+        &self,
+        request: Request<Streaming<proto::Transaction>>,
+    ) -> Result<Response<proto::BulkSubmitResponse>, Status> {
+        let mut stream = request.into_inner();
+        let mut summary = proto::BulkSubmitResponse::default();
+        while let Some(tx) = stream.message().await? {
+            let response = self.apply(tx).await?;
+            if response.accepted {
+                summary.accepted += 1;
+            } else {
+                summary.rejected += 1;
+                summary.rejections.push(response);
+            }
+        }
+        Ok(Response::new(summary))
+    }
+
+    async fn submit_admin(
+        &self,
+        request: Request<proto::Transaction>,
+    ) -> Result<Response<proto::SubmitResponse>, Status> {
+        self.authorize(request.metadata())?;
+        self.apply_admin(request.into_inner())
+            .await
+            .map(Response::new)
+    }
+
+    type WatchAccountsStream = AccountStream;
+
+    async fn watch_accounts(
+        &self,
+        request: Request<proto::WatchAccountsRequest>,
+    ) -> Result<Response<AccountStream>, Status> {
+        let clients = request.into_inner().clients;
+        let stream = BroadcastStream::new(self.changes.subscribe()).filter_map(move |change| {
+            match change {
+                Ok(account) if clients.is_empty() || clients.contains(&account.client) => {
+                    Some(Ok(account))
+                }
+                Ok(_) => None,
+                // The watcher fell behind and missed changes, it has to reload accounts
+                Err(BroadcastStreamRecvError::Lagged(missed)) => Some(Err(Status::data_loss(
+                    format!("{} account changes were dropped", missed),
+                ))),
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
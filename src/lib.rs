@@ -1,3 +1,17 @@
+pub mod args;
 pub mod csv_processor;
 pub mod db;
 pub mod domain;
+pub mod event_time;
+pub mod events;
+pub mod fees;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod model;
+pub mod output;
+pub mod rates;
+pub mod replay;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod verify;
+pub mod workload;
@@ -6,8 +6,57 @@
 //! ## Usage:
 //! ```
 //! cargo run -- transactions.csv > accounts.csv
+//! cargo run -- transactions.csv --output-format table --precision 2 --columns client,total
+//! cargo run -- history transactions.csv --client 1 --output-format json
+//! cargo run -- replay transactions.csv --up-to-seq 100
+//! cargo run -- balance transactions.csv --client 7 --as-of-tx 1200
+//! cargo run -- balance transactions.csv --client 7 --as-of-time 2024-05-01T12:00:00Z
+//! cargo run -- transactions.csv --event-time 30s --rejections late.csv
+//! cargo run -- transactions.csv --rates rates.csv
+//! cargo run -- fees transactions.csv --fee-schedule fees.json
+//! cargo run -- negative_balance transactions.csv --allow-negative --chargeback-fee 15
 //! ```
 //!
+//! ## Commands:
+//! - (none): Output final Client Accounts
+//! - history: Output the chronological ledger of the client given by --client (tx, type, amount, disputed, resulting balances).
+//!   Only inputs that were applied are listed, including each dispute, resolve and chargeback
+//! - replay: Rebuild all accounts from the event log of accepted inputs and output them. Without --up-to-seq,
+//!   fails if the result differs from the processed accounts. With --up-to-seq N, stops after the Nth logged input
+//! - balance: Output the accounts given by --client as they were right after --as-of-tx (the deposit, withdrawal or
+//!   administrative operation with that ID), --as-of-line (the input line) or --as-of-time (the last input with a
+//!   timestamp at or before it, RFC 3339 or epoch milliseconds) was applied
+//! - fees: Output the fees charged, totalled per client, currency and transaction type (client, currency, type, count, total)
+//! - negative_balance: Output the accounts with negative Available funds, i.e. the clients owing money
+//!
+//! ## Processing Options:
+//! - --paranoid: Verify account invariants after every transaction, stopping at the first violating row
+//! - --admin: Privileged CSV with administrative operations (lock, unlock, adjustment), applied after the input file
+//! - --locked-allow: Transaction types still accepted on locked accounts, e.g. resolve,chargeback (default none)
+//! - --rejections: Write rows that were not applied, and why, to a CSV file (line, type, client, tx, reason)
+//! - --event-time: Apply rows in timestamp order instead of file order, waiting up to this lateness (e.g. 500ms, 30s, 5m)
+//!   for out of order rows. Every row needs a timestamp. Rows older than the newest timestamp seen minus the lateness
+//!   are rejected as late_arrival
+//! - --dispute-window: Reject disputes of transactions older than this, as dispute_window_closed. Either a duration
+//!   between timestamps (e.g. 30d) or a distance in accepted inputs (e.g. 5000rows)
+//! - --dispute-expiry: Close disputes open longer than this, same format as --dispute-window. Each one is closed by a
+//!   synthetic input with operator dispute-expiry, recorded in the journal and event log
+//! - --dispute-expiry-action: resolve (default) or chargeback, applied to expired disputes
+//! - --allow-negative: Disputes hold the full amount even if the client already spent it, leaving Available negative.
+//!   Without it such disputes are rejected as insufficient_funds
+//! - --events: Publish domain events (FundsDeposited, WithdrawalRejected, DisputeOpened, DisputeResolved, ChargebackApplied, AccountLocked,
+//!   AccountUnlocked, FundsConverted, FundsTransferred, FeeCharged, FundsRefunded) as JSON to jsonl:<path> (appended) or webhook:<url> (POST per event). Can be repeated
+//! - --rates: Exchange rates used by conversions, a CSV (from, to, rate, effective) or a JSON array of objects with the same fields
+//! - --fee-schedule: Fees charged per transaction type and client tier, a JSON file. Fees are posted to the house account
+//! - --chargeback-fee: Flat fee charged on every chargeback, replacing the chargeback rule without tier of --fee-schedule.
+//!   With --allow-negative it is charged in full, otherwise at most the Available funds
+//!
+//! ## Output Options:
+//! - --output-format: csv (default), json, jsonl or table. JSON amounts are strings with fixed decimals, e.g. "1.5000"
+//! - --sort: client (default), total or available. Amount orders are descending
+//! - --precision: Decimal places for amounts (default the ones of their currency, 4 without currency)
+//! - --columns: Comma separated subset of client, currency, available, held, total, locked
+//!
 //! ## CSV Input File:
 //! Input CSV must have the following fields: type of transaction, client ID, transaction ID, amount. E.g.:
 //! ```
@@ -15,57 +64,168 @@
 //! deposit, 1, 1, 2.0
 //! withdrawal, 1, 2, 1.5
 //! ```
+//! An optional timestamp field gives the event time, as RFC 3339 or epoch milliseconds. It is stored with the transaction. E.g.:
+//! ```
+//! type, client, tx, amount, timestamp
+//! deposit, 1, 1, 2.0, 2024-05-01T12:00:00Z
+//! withdrawal, 1, 2, 1.5, 1714564860000
+//! ```
+//! An optional currency field gives an ISO 4217 code. Rows without it use the implicit currency of the engine, with 4 decimal places. E.g.:
+//! ```
+//! type, client, tx, amount, currency
+//! deposit, 1, 1, 2.0, USD
+//! deposit, 1, 2, 1500, JPY
+//! ```
+//! Conversions take the source currency from the currency field and the target from an optional to_currency field. E.g.:
+//! ```
+//! type, client, tx, amount, currency, to_currency
+//! convert, 1, 3, 1.5, USD, EUR
+//! ```
+//! Transfers name the receiving client in an optional to_client field. E.g.:
+//! ```
+//! type, client, tx, amount, to_client
+//! transfer, 1, 4, 2.0, 2
+//! ```
 //!
 //! ## Supported Transaction Types:
 //! - Deposit: Increase funds
 //! - Withdrawal: Decrease Available funds, if enough
-//! - Dispute: Mark transaction for reversal investigation. (Done by tx, amount not needed)
-//! - Resolve: Resolves dispute, making funds available. (Done by tx, amount not needed)
-//! - Chargeback: Withdraw funds under dispute. Account is locked afterwards. (Done by tx, amount not needed)
+//! - Dispute: Mark transaction for reversal investigation. (Done by tx, an optional amount disputes only part of it)
+//! - Resolve: Resolves dispute, making funds available. (Done by tx, an optional amount resolves only part of it)
+//! - Chargeback: Withdraw funds under dispute. Account is locked afterwards. (Done by tx, an optional amount charges back only part of it)
+//! - Convert: Move Available funds to another currency at the rate in effect, if enough
+//! - Transfer: Move Available funds to another client in the same currency, if enough
+//! - Refund: Return Available funds of a deposit to its source. (Done by tx of the deposit, all that is left when no amount)
+//!
+//! Deposits and withdrawals are rejected when the amount is not positive, above 1000000000, or the tx ID was already used
+//!
+//! ## Administrative Operations:
+//! The CLI only accepts them from the --admin file, which also requires operator and reason fields. The file is applied after the
+//! whole input file, so its operations see the final state of the accounts, not the one at some input row. E.g.:
+//! ```
+//! type, client, tx, amount, operator, reason
+//! unlock, 1, 100, , ops-42, chargeback reviewed
+//! adjustment, 1, 101, -2.5, ops-42, duplicated deposit
+//! ```
+//! - Lock / Unlock: Freeze or unfreeze the account
+//! - Adjustment: Credit (positive) or debit (negative) Available funds. Cannot be disputed
+//!
+//! The servers apply them as they arrive, interleaved with regular transactions, from callers with the admin token
+//! (POST /admin/operations, SubmitAdmin)
 //!
 //! ## Implementation
 //! Implementation details on README.md
 
-mod csv_processor;
-mod db;
-mod domain;
+mod cli;
 
-use crate::db::{ClientAccountDB, TransactionDB};
+use crate::cli::Command;
+use rust_payment_engine::csv_processor;
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::output::{OutputOptions, format_accounts, format_records};
+use rust_payment_engine::replay::{balance_as_of, replay, verify_replay};
 use std::env;
 use std::error::Error;
-use std::ffi::OsString;
-use std::fs::File;
+use std::fs::{self, File};
+use std::path::PathBuf;
 
-fn run() -> Result<(), Box<dyn Error>> {
-    let file_path = get_first_arg()?;
-    let file = File::open(file_path)?;
+// SQLite files of one run, in a directory of their own that is deleted on every exit path, errors included
+struct RunDirectory {
+    dir: PathBuf,
+}
 
-    let transaction_db_path = "transactions.db";
-    let client_account_db_path = "client_accounts.db";
-    let transaction_db = TransactionDB::new(transaction_db_path)?;
-    let client_account_db = ClientAccountDB::new(client_account_db_path)?;
+impl RunDirectory {
+    fn create() -> Result<Self, Box<dyn Error>> {
+        let dir = env::temp_dir().join(format!("payment-engine-{}", std::process::id()));
+        // Left by a killed run with the same process ID
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        Ok(RunDirectory { dir })
+    }
 
-    csv_processor::process_csv(file, &transaction_db, &client_account_db)?;
+    fn path(&self, name: &str) -> String {
+        self.dir.join(name).to_string_lossy().into_owned()
+    }
+}
 
-    let accounts_csv = csv_processor::get_all_accounts_as_csv(&client_account_db)?;
-    println!("{}", accounts_csv);
+impl Drop for RunDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
 
-    // Delete the .db files at the end of the run to make re-testing easier
-    let _ = std::fs::remove_file(transaction_db_path);
-    let _ = std::fs::remove_file(client_account_db_path);
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut args = cli::parse_args(env::args_os().skip(1))?;
+    args.process
+        .events
+        .on_failure(|event, err| eprintln!("event {:?} was not published: {}", event, err));
+    let file = File::open(&args.input)?;
 
-    Ok(())
-}
+    // Declared first, so the databases are closed before their files are deleted
+    let run_directory = RunDirectory::create()?;
+    let transaction_db = TransactionDB::new(&run_directory.path("transactions.db"))?;
+    let client_account_db = ClientAccountDB::new(&run_directory.path("client_accounts.db"))?;
 
-fn get_first_arg() -> Result<OsString, Box<dyn Error>> {
-    match env::args_os().nth(1) {
-        None => Err(From::from("expected 1 argument, but got none")),
-        Some(file_path) => Ok(file_path),
+    let rejections = csv_processor::process_csv_with_options(
+        file,
+        &transaction_db,
+        &client_account_db,
+        &args.process,
+    )?;
+    if let Some(rejections_path) = &args.rejections_output {
+        let rejections_csv = format_records(&rejections, &OutputOptions::default())?;
+        std::fs::write(rejections_path, rejections_csv)?;
+    }
+    if let Some(admin_path) = &args.admin_input {
+        let admin_file = File::open(admin_path)?;
+        csv_processor::process_admin_csv_with_options(
+            admin_file,
+            &transaction_db,
+            &client_account_db,
+            &args.process,
+        )?;
     }
+
+    let output = match args.command {
+        Command::Accounts => {
+            csv_processor::get_all_accounts_formatted(&client_account_db, &args.output)?
+        }
+        Command::History { client_id } => {
+            csv_processor::get_client_history_formatted(&transaction_db, client_id, &args.output)?
+        }
+        Command::Replay { up_to_seq } => {
+            let replayed = replay(&transaction_db, up_to_seq)?;
+            // Only the full log is expected to match the current accounts
+            if up_to_seq.is_none() {
+                verify_replay(&replayed.client_account_db, &client_account_db)?;
+            }
+            format_accounts(
+                &replayed
+                    .client_account_db
+                    .get_all_accounts_ordered(args.output.order)?,
+                &args.output,
+            )?
+        }
+        Command::Balance { client_id, as_of } => {
+            let accounts = balance_as_of(&transaction_db, client_id, as_of)?;
+            if accounts.is_empty() {
+                return Err(format!("client {} had no account at that point", client_id).into());
+            }
+            format_accounts(&accounts, &args.output)?
+        }
+        Command::Fees => format_records(&transaction_db.get_fee_totals()?, &args.output)?,
+        Command::NegativeBalance => format_accounts(
+            &client_account_db.get_negative_accounts(args.output.order)?,
+            &args.output,
+        )?,
+    };
+    print!("{}", output);
+
+    Ok(())
 }
 
 fn main() {
     if let Err(err) = run() {
         println!("{}", err);
+        std::process::exit(1);
     }
 }
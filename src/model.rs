@@ -0,0 +1,230 @@
+use crate::csv_processor::ProcessOptions;
+use crate::domain::{
+    DisputeState, RejectionReason, Transaction, TransactionType, is_valid_amount, round_amount,
+};
+use crate::output::Record;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Final state of an account in the reference model
+///
+/// Serializes with the same columns and precision as `ClientAccount`, so expected outputs can be rendered
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModelAccount {
+    pub client: u16,
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
+    pub available: f64,
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
+    pub held: f64,
+    #[serde(serialize_with = "crate::domain::serialize_f64_4")]
+    pub total: f64,
+    pub locked: bool,
+}
+
+impl Record for ModelAccount {
+    const COLUMNS: &'static [&'static str] = &["client", "available", "held", "total", "locked"];
+}
+
+#[derive(Debug, Clone)]
+struct ModelTransaction {
+    client: u16,
+    transaction_type: TransactionType,
+    amount: f64,
+    dispute_state: DisputeState,
+    applied: bool,
+}
+
+/// Simple in-memory implementation of the engine rules, used to diff against the real engine
+///
+/// # Notes:
+///
+/// - Written independently of `ClientAccount` and the databases, on purpose
+/// - Only client transactions are modeled, administrative operations are rejected as in regular inputs
+#[derive(Debug, Default)]
+pub struct ReferenceModel {
+    options: ProcessOptions,
+    accounts: BTreeMap<u16, ModelAccount>,
+    transactions: HashMap<u32, ModelTransaction>,
+}
+
+impl ReferenceModel {
+    pub fn new(options: ProcessOptions) -> Self {
+        ReferenceModel {
+            options,
+            ..ReferenceModel::default()
+        }
+    }
+
+    /// Apply a transaction, returning the rejection reason when it was not applied
+    pub fn apply(&mut self, tx: &Transaction) -> Option<RejectionReason> {
+        if tx.transaction_type.is_admin() {
+            return Some(RejectionReason::AdminOnly);
+        }
+
+        let account = self
+            .accounts
+            .entry(tx.client_id)
+            .or_insert_with(|| ModelAccount {
+                client: tx.client_id,
+                ..ModelAccount::default()
+            });
+        if account.locked && !self.options.lock_policy.allows(tx.transaction_type) {
+            return Some(RejectionReason::AccountLocked);
+        }
+
+        let amount = tx.amount;
+        let result = match tx.transaction_type {
+            TransactionType::Deposit | TransactionType::Withdrawal => {
+                let Some(amount) = amount else {
+                    return Some(RejectionReason::MissingAmount);
+                };
+                // Amounts with more than 4 decimal places are rejected, not rounded
+                if !is_valid_amount(amount) || round_amount(amount) != amount {
+                    return Some(RejectionReason::InvalidAmount);
+                }
+                if self.transactions.contains_key(&tx.id) {
+                    return Some(RejectionReason::DuplicateTransaction);
+                }
+                let result = if tx.transaction_type == TransactionType::Deposit {
+                    account.available += amount;
+                    account.total += amount;
+                    None
+                } else if account.available >= amount {
+                    account.available -= amount;
+                    account.total -= amount;
+                    None
+                } else {
+                    Some(RejectionReason::InsufficientFunds)
+                };
+                // Failed withdrawals are still stored, same as the engine, but cannot be disputed
+                self.transactions.insert(
+                    tx.id,
+                    ModelTransaction {
+                        client: tx.client_id,
+                        transaction_type: tx.transaction_type,
+                        amount,
+                        dispute_state: DisputeState::Undisputed,
+                        applied: result.is_none(),
+                    },
+                );
+                result
+            }
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+                let referred = match self.transactions.get_mut(&tx.id) {
+                    Some(referred) if referred.client == tx.client_id && referred.applied => {
+                        referred
+                    }
+                    _ => return Some(RejectionReason::TransactionNotFound),
+                };
+                if !referred.transaction_type.is_disputable() {
+                    return Some(RejectionReason::NotDisputable);
+                }
+                // Disputes of the model always take the full amount
+                let open = referred.dispute_state == DisputeState::Disputed;
+                let allowed = match tx.transaction_type {
+                    TransactionType::Dispute => {
+                        !open && referred.dispute_state != DisputeState::ChargedBack
+                    }
+                    _ => open,
+                };
+                if !allowed {
+                    return Some(RejectionReason::InvalidDisputeState);
+                }
+                let amount = referred.amount;
+                let in_dispute = match tx.transaction_type {
+                    TransactionType::Dispute => amount,
+                    _ => 0.0,
+                };
+                let next_state = DisputeState::after(tx.transaction_type, in_dispute);
+                match tx.transaction_type {
+                    TransactionType::Dispute if account.available < amount => {
+                        return Some(RejectionReason::InsufficientFunds);
+                    }
+                    TransactionType::Dispute => {
+                        account.available -= amount;
+                        account.held += amount;
+                    }
+                    _ if account.held < amount => {
+                        return Some(RejectionReason::InsufficientHeldFunds);
+                    }
+                    TransactionType::Resolve => {
+                        account.held -= amount;
+                        account.available += amount;
+                    }
+                    _ => {
+                        account.held -= amount;
+                        account.total -= amount;
+                        account.locked = true;
+                    }
+                }
+                referred.dispute_state = next_state;
+                None
+            }
+            TransactionType::Lock | TransactionType::Unlock | TransactionType::Adjustment => None,
+            // Model accounts only hold the implicit currency, which cannot be converted
+            TransactionType::Convert => Some(RejectionReason::InvalidConversion),
+            // Workloads do not generate transfers, the model only follows changes of a single client
+            TransactionType::Transfer => Some(RejectionReason::InvalidTransfer),
+            // Workloads do not generate refunds either, the model treats them as referring to unknown deposits
+            TransactionType::Refund => Some(RejectionReason::TransactionNotFound),
+        };
+
+        // Balances are stored with 4 decimal places by the engine
+        account.available = round_amount(account.available);
+        account.held = round_amount(account.held);
+        account.total = round_amount(account.total);
+        result
+    }
+
+    /// All accounts, sorted by client ID
+    pub fn accounts(&self) -> Vec<ModelAccount> {
+        self.accounts.values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(
+        transaction_type: TransactionType,
+        client_id: u16,
+        id: u32,
+        amount: Option<f64>,
+    ) -> Transaction {
+        Transaction {
+            transaction_type,
+            client_id,
+            id,
+            amount,
+            operator: None,
+            reason: None,
+            timestamp: None,
+            currency: None,
+            to_currency: None,
+            rate: None,
+            to_client: None,
+            fee: None,
+        }
+    }
+
+    #[test]
+    fn test_chargeback_locks_and_reverses_funds() {
+        let mut model = ReferenceModel::default();
+        assert_eq!(
+            model.apply(&tx(TransactionType::Deposit, 1, 1, Some(10.0))),
+            None
+        );
+        assert_eq!(model.apply(&tx(TransactionType::Dispute, 1, 1, None)), None);
+        assert_eq!(
+            model.apply(&tx(TransactionType::Chargeback, 1, 1, None)),
+            None
+        );
+        assert_eq!(
+            model.apply(&tx(TransactionType::Deposit, 1, 2, Some(1.0))),
+            Some(RejectionReason::AccountLocked)
+        );
+        let account = &model.accounts()[0];
+        assert_eq!((account.total, account.locked), (0.0, true));
+    }
+}
@@ -0,0 +1,540 @@
+use crate::db::AccountOrder;
+use crate::domain::{ClientAccount, FeeTotal, HistoryEntry, Rejection, currency_precision};
+use csv::WriterBuilder;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::str::FromStr;
+
+/// Supported formats for the final account state
+///
+/// # Formats:
+///
+/// - Csv: Default format, same as the original output
+/// - Json: Single JSON array with one object per account
+/// - Jsonl: One JSON object per line
+/// - Table: Aligned columns, intended for terminals
+///
+/// JSON amounts are strings with the same fixed decimal places as the CSV, e.g. "1.5000", so no precision is lost
+/// to floating point parsing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Json,
+    Jsonl,
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "table" => Ok(OutputFormat::Table),
+            other => Err(format!(
+                "unknown output format '{}', expected one of: csv, json, jsonl, table",
+                other
+            )),
+        }
+    }
+}
+
+/// Controls how accounts (and other records, like client history) are rendered
+///
+/// # Notes:
+///
+/// - Columns are the serde names of the record, e.g. client, available, held, total, locked for `ClientAccount`
+/// - When no columns are given, all of them are rendered in struct order
+/// - Order is applied when accounts are loaded from the database, other records are rendered as given
+/// - Amounts use the decimal places of the currency of their record unless a precision is given
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    pub order: AccountOrder,
+    pub precision: Option<usize>,
+    pub columns: Option<Vec<String>>,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions {
+            format: OutputFormat::Csv,
+            order: AccountOrder::ClientId,
+            precision: None,
+            columns: None,
+        }
+    }
+}
+
+/// Record rendered by `format_records`
+///
+/// # Notes:
+///
+/// - COLUMNS are the serde names of the fields, in struct order. They are the CSV header and the names accepted
+///   by --columns
+pub trait Record: Serialize {
+    const COLUMNS: &'static [&'static str];
+}
+
+impl Record for ClientAccount {
+    const COLUMNS: &'static [&'static str] =
+        &["client", "currency", "available", "held", "total", "locked"];
+}
+
+impl Record for HistoryEntry {
+    const COLUMNS: &'static [&'static str] = &[
+        "tx",
+        "type",
+        "amount",
+        "currency",
+        "dispute_state",
+        "operator",
+        "available",
+        "held",
+        "total",
+    ];
+}
+
+impl Record for FeeTotal {
+    const COLUMNS: &'static [&'static str] = &["client", "currency", "type", "count", "total"];
+}
+
+impl Record for Rejection {
+    const COLUMNS: &'static [&'static str] = &["line", "type", "client", "tx", "reason"];
+}
+
+type Row = Vec<(String, Value)>;
+
+fn available_columns<T: Record>() -> Vec<String> {
+    T::COLUMNS.iter().map(|column| column.to_string()).collect()
+}
+
+fn selected_columns<T: Record>(options: &OutputOptions) -> Result<Vec<String>, Box<dyn Error>> {
+    let available = available_columns::<T>();
+    match &options.columns {
+        None => Ok(available),
+        Some(columns) => {
+            if columns.is_empty() {
+                return Err(From::from("at least one column must be selected"));
+            }
+            for column in columns {
+                if !available.contains(column) {
+                    return Err(format!(
+                        "unknown column '{}', expected any of: {}",
+                        column,
+                        available.join(", ")
+                    )
+                    .into());
+                }
+            }
+            Ok(columns.clone())
+        }
+    }
+}
+
+// Columns serialized as 4 decimal strings by serialize_f64_4. Other text columns are kept as they are, even numeric ones
+const AMOUNT_COLUMNS: [&str; 5] = ["available", "held", "total", "amount", "fee"];
+
+// Decimal places of the amounts of a record: the requested ones, or the ones of its currency
+fn record_precision(record: &Map<String, Value>, precision: Option<usize>) -> usize {
+    precision.unwrap_or_else(|| {
+        let currency = record.get("currency").and_then(Value::as_str);
+        currency_precision(currency.unwrap_or_default()) as usize
+    })
+}
+
+// Re-format amounts to the requested precision
+fn apply_precision(column: &str, value: Value, precision: usize) -> Value {
+    if !AMOUNT_COLUMNS.contains(&column) {
+        return value;
+    }
+    match value {
+        Value::String(s) => match s.parse::<f64>() {
+            Ok(amount) => Value::String(format!("{:.*}", precision, amount)),
+            Err(_) => Value::String(s),
+        },
+        other => other,
+    }
+}
+
+fn to_rows<T: Serialize>(
+    records: &[T],
+    columns: &[String],
+    precision: Option<usize>,
+) -> Result<Vec<Row>, Box<dyn Error>> {
+    let mut rows = Vec::with_capacity(records.len());
+    for record in records {
+        let mut map = match serde_json::to_value(record)? {
+            Value::Object(map) => map,
+            _ => return Err(From::from("record must serialize as a map")),
+        };
+        let precision = record_precision(&map, precision);
+        let row = columns
+            .iter()
+            .map(|column| {
+                let value = map.remove(column).unwrap_or(Value::Null);
+                (column.clone(), apply_precision(column, value, precision))
+            })
+            .collect();
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn cell_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn row_to_object(row: Row) -> Value {
+    Value::Object(row.into_iter().collect::<Map<String, Value>>())
+}
+
+fn render_csv(columns: &[String], rows: &[Row]) -> Result<String, Box<dyn Error>> {
+    let mut wtr = WriterBuilder::new().from_writer(vec![]);
+    wtr.write_record(columns)?;
+    for row in rows {
+        wtr.write_record(row.iter().map(|(_, value)| cell_to_string(value)))?;
+    }
+    let data = String::from_utf8(wtr.into_inner()?)?;
+    Ok(data)
+}
+
+fn render_json(rows: Vec<Row>) -> Result<String, Box<dyn Error>> {
+    let values: Vec<Value> = rows.into_iter().map(row_to_object).collect();
+    Ok(serde_json::to_string_pretty(&values)? + "\n")
+}
+
+fn render_jsonl(rows: Vec<Row>) -> Result<String, Box<dyn Error>> {
+    let mut data = String::new();
+    for row in rows {
+        data.push_str(&serde_json::to_string(&row_to_object(row))?);
+        data.push('\n');
+    }
+    Ok(data)
+}
+
+// Numbers (including amount strings) are right aligned, everything else left aligned
+fn render_table(columns: &[String], rows: &[Row]) -> String {
+    let cells: Vec<Vec<(String, bool)>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|(_, value)| {
+                    let text = cell_to_string(value);
+                    let numeric = value.is_number() || text.parse::<f64>().is_ok();
+                    (text, numeric)
+                })
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            cells
+                .iter()
+                .map(|row| row[i].0.len())
+                .chain(std::iter::once(column.len()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut data = String::new();
+    let header: Vec<String> = columns
+        .iter()
+        .zip(&widths)
+        .map(|(column, width)| format!("{:<width$}", column, width = width))
+        .collect();
+    data.push_str(header.join("  ").trim_end());
+    data.push('\n');
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    data.push_str(&separator.join("  "));
+    data.push('\n');
+    for row in cells {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|((text, numeric), width)| {
+                if *numeric {
+                    format!("{:>width$}", text, width = width)
+                } else {
+                    format!("{:<width$}", text, width = width)
+                }
+            })
+            .collect();
+        data.push_str(line.join("  ").trim_end());
+        data.push('\n');
+    }
+    data
+}
+
+// Inputs without currency keep the original columns, the currency column only shows up once a currency is used
+fn hide_unused_currency<T: Record>(
+    options: &OutputOptions,
+    used: bool,
+) -> Result<OutputOptions, Box<dyn Error>> {
+    if used || options.columns.is_some() {
+        return Ok(options.clone());
+    }
+    let columns = available_columns::<T>()
+        .into_iter()
+        .filter(|column| column != "currency")
+        .collect();
+    Ok(OutputOptions {
+        columns: Some(columns),
+        ..options.clone()
+    })
+}
+
+/// Render accounts in the format, precision and columns given by the options
+///
+/// # Notes:
+///
+/// - The currency column is left out when every account is in the implicit currency, unless columns are given
+pub fn format_accounts(
+    accounts: &[ClientAccount],
+    options: &OutputOptions,
+) -> Result<String, Box<dyn Error>> {
+    let used = accounts
+        .iter()
+        .any(|account| !account.currency().is_empty());
+    format_records(
+        accounts,
+        &hide_unused_currency::<ClientAccount>(options, used)?,
+    )
+}
+
+/// Render a client history, leaving out the currency column the same way as `format_accounts`
+pub fn format_history(
+    entries: &[HistoryEntry],
+    options: &OutputOptions,
+) -> Result<String, Box<dyn Error>> {
+    let used = entries.iter().any(|entry| !entry.currency.is_empty());
+    format_records(
+        entries,
+        &hide_unused_currency::<HistoryEntry>(options, used)?,
+    )
+}
+
+/// Amounts of a serialized record, or of each record of an array, with the decimal places of the record currency
+///
+/// # Notes:
+///
+/// - Used by the HTTP API, so its amounts are formatted the same as the CLI output
+pub fn with_currency_precision(value: Value) -> Value {
+    match value {
+        Value::Array(records) => {
+            Value::Array(records.into_iter().map(with_currency_precision).collect())
+        }
+        Value::Object(record) => {
+            let precision = record_precision(&record, None);
+            Value::Object(
+                record
+                    .into_iter()
+                    .map(|(column, value)| {
+                        let value = apply_precision(&column, value, precision);
+                        (column, value)
+                    })
+                    .collect(),
+            )
+        }
+        other => other,
+    }
+}
+
+/// Render any serializable record in the format, precision and columns given by the options
+pub fn format_records<T: Record>(
+    records: &[T],
+    options: &OutputOptions,
+) -> Result<String, Box<dyn Error>> {
+    let columns = selected_columns::<T>(options)?;
+    let rows = to_rows(records, &columns, options.precision)?;
+    match options.format {
+        OutputFormat::Csv => render_csv(&columns, &rows),
+        OutputFormat::Json => render_json(rows),
+        OutputFormat::Jsonl => render_jsonl(rows),
+        OutputFormat::Table => Ok(render_table(&columns, &rows)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DisputeState, RejectionReason, TransactionType};
+
+    fn sample_accounts() -> Vec<ClientAccount> {
+        let mut first = ClientAccount::new(1);
+        first.add_funds(1.5);
+        let mut second = ClientAccount::new(2);
+        second.add_funds(20.25);
+        second.hold_funds(0.25).unwrap();
+        vec![first, second]
+    }
+
+    #[test]
+    fn test_default_csv_matches_original_format() {
+        let data = format_accounts(&sample_accounts(), &OutputOptions::default()).unwrap();
+        assert_eq!(
+            data,
+            "client,available,held,total,locked\n\
+             1,1.5000,0.0000,1.5000,false\n\
+             2,20.0000,0.2500,20.2500,false\n"
+        );
+    }
+
+    #[test]
+    fn test_json_with_precision_and_columns() {
+        let options = OutputOptions {
+            format: OutputFormat::Json,
+            precision: Some(2),
+            columns: Some(vec!["client".to_string(), "total".to_string()]),
+            ..OutputOptions::default()
+        };
+        let data = format_accounts(&sample_accounts(), &options).unwrap();
+        let parsed: Value = serde_json::from_str(&data).unwrap();
+        assert_eq!(
+            parsed,
+            serde_json::json!([
+                {"client": 1, "total": "1.50"},
+                {"client": 2, "total": "20.25"}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_jsonl_emits_one_object_per_line() {
+        let options = OutputOptions {
+            format: OutputFormat::Jsonl,
+            ..OutputOptions::default()
+        };
+        let data = format_accounts(&sample_accounts(), &options).unwrap();
+        let lines: Vec<&str> = data.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"client":1,"available":"1.5000","held":"0.0000","total":"1.5000","locked":false}"#
+        );
+    }
+
+    #[test]
+    fn test_table_is_aligned() {
+        let options = OutputOptions {
+            format: OutputFormat::Table,
+            precision: Some(1),
+            columns: Some(vec![
+                "client".to_string(),
+                "available".to_string(),
+                "locked".to_string(),
+            ]),
+            ..OutputOptions::default()
+        };
+        let data = format_accounts(&sample_accounts(), &options).unwrap();
+        assert_eq!(
+            data,
+            "client  available  locked\n\
+             ------  ---------  ------\n     \
+             1        1.5  false\n     \
+             2       20.0  false\n"
+        );
+    }
+
+    #[test]
+    fn test_currency_column_only_when_used() {
+        let mut accounts = sample_accounts();
+        let mut euros = ClientAccount::with_currency(1, "EUR");
+        euros.add_funds(3.0);
+        accounts.insert(1, euros);
+        let data = format_accounts(&accounts, &OutputOptions::default()).unwrap();
+        assert_eq!(
+            data,
+            "client,currency,available,held,total,locked\n\
+             1,,1.5000,0.0000,1.5000,false\n\
+             1,EUR,3.00,0.00,3.00,false\n\
+             2,,20.0000,0.2500,20.2500,false\n"
+        );
+    }
+
+    #[test]
+    fn test_precision_only_applies_to_amounts() {
+        let entry = HistoryEntry {
+            id: 7,
+            transaction_type: TransactionType::Deposit,
+            amount: Some(2.5),
+            currency: String::new(),
+            dispute_state: DisputeState::Undisputed,
+            operator: Some("inf".to_string()),
+            available: 2.5,
+            held: 0.0,
+            total: 2.5,
+        };
+        let options = OutputOptions {
+            precision: Some(1),
+            columns: Some(vec![
+                "tx".to_string(),
+                "amount".to_string(),
+                "operator".to_string(),
+                "total".to_string(),
+            ]),
+            ..OutputOptions::default()
+        };
+        let data = format_records(&[entry], &options).unwrap();
+        assert_eq!(data, "tx,amount,operator,total\n7,2.5,inf,2.5\n");
+    }
+
+    // COLUMNS have to follow the serde names, or selected columns would render as empty
+    fn assert_columns<T: Record>(record: &T) {
+        let Value::Object(map) = serde_json::to_value(record).unwrap() else {
+            panic!("record must serialize as a map");
+        };
+        assert_eq!(map.keys().collect::<Vec<_>>(), T::COLUMNS);
+    }
+
+    #[test]
+    fn test_columns_match_serialized_fields() {
+        assert_columns(&sample_accounts()[0]);
+        assert_columns(&HistoryEntry {
+            id: 1,
+            transaction_type: TransactionType::Deposit,
+            amount: None,
+            currency: String::new(),
+            dispute_state: DisputeState::Undisputed,
+            operator: None,
+            available: 0.0,
+            held: 0.0,
+            total: 0.0,
+        });
+        assert_columns(&FeeTotal {
+            client: 1,
+            currency: String::new(),
+            transaction_type: TransactionType::Withdrawal,
+            count: 1,
+            total: 0.5,
+        });
+        assert_columns(&Rejection {
+            line: Some(2),
+            transaction_type: TransactionType::Withdrawal,
+            client_id: 1,
+            id: 1,
+            reason: RejectionReason::InsufficientFunds,
+        });
+    }
+
+    #[test]
+    fn test_unknown_column_is_rejected() {
+        let options = OutputOptions {
+            columns: Some(vec!["balance".to_string()]),
+            ..OutputOptions::default()
+        };
+        assert!(format_accounts(&sample_accounts(), &options).is_err());
+    }
+}
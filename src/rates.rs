@@ -0,0 +1,153 @@
+use crate::domain::{deserialize_timestamp, parse_currency};
+use csv::{ReaderBuilder, Trim};
+use serde::{Deserialize, Deserializer, de::Error as _};
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::sync::Arc;
+
+// Rate tables always name both currencies
+fn deserialize_code<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    parse_currency(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+/// Price of one unit of `from` in `to`, applied from `effective` on
+///
+/// # Notes:
+///
+/// - Effective is RFC 3339 or epoch milliseconds. Without it the rate applies since the beginning
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExchangeRate {
+    #[serde(deserialize_with = "deserialize_code")]
+    pub from: String,
+    #[serde(deserialize_with = "deserialize_code")]
+    pub to: String,
+    pub rate: f64,
+    #[serde(deserialize_with = "deserialize_timestamp", default)]
+    pub effective: Option<i64>,
+}
+
+/// Exchange rates used by conversions, loaded from a CSV or JSON file
+///
+/// # Notes:
+///
+/// - CSV files have the fields from, to, rate, effective. JSON files are an array of objects with the same fields
+/// - A conversion uses the latest rate of its currency pair effective at its timestamp,
+///   or the latest one of the table when it has no timestamp
+/// - Only the given direction is used, a USD to EUR rate does not price EUR to USD
+#[derive(Debug, Clone, Default)]
+pub struct RateTable {
+    // Sorted by effective time, rates without one first
+    rates: Arc<Vec<ExchangeRate>>,
+}
+
+impl RateTable {
+    pub fn new(mut rates: Vec<ExchangeRate>) -> Result<Self, Box<dyn Error>> {
+        for rate in &rates {
+            if !rate.rate.is_finite() || rate.rate <= 0.0 {
+                return Err(format!(
+                    "rate {} from {} to {} must be a positive number",
+                    rate.rate, rate.from, rate.to
+                )
+                .into());
+            }
+            if rate.from == rate.to {
+                return Err(format!("rate from {} to itself is not allowed", rate.from).into());
+            }
+        }
+        // Stable, so rates with the same effective time keep the file order and the last one wins
+        rates.sort_by_key(|rate| rate.effective);
+        Ok(RateTable {
+            rates: Arc::new(rates),
+        })
+    }
+
+    pub fn from_csv<R: Read>(reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut rdr = ReaderBuilder::new().trim(Trim::All).from_reader(reader);
+        let rates = rdr
+            .deserialize()
+            .collect::<Result<Vec<ExchangeRate>, _>>()?;
+        Self::new(rates)
+    }
+
+    pub fn from_json<R: Read>(reader: R) -> Result<Self, Box<dyn Error>> {
+        Self::new(serde_json::from_reader(reader)?)
+    }
+
+    // Files ending in .json are read as JSON, anything else as CSV
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file =
+            File::open(path).map_err(|err| format!("cannot open rate table {}: {}", path, err))?;
+        if path.to_ascii_lowercase().ends_with(".json") {
+            Self::from_json(file)
+        } else {
+            Self::from_csv(file)
+        }
+    }
+
+    /// Rate from one currency to another at a point in time, None if no rate of the pair is effective yet
+    pub fn get_rate(&self, from: &str, to: &str, at: Option<i64>) -> Option<f64> {
+        self.rates
+            .iter()
+            .rev()
+            .filter(|rate| rate.from == from && rate.to == to)
+            .find(|rate| match (rate.effective, at) {
+                (Some(effective), Some(at)) => effective <= at,
+                _ => at.is_none() || rate.effective.is_none(),
+            })
+            .map(|rate| rate.rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATES: &str = "from, to, rate, effective
+usd, EUR, 0.90, 2024-05-01T00:00:00Z
+USD, EUR, 0.92, 2024-06-01T00:00:00Z
+USD, JPY, 150,
+";
+
+    #[test]
+    fn test_latest_effective_rate_is_used() {
+        let table = RateTable::from_csv(RATES.as_bytes()).unwrap();
+        let at = |timestamp: &str| Some(crate::domain::parse_timestamp(timestamp).unwrap());
+        assert_eq!(
+            table.get_rate("USD", "EUR", at("2024-04-30T23:59:59Z")),
+            None
+        );
+        assert_eq!(
+            table.get_rate("USD", "EUR", at("2024-05-15T00:00:00Z")),
+            Some(0.90)
+        );
+        assert_eq!(
+            table.get_rate("USD", "EUR", at("2024-06-01T00:00:00Z")),
+            Some(0.92)
+        );
+        assert_eq!(table.get_rate("USD", "EUR", None), Some(0.92));
+        assert_eq!(
+            table.get_rate("USD", "JPY", at("2000-01-01T00:00:00Z")),
+            Some(150.0)
+        );
+        assert_eq!(table.get_rate("EUR", "USD", None), None);
+    }
+
+    #[test]
+    fn test_json_rates_and_validation() {
+        let table = RateTable::from_json(
+            r#"[{"from": "GBP", "to": "USD", "rate": 1.25, "effective": 1714521600000}]"#
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            table.get_rate("GBP", "USD", Some(1714521600000)),
+            Some(1.25)
+        );
+        assert!(RateTable::from_csv("from,to,rate\nUSD,EUR,-1\n".as_bytes()).is_err());
+        assert!(RateTable::from_csv("from,to,rate\nUSD,USD,1\n".as_bytes()).is_err());
+    }
+}
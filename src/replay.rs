@@ -0,0 +1,198 @@
+use crate::csv_processor::{ProcessOptions, process_admin_operation, process_transaction_at_line};
+use crate::db::{ClientAccountDB, LoggedInput, TransactionDB};
+use crate::domain::{BALANCE_TOLERANCE, ClientAccount, DisputePolicy, LockPolicy, TransactionType};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Accounts rebuilt from the event log, kept in new in-memory databases
+///
+/// # Notes:
+///
+/// - last_seq is the sequence number of the last replayed input, None if the log was empty
+pub struct Replay {
+    pub transaction_db: TransactionDB,
+    pub client_account_db: ClientAccountDB,
+    pub last_seq: Option<i64>,
+}
+
+// Only accepted inputs are logged, so they are applied without a lock policy, and disputes can leave balances
+// negative. The policies in effect when they were received already allowed them
+fn apply_inputs(inputs: Vec<LoggedInput>) -> Result<Replay, Box<dyn Error>> {
+    let replayed = Replay {
+        transaction_db: TransactionDB::new(":memory:")?,
+        client_account_db: ClientAccountDB::new(":memory:")?,
+        last_seq: None,
+    };
+    let options = ProcessOptions {
+        lock_policy: LockPolicy::allow(&[
+            TransactionType::Deposit,
+            TransactionType::Withdrawal,
+            TransactionType::Dispute,
+            TransactionType::Resolve,
+            TransactionType::Chargeback,
+            TransactionType::Convert,
+            TransactionType::Transfer,
+            TransactionType::Refund,
+        ]),
+        disputes: DisputePolicy {
+            allow_negative: true,
+            ..DisputePolicy::default()
+        },
+        ..ProcessOptions::default()
+    };
+
+    let mut last_seq = None;
+    for input in inputs {
+        let tx = &input.transaction;
+        if tx.transaction_type.is_admin() {
+            process_admin_operation(tx, &replayed.transaction_db, &replayed.client_account_db)?;
+        } else if let Some(reason) = process_transaction_at_line(
+            tx,
+            input.line,
+            &replayed.transaction_db,
+            &replayed.client_account_db,
+            &options,
+        )? {
+            return Err(format!(
+                "logged input {} (tx {}) was rejected on replay: {}",
+                input.seq, tx.id, reason
+            )
+            .into());
+        }
+        last_seq = Some(input.seq);
+    }
+    Ok(Replay {
+        last_seq,
+        ..replayed
+    })
+}
+
+/// Rebuild all account state from the event log of `transaction_db`, optionally only up to a sequence number
+///
+/// # Notes:
+///
+/// - Fails if a logged input is rejected, as the log and the accounts have diverged
+/// - No domain events are published
+pub fn replay(
+    transaction_db: &TransactionDB,
+    up_to_seq: Option<i64>,
+) -> Result<Replay, Box<dyn Error>> {
+    apply_inputs(transaction_db.get_event_log(up_to_seq)?)
+}
+
+/// Point of the event log a balance is computed at
+///
+/// # Points:
+///
+/// - Tx: Right after the input that introduced the tx id (deposit, withdrawal or administrative operation)
+/// - Line: Right after the last input read up to a CSV line
+/// - Seq: Right after an event log sequence number
+/// - Time: Right after the last logged input with a timestamp at or before this epoch milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    Tx(u32),
+    Line(u64),
+    Seq(i64),
+    Time(i64),
+}
+
+/// State of the accounts of a client at a past point, one per currency, rebuilt from the event log without changing live state
+///
+/// # Notes:
+///
+/// - Empty when the client had no accepted input up to that point
+/// - Disputes only refer to transactions of the same client, so only inputs of that client are replayed.
+///   Transfers link the balances of different clients, so once the log has one every input is replayed
+/// - Fails if the tx id was never applied, e.g. a rejected withdrawal
+pub fn balance_as_of(
+    transaction_db: &TransactionDB,
+    client_id: u16,
+    as_of: AsOf,
+) -> Result<Vec<ClientAccount>, Box<dyn Error>> {
+    let up_to_seq = match as_of {
+        AsOf::Tx(id) => transaction_db
+            .get_event_seq_of_tx(id)?
+            .ok_or_else(|| format!("tx {} is not in the event log", id))?,
+        AsOf::Line(line) => transaction_db.get_event_seq_of_line(line)?.unwrap_or(0),
+        AsOf::Seq(seq) => seq,
+        AsOf::Time(timestamp) => transaction_db
+            .get_event_seq_of_time(timestamp)?
+            .unwrap_or(0),
+    };
+    let inputs = if transaction_db.has_transfers(Some(up_to_seq))? {
+        transaction_db.get_event_log(Some(up_to_seq))?
+    } else {
+        transaction_db.get_client_event_log(client_id, Some(up_to_seq))?
+    };
+    let replayed = apply_inputs(inputs)?;
+    replayed.client_account_db.get_client_accounts(client_id)
+}
+
+fn accounts_match(a: &ClientAccount, b: &ClientAccount) -> bool {
+    (a.available() - b.available()).abs() < BALANCE_TOLERANCE
+        && (a.held() - b.held()).abs() < BALANCE_TOLERANCE
+        && (a.total() - b.total()).abs() < BALANCE_TOLERANCE
+        && a.is_locked() == b.is_locked()
+}
+
+/// Check that the replayed accounts are the same as the materialized ones, returning the first difference
+///
+/// # Notes:
+///
+/// - Accounts are compared per client and currency
+/// - Rejected inputs can still create an empty account, which the log does not know about.
+///   A missing account is compared as a new, empty one, locked if another account of the client is
+pub fn verify_replay(
+    replayed: &ClientAccountDB,
+    client_account_db: &ClientAccountDB,
+) -> Result<(), Box<dyn Error>> {
+    type Accounts = BTreeMap<(u16, String), ClientAccount>;
+    let by_key = |accounts: Vec<ClientAccount>| -> Accounts {
+        accounts
+            .into_iter()
+            .map(|account| ((account.id(), account.currency().to_string()), account))
+            .collect()
+    };
+    let replayed = by_key(replayed.get_all_accounts()?);
+    let materialized = by_key(client_account_db.get_all_accounts()?);
+    let find = |accounts: &Accounts, (id, currency): &(u16, String)| {
+        accounts
+            .get(&(*id, currency.clone()))
+            .cloned()
+            .unwrap_or_else(|| {
+                let mut account = ClientAccount::with_currency(*id, currency);
+                if accounts
+                    .values()
+                    .any(|other| other.id() == *id && other.is_locked())
+                {
+                    account.lock_account();
+                }
+                account
+            })
+    };
+
+    for key in materialized.keys().chain(replayed.keys()) {
+        let (account, other) = (find(&materialized, key), find(&replayed, key));
+        if !accounts_match(&account, &other) {
+            let (id, currency) = key;
+            let currency = if currency.is_empty() {
+                String::new()
+            } else {
+                format!(" {}", currency)
+            };
+            return Err(format!(
+                "client {}{} is available {:.4}, held {:.4}, locked {} but replays to available {:.4}, held {:.4}, locked {}",
+                id,
+                currency,
+                account.available(),
+                account.held(),
+                account.is_locked(),
+                other.available(),
+                other.held(),
+                other.is_locked()
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,434 @@
+use crate::csv_processor::{
+    ProcessOptions, check_admin_operation, process_admin_operation_with_options,
+    process_transaction,
+};
+use crate::db::{AccountOrder, ClientAccountDB, TransactionDB};
+use crate::domain::{DisputeState, RejectionReason, Transaction, TransactionType, parse_currency};
+use crate::output::with_currency_precision;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{Path, Query, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+/// Storage and options used to process API requests
+///
+/// # Notes:
+///
+/// - SQLite connections cannot be shared between threads, so requests are applied one at a time
+/// - Engine calls block on SQLite and event sinks, so the API runs them on the blocking thread pool
+/// - Same rules as the CLI, through `process_transaction`
+pub struct Engine {
+    pub transaction_db: TransactionDB,
+    pub client_account_db: ClientAccountDB,
+    pub options: ProcessOptions,
+}
+
+impl Engine {
+    /// Open the SQLite files kept in `data_dir`, or in-memory databases without it
+    pub fn open(
+        data_dir: Option<&std::path::Path>,
+        options: ProcessOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let path = |name: &str| match data_dir {
+            Some(dir) => dir.join(name).to_string_lossy().into_owned(),
+            None => ":memory:".to_string(),
+        };
+        Ok(Engine {
+            transaction_db: TransactionDB::new(&path("transactions.db"))?,
+            client_account_db: ClientAccountDB::new(&path("client_accounts.db"))?,
+            options,
+        })
+    }
+
+    /// Apply a transaction received from an API, returning the rejection reason when it was not applied
+    pub fn submit(&self, tx: &Transaction) -> Result<Option<RejectionReason>, Box<dyn Error>> {
+        process_transaction(
+            tx,
+            &self.transaction_db,
+            &self.client_account_db,
+            &self.options,
+        )
+    }
+
+    /// Apply an administrative operation received from an authenticated API call, or tell why it is invalid
+    pub fn submit_admin(&self, tx: &Transaction) -> Result<Result<(), String>, Box<dyn Error>> {
+        if let Err(message) = check_admin_operation(tx, &self.transaction_db)? {
+            return Ok(Err(message));
+        }
+        process_admin_operation_with_options(
+            tx,
+            &self.transaction_db,
+            &self.client_account_db,
+            &self.options,
+        )?;
+        Ok(Ok(()))
+    }
+}
+
+/// Whether a token sent by a client is the admin token, comparing every byte so timing does not leak it
+pub fn token_matches(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    engine: Arc<Mutex<Engine>>,
+    admin_token: Option<String>,
+}
+
+impl AppState {
+    pub fn new(engine: Engine) -> Self {
+        AppState {
+            engine: Arc::new(Mutex::new(engine)),
+            admin_token: None,
+        }
+    }
+
+    /// Accept administrative operations from requests with "Authorization: Bearer <token>"
+    pub fn with_admin_token(mut self, token: &str) -> Self {
+        self.admin_token = Some(token.to_string());
+        self
+    }
+
+    // Administrative operations are disabled when the server has no admin token
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), ApiError> {
+        let Some(expected) = &self.admin_token else {
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "admin_only",
+                "administrative operations are disabled",
+            ));
+        };
+        let given = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match given {
+            Some(token) if token_matches(expected, token) => Ok(()),
+            _ => Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "missing or invalid admin token",
+            )),
+        }
+    }
+
+    // Runs on the blocking thread pool, so a slow webhook or disk does not stall the async workers.
+    // Storage errors are turned into API errors before leaving the lock
+    async fn with_engine<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Engine) -> Result<T, Box<dyn Error>> + Send + 'static,
+    ) -> Result<T, ApiError> {
+        let engine = self.engine.clone();
+        tokio::task::spawn_blocking(move || {
+            let engine = engine
+                .lock()
+                .map_err(|_| ApiError::internal("storage is unavailable"))?;
+            f(&engine).map_err(|err| ApiError::internal(&err.to_string()))
+        })
+        .await
+        .map_err(|_| ApiError::internal("storage is unavailable"))?
+    }
+}
+
+/// Error body returned by every endpoint, e.g. {"code": "insufficient_funds", "message": "insufficient funds"}
+///
+/// # Codes:
+///
+/// - Rejection reasons, in snake_case: The transaction was not applied
+/// - invalid_request: Malformed body, path or query, or an invalid administrative operation
+/// - unauthorized: Administrative operation without the admin token of the server
+/// - account_not_found / transaction_not_found: Nothing stored under the requested ID
+/// - internal_error: Storage failure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiError {
+    #[serde(skip)]
+    status: u16,
+    pub code: String,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, code: &str, message: &str) -> Self {
+        ApiError {
+            status: status.as_u16(),
+            code: code.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn invalid_request(message: &str) -> Self {
+        ApiError::new(StatusCode::BAD_REQUEST, "invalid_request", message)
+    }
+
+    fn internal(message: &str) -> Self {
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", message)
+    }
+
+    fn rejected(reason: RejectionReason) -> Self {
+        ApiError::new(
+            rejection_status(reason),
+            &rejection_code(reason),
+            &reason.to_string(),
+        )
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, Json(self)).into_response()
+    }
+}
+
+// Same names used in the rejections CSV
+fn rejection_code(reason: RejectionReason) -> String {
+    match serde_json::to_value(reason) {
+        Ok(serde_json::Value::String(code)) => code,
+        _ => "rejected".to_string(),
+    }
+}
+
+fn rejection_status(reason: RejectionReason) -> StatusCode {
+    match reason {
+        RejectionReason::AccountLocked => StatusCode::LOCKED,
+        RejectionReason::AdminOnly => StatusCode::FORBIDDEN,
+        RejectionReason::MissingAmount
+        | RejectionReason::InvalidAmount
+        | RejectionReason::InvalidConversion
+        | RejectionReason::InvalidTransfer => StatusCode::BAD_REQUEST,
+        RejectionReason::DuplicateTransaction
+        | RejectionReason::NotDisputable
+        | RejectionReason::NotRefundable
+        | RejectionReason::InvalidDisputeState
+        | RejectionReason::LateArrival
+        | RejectionReason::DisputeWindowClosed => StatusCode::CONFLICT,
+        RejectionReason::InsufficientFunds
+        | RejectionReason::InsufficientHeldFunds
+        | RejectionReason::RateNotFound
+        | RejectionReason::ExceedsRemainingAmount => StatusCode::UNPROCESSABLE_ENTITY,
+        RejectionReason::TransactionNotFound => StatusCode::NOT_FOUND,
+    }
+}
+
+/// Body of POST /transactions, a single transaction or a batch applied in order
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Submission {
+    Batch(Vec<Transaction>),
+    Single(Transaction),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionStatus {
+    Accepted,
+    Rejected,
+}
+
+/// Outcome of a submitted transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmissionResult {
+    pub tx: u32,
+    pub client: u16,
+    pub status: SubmissionStatus,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub error: Option<ApiError>,
+}
+
+/// Stored transaction, as returned by GET /transactions/{tx}
+#[derive(Debug, Serialize)]
+struct TransactionView {
+    tx: u32,
+    client: u16,
+    #[serde(rename = "type")]
+    transaction_type: TransactionType,
+    #[serde(serialize_with = "crate::domain::serialize_option_f64_4")]
+    amount: Option<f64>,
+    currency: String,
+    dispute_state: DisputeState,
+}
+
+#[derive(Deserialize)]
+struct AccountsQuery {
+    sort: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AccountQuery {
+    currency: Option<String>,
+}
+
+async fn submit_transactions(
+    State(state): State<AppState>,
+    body: Result<Json<Submission>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(submission) = body.map_err(|err| ApiError::invalid_request(&err.body_text()))?;
+    match submission {
+        Submission::Single(tx) => {
+            let (id, client_id) = (tx.id, tx.client_id);
+            match state.with_engine(move |engine| engine.submit(&tx)).await? {
+                None => Ok(Json(SubmissionResult {
+                    tx: id,
+                    client: client_id,
+                    status: SubmissionStatus::Accepted,
+                    error: None,
+                })
+                .into_response()),
+                Some(reason) => Err(ApiError::rejected(reason)),
+            }
+        }
+        Submission::Batch(txs) => {
+            let results = state
+                .with_engine(move |engine| {
+                    let mut results = Vec::with_capacity(txs.len());
+                    for tx in &txs {
+                        let reason = engine.submit(tx)?;
+                        results.push(SubmissionResult {
+                            tx: tx.id,
+                            client: tx.client_id,
+                            status: match reason {
+                                None => SubmissionStatus::Accepted,
+                                Some(_) => SubmissionStatus::Rejected,
+                            },
+                            error: reason.map(ApiError::rejected),
+                        });
+                    }
+                    Ok(results)
+                })
+                .await?;
+            Ok(Json(results).into_response())
+        }
+    }
+}
+
+async fn submit_admin_operation(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Result<Json<Transaction>, JsonRejection>,
+) -> Result<Json<SubmissionResult>, ApiError> {
+    state.authorize(&headers)?;
+    let Json(tx) = body.map_err(|err| ApiError::invalid_request(&err.body_text()))?;
+    let (id, client_id) = (tx.id, tx.client_id);
+    match state
+        .with_engine(move |engine| engine.submit_admin(&tx))
+        .await?
+    {
+        Ok(()) => Ok(Json(SubmissionResult {
+            tx: id,
+            client: client_id,
+            status: SubmissionStatus::Accepted,
+            error: None,
+        })),
+        Err(message) => Err(ApiError::invalid_request(&message)),
+    }
+}
+
+// Amounts of responses use the decimal places of their currency, same as the CLI output
+fn to_json<T: Serialize>(records: &T) -> Result<Json<Value>, ApiError> {
+    let value =
+        serde_json::to_value(records).map_err(|err| ApiError::internal(&err.to_string()))?;
+    Ok(Json(with_currency_precision(value)))
+}
+
+async fn get_account(
+    State(state): State<AppState>,
+    client: Result<Path<u16>, PathRejection>,
+    query: Result<Query<AccountQuery>, QueryRejection>,
+) -> Result<Json<Value>, ApiError> {
+    let Path(client_id) = client.map_err(|err| ApiError::invalid_request(&err.body_text()))?;
+    let Query(query) = query.map_err(|err| ApiError::invalid_request(&err.body_text()))?;
+    let currency = match query.currency {
+        Some(code) => parse_currency(&code).map_err(|err| ApiError::invalid_request(&err))?,
+        None => String::new(),
+    };
+    let account = state
+        .with_engine(move |engine| {
+            engine
+                .client_account_db
+                .get_currency_account(client_id, &currency)
+        })
+        .await?;
+    let account = account.ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "account_not_found",
+            &format!("client {} has no account", client_id),
+        )
+    })?;
+    to_json(&account)
+}
+
+async fn list_accounts(
+    State(state): State<AppState>,
+    query: Result<Query<AccountsQuery>, QueryRejection>,
+) -> Result<Json<Value>, ApiError> {
+    let Query(query) = query.map_err(|err| ApiError::invalid_request(&err.body_text()))?;
+    let order = match query.sort {
+        Some(sort) => sort
+            .parse::<AccountOrder>()
+            .map_err(|err| ApiError::invalid_request(&err))?,
+        None => AccountOrder::default(),
+    };
+    let accounts = state
+        .with_engine(move |engine| engine.client_account_db.get_all_accounts_ordered(order))
+        .await?;
+    to_json(&accounts)
+}
+
+async fn get_transaction(
+    State(state): State<AppState>,
+    tx: Result<Path<u32>, PathRejection>,
+) -> Result<Json<Value>, ApiError> {
+    let Path(id) = tx.map_err(|err| ApiError::invalid_request(&err.body_text()))?;
+    let stored = state
+        .with_engine(move |engine| engine.transaction_db.get_transaction_state(id))
+        .await?;
+    let state = stored.ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "transaction_not_found",
+            &format!("tx {} was not found", id),
+        )
+    })?;
+    to_json(&TransactionView {
+        tx: id,
+        client: state.client_id,
+        transaction_type: state.transaction_type,
+        amount: state.amount,
+        currency: state.currency.unwrap_or_default(),
+        dispute_state: state.dispute_state,
+    })
+}
+
+/// Routes of the HTTP API
+///
+/// # Routes:
+///
+/// - POST /transactions: Apply a transaction, or a JSON array of them, with the same fields as the CSV input
+/// - POST /admin/operations: Apply a lock, unlock or adjustment with operator and reason, same as the --admin file.
+///   Requires "Authorization: Bearer <token>" with the admin token of the server, disabled without one
+/// - GET /accounts: All accounts, optionally ?sort=client|total|available
+/// - GET /accounts/{client}: Current state of a single account, in the implicit currency or ?currency=<ISO 4217 code>
+/// - GET /transactions/{tx}: Stored deposit, withdrawal or administrative operation, with its dispute state
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/transactions", post(submit_transactions))
+        .route("/admin/operations", post(submit_admin_operation))
+        .route("/transactions/{tx}", get(get_transaction))
+        .route("/accounts", get(list_accounts))
+        .route("/accounts/{client}", get(get_account))
+        .with_state(state)
+}
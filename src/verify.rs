@@ -0,0 +1,140 @@
+use crate::db::{ClientAccountDB, TransactionDB};
+use crate::domain::{BALANCE_TOLERANCE, ClientAccount};
+use std::error::Error;
+
+fn amounts_match(a: f64, b: f64) -> bool {
+    (a - b).abs() < BALANCE_TOLERANCE
+}
+
+/// Check the invariants of a single account
+///
+/// # Invariants:
+///
+/// - Total is always Available + Held
+/// - Held is never negative
+/// - Held matches the sum of the client transactions currently under dispute, in the currency of the account
+pub fn verify_account(
+    account: &ClientAccount,
+    transaction_db: &TransactionDB,
+) -> Result<(), Box<dyn Error>> {
+    if !amounts_match(account.total(), account.available() + account.held()) {
+        return Err(format!(
+            "client {} total {:.4} is not available {:.4} + held {:.4}",
+            account.id(),
+            account.total(),
+            account.available(),
+            account.held()
+        )
+        .into());
+    }
+
+    if account.held() < -BALANCE_TOLERANCE {
+        return Err(format!(
+            "client {} has negative held funds {:.4}",
+            account.id(),
+            account.held()
+        )
+        .into());
+    }
+
+    let disputed = transaction_db.get_disputed_total(account.id(), account.currency())?;
+    if !amounts_match(account.held(), disputed) {
+        return Err(format!(
+            "client {} held funds {:.4} do not match disputed transactions {:.4}",
+            account.id(),
+            account.held(),
+            disputed
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Check that an account locked before a transaction was not changed by it
+pub fn verify_locked_unchanged(
+    before: &ClientAccount,
+    after: &ClientAccount,
+) -> Result<(), Box<dyn Error>> {
+    if before.is_locked() && before != after {
+        return Err(format!("locked client {} was changed", before.id()).into());
+    }
+    Ok(())
+}
+
+/// Cross-check account balances against the double-entry journal
+///
+/// # Checks:
+///
+/// - Every journal entry sums to zero
+/// - Available and held of every account match the sum of its postings
+pub fn reconcile_journal(
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(entry) = transaction_db.get_unbalanced_entries()?.first() {
+        return Err(format!("journal entry {} is not balanced", entry).into());
+    }
+
+    for account in client_account_db.get_all_accounts()? {
+        let (available, held) =
+            transaction_db.get_journal_balances(account.id(), account.currency())?;
+        if !amounts_match(available, account.available()) || !amounts_match(held, account.held()) {
+            return Err(format!(
+                "client {} balances (available {:.4}, held {:.4}) do not match journal (available {:.4}, held {:.4})",
+                account.id(),
+                account.available(),
+                account.held(),
+                available,
+                held
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Run every invariant check over all accounts, returning the first violation
+pub fn verify(
+    transaction_db: &TransactionDB,
+    client_account_db: &ClientAccountDB,
+) -> Result<(), Box<dyn Error>> {
+    for account in client_account_db.get_all_accounts()? {
+        verify_account(&account, transaction_db)?;
+    }
+    reconcile_journal(transaction_db, client_account_db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consistent_account_passes() {
+        let transaction_db = TransactionDB::new(":memory:").unwrap();
+        let mut account = ClientAccount::new(1);
+        account.add_funds(10.0);
+        assert!(verify_account(&account, &transaction_db).is_ok());
+    }
+
+    #[test]
+    fn test_held_without_disputes_is_reported() {
+        let transaction_db = TransactionDB::new(":memory:").unwrap();
+        let mut account = ClientAccount::new(1);
+        account.add_funds(10.0);
+        account.hold_funds(4.0).unwrap();
+        let err = verify_account(&account, &transaction_db).unwrap_err();
+        assert!(err.to_string().contains("do not match disputed"), "{}", err);
+    }
+
+    #[test]
+    fn test_changed_locked_account_is_reported() {
+        let mut before = ClientAccount::new(1);
+        before.lock_account();
+        let mut after = ClientAccount::new(1);
+        after.lock_account();
+        assert!(verify_locked_unchanged(&before, &after).is_ok());
+        after.add_funds(1.0);
+        assert!(verify_locked_unchanged(&before, &after).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+use crate::domain::{Transaction, TransactionType};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use std::error::Error;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Relative weights of each client transaction type in a generated workload
+///
+/// # Notes:
+///
+/// - Parsed from a comma separated list of type=weight, e.g. deposit=60,withdrawal=30,dispute=10
+/// - Types not listed get weight 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionMix {
+    pub deposit: u32,
+    pub withdrawal: u32,
+    pub dispute: u32,
+    pub resolve: u32,
+    pub chargeback: u32,
+}
+
+impl Default for TransactionMix {
+    fn default() -> Self {
+        TransactionMix {
+            deposit: 60,
+            withdrawal: 25,
+            dispute: 8,
+            resolve: 5,
+            chargeback: 2,
+        }
+    }
+}
+
+impl TransactionMix {
+    fn total(&self) -> u32 {
+        self.deposit + self.withdrawal + self.dispute + self.resolve + self.chargeback
+    }
+
+    fn pick(&self, roll: u32) -> TransactionType {
+        let weights = [
+            (TransactionType::Deposit, self.deposit),
+            (TransactionType::Withdrawal, self.withdrawal),
+            (TransactionType::Dispute, self.dispute),
+            (TransactionType::Resolve, self.resolve),
+            (TransactionType::Chargeback, self.chargeback),
+        ];
+        let mut roll = roll;
+        for (transaction_type, weight) in weights {
+            if roll < weight {
+                return transaction_type;
+            }
+            roll -= weight;
+        }
+        TransactionType::Deposit
+    }
+}
+
+impl FromStr for TransactionMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = TransactionMix {
+            deposit: 0,
+            withdrawal: 0,
+            dispute: 0,
+            resolve: 0,
+            chargeback: 0,
+        };
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (name, weight) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected type=weight, got '{}'", entry))?;
+            let weight: u32 = weight
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid weight '{}' for {}", weight.trim(), name))?;
+            match name.trim().to_ascii_lowercase().as_str() {
+                "deposit" => mix.deposit = weight,
+                "withdrawal" => mix.withdrawal = weight,
+                "dispute" => mix.dispute = weight,
+                "resolve" => mix.resolve = weight,
+                "chargeback" => mix.chargeback = weight,
+                other => {
+                    return Err(format!(
+                        "unknown transaction type '{}', expected deposit, withdrawal, dispute, resolve or chargeback",
+                        other
+                    ));
+                }
+            }
+        }
+        if mix.total() == 0 {
+            return Err("at least one transaction type needs a positive weight".to_string());
+        }
+        Ok(mix)
+    }
+}
+
+/// Settings of a generated workload
+///
+/// # Settings:
+///
+/// - seed: Same seed and settings always produce the same stream
+/// - rows: Number of rows to generate
+/// - clients: Client IDs are picked from 1 to clients
+/// - mix: Weights of each transaction type
+/// - duplicate_rate: Share of rows repeating an earlier deposit or withdrawal, tx ID included
+/// - invalid_rate: Share of deposits and withdrawals with a missing, zero, negative or NaN amount
+/// - unknown_tx_rate: Share of disputes, resolves and chargebacks referring to tx IDs that never exist
+/// - max_amount: Amounts are picked from 0.0001 to max_amount, with 4 decimal places
+#[derive(Debug, Clone)]
+pub struct WorkloadConfig {
+    pub seed: u64,
+    pub rows: usize,
+    pub clients: u16,
+    pub mix: TransactionMix,
+    pub duplicate_rate: f64,
+    pub invalid_rate: f64,
+    pub unknown_tx_rate: f64,
+    pub max_amount: f64,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        WorkloadConfig {
+            seed: 0,
+            rows: 1000,
+            clients: 100,
+            mix: TransactionMix::default(),
+            duplicate_rate: 0.0,
+            invalid_rate: 0.0,
+            unknown_tx_rate: 0.0,
+            max_amount: 1000.0,
+        }
+    }
+}
+
+// Unknown tx IDs are taken from the top of the range, generated IDs count up from 1
+const UNKNOWN_TX_RANGE: u32 = 1_000_000;
+
+/// Seeded stream of client transactions, following the settings of a `WorkloadConfig`
+///
+/// # Notes:
+///
+/// - Disputes refer to earlier deposits of the same client, resolves and chargebacks to earlier disputes
+/// - The generator does not know which rows the engine rejects, so some references are stale on purpose
+pub struct WorkloadGenerator {
+    config: WorkloadConfig,
+    rng: ChaCha8Rng,
+    generated: usize,
+    next_tx: u32,
+    funding: Vec<Transaction>,
+    deposits: Vec<(u16, u32)>,
+    disputes: Vec<(u16, u32)>,
+}
+
+impl WorkloadGenerator {
+    pub fn new(config: WorkloadConfig) -> Self {
+        WorkloadGenerator {
+            rng: ChaCha8Rng::seed_from_u64(config.seed),
+            config,
+            generated: 0,
+            next_tx: 1,
+            funding: Vec::new(),
+            deposits: Vec::new(),
+            disputes: Vec::new(),
+        }
+    }
+
+    fn client(&mut self) -> u16 {
+        self.rng.random_range(1..=self.config.clients.max(1))
+    }
+
+    fn amount(&mut self) -> f64 {
+        let max_units = ((self.config.max_amount * 10_000.0) as u64).max(1);
+        self.rng.random_range(1..=max_units) as f64 / 10_000.0
+    }
+
+    fn new_tx(&mut self) -> u32 {
+        let id = self.next_tx;
+        self.next_tx += 1;
+        id
+    }
+
+    fn unknown_reference(&mut self, transaction_type: TransactionType) -> Transaction {
+        let id = u32::MAX - self.rng.random_range(0..UNKNOWN_TX_RANGE);
+        transaction(transaction_type, self.client(), id, None)
+    }
+
+    fn funding(&mut self, transaction_type: TransactionType) -> Transaction {
+        let client = self.client();
+        let id = self.new_tx();
+        let amount = self.amount();
+        if transaction_type == TransactionType::Deposit {
+            self.deposits.push((client, id));
+        }
+        let tx = transaction(transaction_type, client, id, Some(amount));
+        self.funding.push(tx.clone());
+        tx
+    }
+
+    fn invalid_funding(&mut self) -> Transaction {
+        let transaction_type = if self.rng.random_bool(0.5) {
+            TransactionType::Deposit
+        } else {
+            TransactionType::Withdrawal
+        };
+        let client = self.client();
+        let id = self.new_tx();
+        let amount = match self.rng.random_range(0..4) {
+            0 => None,
+            1 => Some(0.0),
+            2 => Some(-self.amount()),
+            _ => Some(f64::NAN),
+        };
+        transaction(transaction_type, client, id, amount)
+    }
+
+    fn reference(&mut self, transaction_type: TransactionType) -> Transaction {
+        if self.rng.random_bool(self.config.unknown_tx_rate) {
+            return self.unknown_reference(transaction_type);
+        }
+        let candidates = if transaction_type == TransactionType::Dispute {
+            &mut self.deposits
+        } else {
+            &mut self.disputes
+        };
+        if candidates.is_empty() {
+            return self.unknown_reference(transaction_type);
+        }
+        let index = self.rng.random_range(0..candidates.len());
+        // A deposit can be disputed again after a resolve, a dispute is only closed once
+        let (client, id) = if transaction_type == TransactionType::Dispute {
+            candidates[index]
+        } else {
+            candidates.swap_remove(index)
+        };
+        if transaction_type == TransactionType::Dispute {
+            self.disputes.push((client, id));
+        }
+        transaction(transaction_type, client, id, None)
+    }
+
+    fn next_transaction(&mut self) -> Transaction {
+        let roll: f64 = self.rng.random();
+        if roll < self.config.duplicate_rate && !self.funding.is_empty() {
+            let index = self.rng.random_range(0..self.funding.len());
+            return self.funding[index].clone();
+        }
+        if roll < self.config.duplicate_rate + self.config.invalid_rate {
+            return self.invalid_funding();
+        }
+
+        let roll = self.rng.random_range(0..self.config.mix.total().max(1));
+        match self.config.mix.pick(roll) {
+            TransactionType::Withdrawal => self.funding(TransactionType::Withdrawal),
+            TransactionType::Dispute => self.reference(TransactionType::Dispute),
+            TransactionType::Resolve => self.reference(TransactionType::Resolve),
+            TransactionType::Chargeback => self.reference(TransactionType::Chargeback),
+            _ => self.funding(TransactionType::Deposit),
+        }
+    }
+}
+
+impl Iterator for WorkloadGenerator {
+    type Item = Transaction;
+
+    fn next(&mut self) -> Option<Transaction> {
+        if self.generated >= self.config.rows {
+            return None;
+        }
+        self.generated += 1;
+        Some(self.next_transaction())
+    }
+}
+
+fn transaction(
+    transaction_type: TransactionType,
+    client_id: u16,
+    id: u32,
+    amount: Option<f64>,
+) -> Transaction {
+    Transaction {
+        transaction_type,
+        client_id,
+        id,
+        amount,
+        operator: None,
+        reason: None,
+        timestamp: None,
+        currency: None,
+        to_currency: None,
+        rate: None,
+        to_client: None,
+        fee: None,
+    }
+}
+
+// Same columns as the regular input, amounts with 4 decimal places
+#[derive(Serialize)]
+struct WorkloadRow {
+    #[serde(rename = "type")]
+    transaction_type: TransactionType,
+    client: u16,
+    tx: u32,
+    amount: Option<String>,
+}
+
+/// Writes transactions as an input CSV for the engine
+pub struct WorkloadWriter<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> WorkloadWriter<W> {
+    pub fn new(writer: W) -> Self {
+        WorkloadWriter {
+            writer: csv::Writer::from_writer(writer),
+        }
+    }
+
+    pub fn write(&mut self, tx: &Transaction) -> Result<(), Box<dyn Error>> {
+        self.writer.serialize(WorkloadRow {
+            transaction_type: tx.transaction_type,
+            client: tx.client_id,
+            tx: tx.id,
+            amount: tx.amount.map(|amount| format!("{:.4}", amount)),
+        })?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W, Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(self.writer.into_inner().map_err(|err| err.to_string())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(config: &WorkloadConfig) -> String {
+        let mut writer = WorkloadWriter::new(vec![]);
+        for tx in WorkloadGenerator::new(config.clone()) {
+            writer.write(&tx).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_same_seed_generates_same_stream() {
+        let config = WorkloadConfig {
+            rows: 200,
+            duplicate_rate: 0.05,
+            invalid_rate: 0.05,
+            unknown_tx_rate: 0.1,
+            ..WorkloadConfig::default()
+        };
+        let data = generate(&config);
+        assert_eq!(data, generate(&config));
+        assert_eq!(data.lines().count(), 201);
+        assert!(data.starts_with("type,client,tx,amount\n"));
+
+        let other = WorkloadConfig { seed: 1, ..config };
+        assert_ne!(data, generate(&other));
+    }
+
+    #[test]
+    fn test_mix_is_parsed_and_respected() {
+        let mix: TransactionMix = "deposit=1, withdrawal=0".parse().unwrap();
+        assert_eq!(mix.deposit, 1);
+        assert_eq!(mix.dispute, 0);
+        assert!("deposit=0".parse::<TransactionMix>().is_err());
+        assert!("transfer=5".parse::<TransactionMix>().is_err());
+
+        let config = WorkloadConfig {
+            rows: 50,
+            mix,
+            ..WorkloadConfig::default()
+        };
+        assert!(
+            WorkloadGenerator::new(config)
+                .all(|tx| tx.transaction_type == TransactionType::Deposit)
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use rust_payment_engine::csv_processor::{process_admin_csv, process_admin_operation, process_csv};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::{DisputeState, Transaction, TransactionType};
+use std::fs::File;
+use std::io::Write;
+
+fn setup() -> (TransactionDB, ClientAccountDB) {
+    let file = File::open("tests/resources/admin/input.csv").expect("Failed to open input CSV");
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    process_csv(file, &transaction_db, &client_account_db).expect("Failed to process CSV");
+    let admin_file =
+        File::open("tests/resources/admin/admin.csv").expect("Failed to open admin CSV");
+    process_admin_csv(admin_file, &transaction_db, &client_account_db)
+        .expect("Failed to process admin CSV");
+    (transaction_db, client_account_db)
+}
+
+fn process_rows(rows: &str, transaction_db: &TransactionDB, client_account_db: &ClientAccountDB) {
+    let path = std::env::temp_dir().join(format!("admin_operations_{}.csv", std::process::id()));
+    let mut file = File::create(&path).unwrap();
+    write!(file, "type, client, tx, amount\n{}", rows).unwrap();
+    process_csv(
+        File::open(&path).unwrap(),
+        transaction_db,
+        client_account_db,
+    )
+    .expect("Failed to process CSV");
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_admin_operations_are_applied_and_recorded() {
+    let (transaction_db, client_account_db) = setup();
+
+    let client_1 = client_account_db.get_account(1).unwrap();
+    assert!(!client_1.is_locked());
+    assert_eq!(client_1.available(), 97.5);
+    assert!(client_account_db.get_account(2).unwrap().is_locked());
+
+    let history = transaction_db.get_client_history(1).unwrap();
+    let unlock = history.iter().find(|entry| entry.id == 100).unwrap();
+    assert_eq!(unlock.transaction_type, TransactionType::Unlock);
+    assert_eq!(unlock.operator.as_deref(), Some("ops-42"));
+}
+
+#[test]
+fn test_unlocked_account_accepts_transactions_but_chargebacks_stay_final() {
+    let (transaction_db, client_account_db) = setup();
+
+    // tx 2 was charged back before the unlock, so it cannot be disputed again
+    process_rows(
+        "deposit, 1, 200, 10.0\ndispute, 1, 2,\n",
+        &transaction_db,
+        &client_account_db,
+    );
+    let client_1 = client_account_db.get_account(1).unwrap();
+    assert_eq!(client_1.available(), 107.5);
+    assert_eq!(client_1.held(), 0.0);
+    let state = transaction_db.get_transaction_state(2).unwrap().unwrap();
+    assert_eq!(state.dispute_state, DisputeState::ChargedBack);
+}
+
+#[test]
+fn test_admin_operations_are_ignored_in_regular_input_and_adjustments_not_disputable() {
+    let (transaction_db, client_account_db) = setup();
+
+    process_rows(
+        "unlock, 2, 300,\ndispute, 1, 101,\n",
+        &transaction_db,
+        &client_account_db,
+    );
+    assert!(client_account_db.get_account(2).unwrap().is_locked());
+    assert_eq!(client_account_db.get_account(1).unwrap().held(), 0.0);
+}
+
+#[test]
+fn test_admin_operation_requires_operator() {
+    let transaction_db = TransactionDB::new(":memory:").unwrap();
+    let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+    let tx = Transaction {
+        transaction_type: TransactionType::Lock,
+        client_id: 1,
+        id: 1,
+        amount: None,
+        operator: None,
+        reason: Some("no operator".to_string()),
+        timestamp: None,
+        currency: None,
+        to_currency: None,
+        rate: None,
+        to_client: None,
+        fee: None,
+    };
+    assert!(process_admin_operation(&tx, &transaction_db, &client_account_db).is_err());
+}
+
+#[test]
+fn test_admin_operation_cannot_reuse_tx_id() {
+    let (transaction_db, client_account_db) = setup();
+    let tx = Transaction {
+        transaction_type: TransactionType::Lock,
+        client_id: 1,
+        id: 1,
+        amount: None,
+        operator: Some("ops-42".to_string()),
+        reason: None,
+        timestamp: None,
+        currency: None,
+        to_currency: None,
+        rate: None,
+        to_client: None,
+        fee: None,
+    };
+    assert!(process_admin_operation(&tx, &transaction_db, &client_account_db).is_err());
+    assert!(!client_account_db.get_account(1).unwrap().is_locked());
+}
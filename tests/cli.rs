@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn work_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("payment_engine_cli_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run_cli(dir: &PathBuf, input: &str) -> Output {
+    let input_path = dir.join("input.csv");
+    std::fs::write(&input_path, input).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_rust-payment-engine"))
+        .arg(&input_path)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_failed_run_leaves_no_state_behind() {
+    let dir = work_dir();
+    let failed = run_cli(
+        &dir,
+        "type, client, tx, amount, currency\ndeposit, 1, 1, 5.0,\ndeposit, 1, 2, 1.0, DOLLARS\n",
+    );
+    assert!(!failed.status.success());
+
+    let output = run_cli(&dir, "type, client, tx, amount\ndeposit, 1, 1, 100.0\n");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,100.0000,0.0000,100.0000,false\n"
+    );
+    // Nothing is written next to the input
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::assert_csv_eq_ordered;
+use rust_payment_engine::csv_processor::{get_client_history_formatted, process_csv};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::TransactionType;
+use rust_payment_engine::output::OutputOptions;
+use std::fs::File;
+
+#[test]
+fn test_client_history_follows_input_order() {
+    let input_path = "tests/resources/big/input.csv";
+    let file = File::open(input_path).expect("Failed to open input CSV");
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    process_csv(file, &transaction_db, &client_account_db).expect("Failed to process CSV");
+
+    // Inputs of client 1 that were applied, in the order they appear in the input
+    let expected: Vec<(u32, TransactionType)> = transaction_db
+        .get_client_event_log(1, None)
+        .expect("Failed to get event log")
+        .into_iter()
+        .map(|logged| (logged.transaction.id, logged.transaction.transaction_type))
+        .collect();
+    assert!(!expected.is_empty());
+
+    let history = transaction_db
+        .get_client_history(1)
+        .expect("Failed to get client history");
+    let entries: Vec<(u32, TransactionType)> = history
+        .iter()
+        .map(|entry| (entry.id, entry.transaction_type))
+        .collect();
+    assert_eq!(entries, expected);
+}
+
+#[test]
+fn test_client_history_as_csv() {
+    let file = File::open("tests/resources/lock/input.csv").expect("Failed to open input CSV");
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    process_csv(file, &transaction_db, &client_account_db).expect("Failed to process CSV");
+
+    let actual = get_client_history_formatted(&transaction_db, 2, &OutputOptions::default())
+        .expect("Failed to format client history");
+    let expected = "tx,type,amount,dispute_state,operator,available,held,total\n\
+                    6,deposit,100.0000,undisputed,,100.0000,0.0000,100.0000\n\
+                    7,deposit,1000.0000,disputed,,1100.0000,0.0000,1100.0000\n\
+                    7,dispute,1000.0000,disputed,,100.0000,1000.0000,1100.0000\n";
+    assert_csv_eq_ordered(&actual, expected);
+}
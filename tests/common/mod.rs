@@ -0,0 +1,127 @@
+#![allow(dead_code)] // Each integration test binary only uses part of these helpers
+
+use rust_payment_engine::csv_processor::{
+    ProcessOptions, get_all_accounts_formatted, process_csv, process_csv_with_options,
+};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::{Rejection, RejectionReason};
+use rust_payment_engine::output::OutputOptions;
+use std::fs::File;
+use std::io::Read;
+
+/// Process an input CSV with in-memory databases, returning the rendered accounts
+pub fn run_csv(input_path: &str, options: &OutputOptions) -> String {
+    let file = File::open(input_path).expect("Failed to open input CSV");
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    process_csv(file, &transaction_db, &client_account_db).expect("Failed to process CSV");
+    get_all_accounts_formatted(&client_account_db, options).expect("Failed to get output CSV")
+}
+
+/// Default options, verifying account invariants after every row
+pub fn paranoid_options() -> ProcessOptions {
+    ProcessOptions {
+        paranoid: true,
+        ..ProcessOptions::default()
+    }
+}
+
+/// Process an input CSV with in-memory databases, returning them with the rejections, or the error that stopped
+/// processing
+pub fn try_process(
+    input: &str,
+    options: &ProcessOptions,
+) -> (
+    TransactionDB,
+    ClientAccountDB,
+    Result<Vec<Rejection>, String>,
+) {
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    let result = process_csv_with_options(
+        input.as_bytes(),
+        &transaction_db,
+        &client_account_db,
+        options,
+    )
+    .map_err(|err| err.to_string());
+    (transaction_db, client_account_db, result)
+}
+
+/// Same as try_process for inputs that must be processed, returning the tx and reason of every rejected row
+pub fn process(
+    input: &str,
+    options: &ProcessOptions,
+) -> (TransactionDB, ClientAccountDB, Vec<(u32, RejectionReason)>) {
+    let (transaction_db, client_account_db, result) = try_process(input, options);
+    let rejected = result
+        .expect("Failed to process CSV")
+        .iter()
+        .map(|rejection| (rejection.id, rejection.reason))
+        .collect();
+    (transaction_db, client_account_db, rejected)
+}
+
+/// (available, held, total, locked) of a client in the implicit currency
+pub fn balances(client_account_db: &ClientAccountDB, client_id: u16) -> (f64, f64, f64, bool) {
+    let account = client_account_db.get_account(client_id).unwrap();
+    (
+        account.available(),
+        account.held(),
+        account.total(),
+        account.is_locked(),
+    )
+}
+
+/// Available funds of a client in a currency, None if it has no account in it
+pub fn available(
+    client_account_db: &ClientAccountDB,
+    client_id: u16,
+    currency: &str,
+) -> Option<f64> {
+    client_account_db
+        .get_currency_account(client_id, currency)
+        .unwrap()
+        .map(|account| account.available())
+}
+
+pub fn read_file(path: &str) -> String {
+    let mut data = String::new();
+    File::open(path)
+        .expect("Failed to open expected output CSV")
+        .read_to_string(&mut data)
+        .expect("Failed to read expected output");
+    data
+}
+
+/// Normalize line endings and surrounding whitespace, returning the header and data rows
+pub fn split_csv(data: &str) -> (String, Vec<String>) {
+    let data = data.replace("\r\n", "\n");
+    let mut lines = data.trim().lines().map(|line| line.trim().to_string());
+    let header = lines.next().unwrap_or_default();
+    (header, lines.collect())
+}
+
+/// Compare two CSVs with the same header, ignoring the order of data rows
+pub fn assert_csv_eq_unordered(actual: &str, expected: &str) {
+    let (actual_header, mut actual_rows) = split_csv(actual);
+    let (expected_header, mut expected_rows) = split_csv(expected);
+    assert_eq!(actual_header, expected_header, "CSV headers do not match");
+    actual_rows.sort();
+    expected_rows.sort();
+    assert_eq!(
+        actual_rows, expected_rows,
+        "Output CSV does not match expected"
+    );
+}
+
+/// Compare two CSVs line by line, including row order
+pub fn assert_csv_eq_ordered(actual: &str, expected: &str) {
+    assert_eq!(
+        split_csv(actual),
+        split_csv(expected),
+        "Output CSV does not match expected"
+    );
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{available, process};
+use rust_payment_engine::csv_processor::ProcessOptions;
+use rust_payment_engine::db::TransactionDB;
+use rust_payment_engine::domain::{DomainEvent, RejectionReason};
+use rust_payment_engine::events::ChannelSink;
+use rust_payment_engine::rates::RateTable;
+use rust_payment_engine::replay::{replay, verify_replay};
+use std::sync::Arc;
+
+const RATES: &str = "from, to, rate, effective
+USD, EUR, 0.90, 2024-05-01T00:00:00Z
+USD, EUR, 0.95, 2024-06-01T00:00:00Z
+USD, JPY, 151.237,
+";
+
+fn options() -> ProcessOptions {
+    ProcessOptions {
+        paranoid: true,
+        rates: RateTable::from_csv(RATES.as_bytes()).unwrap(),
+        ..ProcessOptions::default()
+    }
+}
+
+fn rate(transaction_db: &TransactionDB, id: u32) -> Option<f64> {
+    transaction_db
+        .get_transaction_state(id)
+        .unwrap()
+        .unwrap()
+        .rate
+}
+
+#[test]
+fn test_conversion_uses_rate_in_effect() {
+    let input = "type, client, tx, amount, currency, to_currency, timestamp
+deposit, 1, 1, 100.0, USD, , 2024-05-01T00:00:00Z
+convert, 1, 2, 10.0, USD, EUR, 2024-05-15T00:00:00Z
+convert, 1, 3, 10.0, USD, EUR, 2024-06-15T00:00:00Z
+convert, 1, 4, 1.0, USD, JPY, 2024-06-15T00:00:00Z
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &options());
+    assert_eq!(rejected, vec![]);
+
+    assert_eq!(available(&client_account_db, 1, "USD"), Some(79.0));
+    assert_eq!(available(&client_account_db, 1, "EUR"), Some(18.5));
+    assert_eq!(available(&client_account_db, 1, "JPY"), Some(151.0));
+    assert_eq!(rate(&transaction_db, 2), Some(0.90));
+    assert_eq!(rate(&transaction_db, 3), Some(0.95));
+    assert!(transaction_db.get_unbalanced_entries().unwrap().is_empty());
+
+    // The logged rate is replayed, without the rate table
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_invalid_conversions_are_rejected() {
+    let input = "type, client, tx, amount, currency, to_currency, timestamp
+deposit, 1, 1, 10.0, USD, , 2024-05-01T00:00:00Z
+convert, 1, 2, 20.0, USD, EUR, 2024-05-02T00:00:00Z
+convert, 1, 3, 5.0, USD, GBP, 2024-05-02T00:00:00Z
+convert, 1, 4, 5.0, USD, EUR, 2024-04-30T00:00:00Z
+convert, 1, 5, 5.0, USD, USD,
+convert, 1, 6, 5.0, , EUR,
+convert, 1, 7, 5.0, EUR, USD,
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &options());
+    assert_eq!(
+        rejected,
+        vec![
+            (2, RejectionReason::InsufficientFunds),
+            (3, RejectionReason::RateNotFound),
+            (4, RejectionReason::RateNotFound),
+            (5, RejectionReason::InvalidConversion),
+            (6, RejectionReason::InvalidConversion),
+            (7, RejectionReason::RateNotFound),
+        ]
+    );
+    assert_eq!(available(&client_account_db, 1, "USD"), Some(10.0));
+    assert_eq!(available(&client_account_db, 1, "EUR"), Some(0.0));
+
+    // Same as withdrawals, the conversion without enough funds is stored with its rate
+    assert_eq!(rate(&transaction_db, 2), Some(0.90));
+    let history = transaction_db.get_client_history(1).unwrap();
+    assert_eq!(
+        history.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+        vec![1]
+    );
+}
+
+#[test]
+fn test_conversions_publish_events_and_respect_locks() {
+    let input = "type, client, tx, amount, currency, to_currency
+deposit, 1, 1, 10.0, USD,
+deposit, 1, 2, 5.0, EUR,
+convert, 1, 3, 2.0, USD, JPY
+dispute, 1, 2, , ,
+chargeback, 1, 2, , ,
+convert, 1, 4, 2.0, USD, JPY
+";
+    let (sink, receiver) = ChannelSink::new();
+    let mut options = options();
+    options.events.add_sink(Arc::new(sink));
+    let (_, client_account_db, rejected) = process(input, &options);
+    assert_eq!(rejected, vec![(4, RejectionReason::AccountLocked)]);
+    assert_eq!(available(&client_account_db, 1, "USD"), Some(8.0));
+    assert_eq!(available(&client_account_db, 1, "JPY"), Some(302.0));
+
+    let converted: Vec<DomainEvent> = receiver
+        .try_iter()
+        .filter(|event| matches!(event, DomainEvent::FundsConverted { .. }))
+        .collect();
+    assert_eq!(
+        converted,
+        vec![DomainEvent::FundsConverted {
+            client: 1,
+            tx: 3,
+            from: "USD".to_string(),
+            to: "JPY".to_string(),
+            amount: 2.0,
+            converted: 302.0,
+            rate: 151.237,
+        }]
+    );
+}
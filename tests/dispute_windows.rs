@@ -0,0 +1,163 @@
+mod common;
+
+use common::{paranoid_options, process};
+use rust_payment_engine::csv_processor::ProcessOptions;
+use rust_payment_engine::domain::{DisputePolicy, ExpiryAction, RejectionReason};
+use rust_payment_engine::replay::{replay, verify_replay};
+
+fn options(disputes: DisputePolicy) -> ProcessOptions {
+    ProcessOptions {
+        disputes,
+        ..paranoid_options()
+    }
+}
+
+#[test]
+fn test_disputes_outside_filing_window_are_rejected() {
+    let input = "type, client, tx, amount, timestamp
+deposit, 1, 1, 10.0, 2024-05-01T12:00:00Z
+deposit, 1, 2, 5.0, 2024-05-20T12:00:00Z
+dispute, 1, 1, , 2024-06-01T12:00:00Z
+dispute, 1, 2, , 2024-06-01T12:00:00Z
+";
+    let policy = DisputePolicy {
+        filing_window: Some("30d".parse().unwrap()),
+        ..DisputePolicy::default()
+    };
+    let (_, client_account_db, rejected) = process(input, &options(policy));
+    assert_eq!(rejected, vec![(1, RejectionReason::DisputeWindowClosed)]);
+    assert_eq!(client_account_db.get_account(1).unwrap().held(), 5.0);
+}
+
+#[test]
+fn test_filing_window_in_rows() {
+    let input = "type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 1.0
+deposit, 2, 3, 1.0
+dispute, 1, 1,
+deposit, 1, 4, 10.0
+deposit, 2, 5, 1.0
+deposit, 2, 6, 1.0
+deposit, 2, 7, 1.0
+dispute, 1, 4,
+";
+    let policy = DisputePolicy {
+        filing_window: Some("3rows".parse().unwrap()),
+        ..DisputePolicy::default()
+    };
+    // Tx 1 is 3 inputs before its dispute, tx 4 is 4 inputs before
+    let (_, _, rejected) = process(input, &options(policy));
+    assert_eq!(rejected, vec![(4, RejectionReason::DisputeWindowClosed)]);
+}
+
+#[test]
+fn test_expired_disputes_are_closed() {
+    let input = "type, client, tx, amount, timestamp
+deposit, 1, 1, 10.0, 2024-05-01T12:00:00Z
+deposit, 2, 2, 10.0, 2024-05-01T12:00:00Z
+dispute, 1, 1, , 2024-05-02T12:00:00Z
+dispute, 2, 2, , 2024-05-05T12:00:00Z
+deposit, 3, 3, 1.0, 2024-05-09T12:00:00Z
+";
+    for (action, client_1) in [
+        (ExpiryAction::Resolve, (10.0, 0.0, false)),
+        (ExpiryAction::Chargeback, (0.0, 0.0, true)),
+    ] {
+        let policy = DisputePolicy {
+            expire_after: Some("6d".parse().unwrap()),
+            expiry_action: action,
+            ..DisputePolicy::default()
+        };
+        let (transaction_db, client_account_db, rejected) = process(input, &options(policy));
+        assert_eq!(rejected, vec![]);
+
+        // Only the dispute of client 1 is open for more than 6 days at the last input
+        let account = client_account_db.get_account(1).unwrap();
+        assert_eq!(
+            (account.available(), account.held(), account.is_locked()),
+            client_1
+        );
+        assert_eq!(client_account_db.get_account(2).unwrap().held(), 10.0);
+
+        let log = transaction_db.get_event_log(None).unwrap();
+        let synthetic = &log[log.len() - 2].transaction;
+        assert_eq!(synthetic.transaction_type, action.transaction_type());
+        assert_eq!(synthetic.id, 1);
+        assert_eq!(synthetic.operator.as_deref(), Some("dispute-expiry"));
+        assert_eq!(
+            synthetic.timestamp,
+            log[log.len() - 1].transaction.timestamp
+        );
+
+        // Replays reproduce the expiry from the log alone
+        let replayed = replay(&transaction_db, None).unwrap();
+        verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+    }
+}
+
+#[test]
+fn test_expiry_closes_disputes_on_locked_accounts() {
+    let input = "type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 1, 2, 5.0
+dispute, 1, 1,
+dispute, 1, 2,
+chargeback, 1, 2,
+deposit, 1, 3, 1.0
+";
+    let policy = DisputePolicy {
+        expire_after: Some("2rows".parse().unwrap()),
+        ..DisputePolicy::default()
+    };
+    let (_, client_account_db, rejected) = process(input, &options(policy));
+    assert_eq!(rejected, vec![(3, RejectionReason::AccountLocked)]);
+
+    // Dispute of tx 1 expires before the last deposit, which is rejected as the account is locked
+    let account = client_account_db.get_account(1).unwrap();
+    assert_eq!((account.available(), account.held()), (10.0, 0.0));
+    assert!(account.is_locked());
+}
+
+#[test]
+fn test_partial_disputes_expire_separately() {
+    let input = "type, client, tx, amount
+deposit, 1, 1, 10.0
+dispute, 1, 1, 2.0
+deposit, 2, 2, 1.0
+dispute, 1, 1, 3.0
+deposit, 2, 3, 1.0
+deposit, 2, 4, 1.0
+";
+    let policy = DisputePolicy {
+        expire_after: Some("2rows".parse().unwrap()),
+        ..DisputePolicy::default()
+    };
+    let held_after = |rows: usize| {
+        let input: String = input
+            .lines()
+            .take(rows + 1)
+            .map(|l| format!("{}\n", l))
+            .collect();
+        let (_, client_account_db, rejected) = process(&input, &options(policy.clone()));
+        assert_eq!(rejected, vec![]);
+        client_account_db.get_account(1).unwrap().held()
+    };
+
+    // The first dispute expires on its own, the second one still holds its amount
+    assert_eq!(held_after(5), 3.0);
+    assert_eq!(held_after(6), 0.0);
+
+    let (transaction_db, client_account_db, _) = process(input, &options(policy));
+    let expired: Vec<_> = transaction_db
+        .get_event_log(None)
+        .unwrap()
+        .into_iter()
+        .map(|logged| logged.transaction)
+        .filter(|tx| tx.operator.as_deref() == Some("dispute-expiry"))
+        .map(|tx| tx.amount)
+        .collect();
+    assert_eq!(expired, vec![Some(2.0), Some(3.0)]);
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
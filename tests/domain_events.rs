@@ -0,0 +1,190 @@
+use rust_payment_engine::csv_processor::{
+    ProcessOptions, process_admin_csv_with_options, process_csv_with_options,
+};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::{DomainEvent, RejectionReason};
+use rust_payment_engine::events::ChannelSink;
+use std::sync::Arc;
+
+const INPUT: &str = "type, client, tx, amount
+deposit, 1, 1, 10.0
+withdrawal, 1, 2, 15.0
+dispute, 1, 1,
+resolve, 1, 1,
+dispute, 1, 1,
+chargeback, 1, 1,
+withdrawal, 1, 3, 1.0
+";
+
+fn run(options: &ProcessOptions) {
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    process_csv_with_options(
+        INPUT.as_bytes(),
+        &transaction_db,
+        &client_account_db,
+        options,
+    )
+    .expect("Failed to process CSV");
+}
+
+fn expected_events() -> Vec<DomainEvent> {
+    vec![
+        DomainEvent::FundsDeposited {
+            client: 1,
+            tx: 1,
+            amount: 10.0,
+            currency: String::new(),
+        },
+        DomainEvent::WithdrawalRejected {
+            client: 1,
+            tx: 2,
+            amount: Some(15.0),
+            currency: String::new(),
+            reason: RejectionReason::InsufficientFunds,
+        },
+        DomainEvent::DisputeOpened {
+            client: 1,
+            tx: 1,
+            amount: 10.0,
+            currency: String::new(),
+        },
+        DomainEvent::DisputeResolved {
+            client: 1,
+            tx: 1,
+            amount: 10.0,
+            currency: String::new(),
+        },
+        DomainEvent::DisputeOpened {
+            client: 1,
+            tx: 1,
+            amount: 10.0,
+            currency: String::new(),
+        },
+        DomainEvent::ChargebackApplied {
+            client: 1,
+            tx: 1,
+            amount: 10.0,
+            currency: String::new(),
+        },
+        DomainEvent::AccountLocked { client: 1, tx: 1 },
+        DomainEvent::WithdrawalRejected {
+            client: 1,
+            tx: 3,
+            amount: Some(1.0),
+            currency: String::new(),
+            reason: RejectionReason::AccountLocked,
+        },
+    ]
+}
+
+#[test]
+fn test_events_are_published_in_processing_order() {
+    let (sink, receiver) = ChannelSink::new();
+    let mut options = ProcessOptions::default();
+    options.events.add_sink(Arc::new(sink));
+    run(&options);
+
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), expected_events());
+}
+
+#[test]
+fn test_admin_locks_and_unlocks_are_published() {
+    let (sink, receiver) = ChannelSink::new();
+    let mut options = ProcessOptions::default();
+    options.events.add_sink(Arc::new(sink));
+    let transaction_db = TransactionDB::new(":memory:").unwrap();
+    let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+    let admin = "type, client, tx, amount, operator, reason
+lock, 4, 100, , ops-1, fraud review
+unlock, 4, 101, , ops-1, review done
+";
+    process_admin_csv_with_options(
+        admin.as_bytes(),
+        &transaction_db,
+        &client_account_db,
+        &options,
+    )
+    .unwrap();
+
+    assert_eq!(
+        receiver.try_iter().collect::<Vec<_>>(),
+        vec![
+            DomainEvent::AccountLocked { client: 4, tx: 100 },
+            DomainEvent::AccountUnlocked { client: 4, tx: 101 },
+        ]
+    );
+}
+
+// Webhook sinks only exist with the webhook feature, the receiver of the test is an HTTP server
+#[cfg(feature = "webhook")]
+mod webhook {
+    use super::*;
+    #[cfg(feature = "server")]
+    use axum::routing::post;
+    #[cfg(feature = "server")]
+    use axum::{Json, Router};
+    use rust_payment_engine::events::WebhookSink;
+    use rust_payment_engine::replay::{replay, verify_replay};
+    #[cfg(feature = "server")]
+    use std::sync::Mutex;
+
+    #[cfg(feature = "server")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_webhook_receives_every_event() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let store = received.clone();
+        let app = Router::new().route(
+            "/events",
+            post(move |Json(event): Json<DomainEvent>| async move {
+                store.lock().unwrap().push(event);
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // Webhooks are delivered with blocking requests, same as the rest of the engine
+        tokio::task::spawn_blocking(move || {
+            let mut options = ProcessOptions::default();
+            options.events.add_sink(Arc::new(WebhookSink::new(&format!(
+                "http://{}/events",
+                address
+            ))));
+            run(&options);
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(*received.lock().unwrap(), expected_events());
+    }
+
+    #[test]
+    fn test_unreachable_webhook_does_not_fail_processing() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let mut options = ProcessOptions::default();
+        options.events.add_sink(Arc::new(WebhookSink::new(&format!(
+            "http://{}/events",
+            address
+        ))));
+        let transaction_db = TransactionDB::new(":memory:").unwrap();
+        let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+        let rejections = process_csv_with_options(
+            INPUT.as_bytes(),
+            &transaction_db,
+            &client_account_db,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(rejections.len(), 2);
+
+        // Every applied input is still logged
+        assert_eq!(transaction_db.get_event_log(None).unwrap().len(), 5);
+        let replayed = replay(&transaction_db, None).unwrap();
+        verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+use proptest::prelude::*;
+use rust_payment_engine::csv_processor::{ProcessOptions, process_transaction};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::{ClientAccount, Transaction, TransactionType};
+use rust_payment_engine::model::ReferenceModel;
+use rust_payment_engine::verify::{verify, verify_account};
+
+const MAX_CLIENT: u16 = 3;
+
+// Raw operation, turned into a Transaction once tx ids are known
+#[derive(Debug, Clone)]
+enum Op {
+    Deposit { client: u16, amount: f64 },
+    Withdrawal { client: u16, amount: f64 },
+    Dispute { client: u16, tx: u32 },
+    Resolve { client: u16, tx: u32 },
+    Chargeback { client: u16, tx: u32 },
+    // Deposit reusing a tx id, usually of an already stored transaction
+    DuplicateDeposit { client: u16, tx: u32, amount: f64 },
+}
+
+// Amounts with at most 4 decimal places, same precision as the engine
+fn amount() -> impl Strategy<Value = f64> {
+    (1u32..2_000_000).prop_map(|units| units as f64 / 10_000.0)
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let client = 1..=MAX_CLIENT;
+    // Referred tx ids are kept small so most of them point to existing deposits or withdrawals
+    let tx = 1u32..25;
+    prop_oneof![
+        4 => (client.clone(), amount()).prop_map(|(client, amount)| Op::Deposit { client, amount }),
+        2 => (client.clone(), amount()).prop_map(|(client, amount)| Op::Withdrawal { client, amount }),
+        2 => (client.clone(), tx.clone()).prop_map(|(client, tx)| Op::Dispute { client, tx }),
+        1 => (client.clone(), tx.clone()).prop_map(|(client, tx)| Op::Resolve { client, tx }),
+        1 => (client.clone(), tx.clone()).prop_map(|(client, tx)| Op::Chargeback { client, tx }),
+        1 => (client, tx, amount())
+            .prop_map(|(client, tx, amount)| Op::DuplicateDeposit { client, tx, amount }),
+    ]
+}
+
+// Deposits and withdrawals get unique sequential ids, disputes refer to any of them
+fn to_transactions(ops: Vec<Op>) -> Vec<Transaction> {
+    let mut next_id = 1;
+    ops.into_iter()
+        .map(|op| {
+            let (transaction_type, client_id, id, amount) = match op {
+                Op::Deposit { client, amount } | Op::Withdrawal { client, amount } => {
+                    let id = next_id;
+                    next_id += 1;
+                    let transaction_type = if matches!(op, Op::Deposit { .. }) {
+                        TransactionType::Deposit
+                    } else {
+                        TransactionType::Withdrawal
+                    };
+                    (transaction_type, client, id, Some(amount))
+                }
+                Op::Dispute { client, tx } => (TransactionType::Dispute, client, tx, None),
+                Op::Resolve { client, tx } => (TransactionType::Resolve, client, tx, None),
+                Op::Chargeback { client, tx } => (TransactionType::Chargeback, client, tx, None),
+                Op::DuplicateDeposit { client, tx, amount } => {
+                    (TransactionType::Deposit, client, tx, Some(amount))
+                }
+            };
+            Transaction {
+                transaction_type,
+                client_id,
+                id,
+                amount,
+                operator: None,
+                reason: None,
+                timestamp: None,
+                currency: None,
+                to_currency: None,
+                rate: None,
+                to_client: None,
+                fee: None,
+            }
+        })
+        .collect()
+}
+
+fn transactions() -> impl Strategy<Value = Vec<Transaction>> {
+    prop::collection::vec(op(), 1..60).prop_map(to_transactions)
+}
+
+fn new_dbs() -> (TransactionDB, ClientAccountDB) {
+    (
+        TransactionDB::new(":memory:").unwrap(),
+        ClientAccountDB::new(":memory:").unwrap(),
+    )
+}
+
+fn find_account(client_account_db: &ClientAccountDB, client_id: u16) -> Option<ClientAccount> {
+    if client_account_db.does_account_exist(client_id).unwrap() {
+        Some(client_account_db.get_account(client_id).unwrap())
+    } else {
+        None
+    }
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn engine_matches_reference_model(txs in transactions()) {
+        let (transaction_db, client_account_db) = new_dbs();
+        let options = ProcessOptions::default();
+        let mut model = ReferenceModel::new(options.clone());
+
+        for (row, tx) in txs.iter().enumerate() {
+            let engine = process_transaction(tx, &transaction_db, &client_account_db, &options).unwrap();
+            let expected = model.apply(tx);
+            prop_assert_eq!(engine, expected, "row {} {:?}", row, tx);
+        }
+
+        let accounts = client_account_db.get_all_accounts().unwrap();
+        let expected = model.accounts();
+        prop_assert_eq!(accounts.len(), expected.len());
+        for (account, expected) in accounts.iter().zip(&expected) {
+            prop_assert_eq!(account.id(), expected.client);
+            prop_assert!(close(account.available(), expected.available), "{:?} != {:?}", account, expected);
+            prop_assert!(close(account.held(), expected.held), "{:?} != {:?}", account, expected);
+            prop_assert!(close(account.total(), expected.total), "{:?} != {:?}", account, expected);
+            prop_assert_eq!(account.is_locked(), expected.locked);
+        }
+    }
+
+    #[test]
+    fn available_never_negative_and_invariants_hold(txs in transactions()) {
+        let (transaction_db, client_account_db) = new_dbs();
+        let options = ProcessOptions::default();
+
+        for tx in &txs {
+            process_transaction(tx, &transaction_db, &client_account_db, &options).unwrap();
+            if let Some(account) = find_account(&client_account_db, tx.client_id) {
+                prop_assert!(account.available() >= 0.0, "{:?} after {:?}", account, tx);
+                prop_assert!(verify_account(&account, &transaction_db).is_ok(), "{:?} after {:?}", account, tx);
+            }
+        }
+        // Includes the journal cross-check, so money is conserved across disputes and resolves
+        prop_assert!(verify(&transaction_db, &client_account_db).is_ok());
+    }
+
+    #[test]
+    fn money_is_conserved(txs in transactions()) {
+        let (transaction_db, client_account_db) = new_dbs();
+        let options = ProcessOptions::default();
+        let mut model = ReferenceModel::new(options.clone());
+
+        // Money in the engine only changes by deposits, withdrawals and chargebacks
+        let mut expected_total = 0.0;
+        for tx in &txs {
+            let rejected = process_transaction(tx, &transaction_db, &client_account_db, &options).unwrap();
+            model.apply(tx);
+            if rejected.is_none() {
+                match tx.transaction_type {
+                    TransactionType::Deposit => expected_total += tx.amount.unwrap(),
+                    TransactionType::Withdrawal => expected_total -= tx.amount.unwrap(),
+                    TransactionType::Chargeback => {
+                        let state = transaction_db.get_transaction_state(tx.id).unwrap().unwrap();
+                        expected_total -= state.amount.unwrap()
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let total: f64 = client_account_db
+            .get_all_accounts()
+            .unwrap()
+            .iter()
+            .map(|account| account.total())
+            .sum();
+        prop_assert!(close(total, expected_total), "{} != {}", total, expected_total);
+    }
+
+    #[test]
+    fn locks_are_final(txs in transactions()) {
+        let (transaction_db, client_account_db) = new_dbs();
+        let options = ProcessOptions::default();
+        let mut locked: Vec<ClientAccount> = Vec::new();
+
+        for tx in &txs {
+            process_transaction(tx, &transaction_db, &client_account_db, &options).unwrap();
+            for before in &locked {
+                let after = client_account_db.get_account(before.id()).unwrap();
+                prop_assert_eq!(before, &after);
+            }
+            if let Some(account) = find_account(&client_account_db, tx.client_id)
+                && account.is_locked()
+                && !locked.iter().any(|before| before.id() == account.id())
+            {
+                locked.push(account);
+            }
+        }
+    }
+}
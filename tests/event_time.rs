@@ -0,0 +1,107 @@
+mod common;
+
+use common::try_process;
+use rust_payment_engine::csv_processor::ProcessOptions;
+use rust_payment_engine::domain::{RejectionReason, parse_timestamp};
+use rust_payment_engine::replay::{AsOf, balance_as_of};
+
+fn options(lateness: i64) -> ProcessOptions {
+    ProcessOptions {
+        event_time: Some(lateness),
+        ..ProcessOptions::default()
+    }
+}
+
+#[test]
+fn test_rows_are_applied_in_timestamp_order() {
+    // In file order the withdrawal would be rejected, its deposit arrives a second later
+    let input = "type, client, tx, amount, timestamp
+withdrawal, 1, 2, 5.0, 2024-05-01T12:00:01Z
+deposit, 1, 1, 10.0, 2024-05-01T12:00:00Z
+";
+    let (transaction_db, client_account_db, result) = try_process(input, &options(5_000));
+    assert_eq!(result.unwrap(), vec![]);
+    let account = client_account_db.get_account(1).unwrap();
+    assert_eq!(account.available(), 5.0);
+
+    let log: Vec<(u32, Option<u64>, Option<i64>)> = transaction_db
+        .get_event_log(None)
+        .unwrap()
+        .into_iter()
+        .map(|input| {
+            (
+                input.transaction.id,
+                input.line,
+                input.transaction.timestamp,
+            )
+        })
+        .collect();
+    assert_eq!(
+        log,
+        vec![
+            (1, Some(3), Some(1_714_564_800_000)),
+            (2, Some(2), Some(1_714_564_801_000)),
+        ]
+    );
+}
+
+#[test]
+fn test_late_rows_are_rejected() {
+    let input = "type, client, tx, amount, timestamp
+deposit, 1, 1, 10.0, 1000
+deposit, 1, 2, 10.0, 5000
+deposit, 1, 3, 10.0, 3500
+deposit, 1, 4, 10.0, 2000
+";
+    let (_, client_account_db, result) = try_process(input, &options(2_000));
+    let rejections = result.unwrap();
+    assert_eq!(rejections.len(), 1);
+    assert_eq!(rejections[0].id, 4);
+    assert_eq!(rejections[0].line, Some(5));
+    assert_eq!(rejections[0].reason, RejectionReason::LateArrival);
+    assert_eq!(client_account_db.get_account(1).unwrap().available(), 30.0);
+}
+
+#[test]
+fn test_timestamp_is_required() {
+    let input = "type, client, tx, amount, timestamp
+deposit, 1, 1, 10.0, 1000
+deposit, 1, 2, 10.0,
+";
+    let (_, _, result) = try_process(input, &options(1_000));
+    assert_eq!(
+        result.unwrap_err(),
+        "line 3: event time mode requires a timestamp"
+    );
+
+    let input = "type, client, tx, amount, timestamp\ndeposit, 1, 1, 10.0, yesterday\n";
+    let (_, _, result) = try_process(input, &options(1_000));
+    assert!(result.unwrap_err().contains("invalid timestamp"));
+}
+
+#[test]
+fn test_balance_as_of_time() {
+    let input = "type, client, tx, amount, timestamp
+deposit, 1, 1, 10.0, 2024-05-01T12:00:00Z
+deposit, 1, 2, 4.0, 2024-05-01T13:00:00Z
+dispute, 1, 2, , 2024-05-01T14:00:00Z
+";
+    let (transaction_db, _, result) = try_process(input, &options(0));
+    result.unwrap();
+    let available_at = |timestamp: &str| {
+        balance_as_of(
+            &transaction_db,
+            1,
+            AsOf::Time(parse_timestamp(timestamp).unwrap()),
+        )
+        .unwrap()
+        .into_iter()
+        .next()
+        .map(|account| (account.available(), account.held()))
+    };
+
+    assert_eq!(available_at("2024-05-01T11:59:59Z"), None);
+    assert_eq!(available_at("2024-05-01T12:30:00Z"), Some((10.0, 0.0)));
+    assert_eq!(available_at("2024-05-01T13:00:00Z"), Some((14.0, 0.0)));
+    assert_eq!(available_at("2024-05-02T00:00:00Z"), Some((10.0, 4.0)));
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::{available, process};
+use rust_payment_engine::csv_processor::ProcessOptions;
+use rust_payment_engine::domain::{DomainEvent, RejectionReason};
+use rust_payment_engine::events::ChannelSink;
+use rust_payment_engine::fees::FeeSchedule;
+use rust_payment_engine::output::{OutputOptions, format_records};
+use rust_payment_engine::replay::{replay, verify_replay};
+use std::sync::Arc;
+
+const SCHEDULE: &str = r#"{
+    "tiers": {"2": "vip"},
+    "rules": [
+        {"type": "withdrawal", "flat": 0.5, "percent": 1.0, "min": 1.0, "max": 5.0},
+        {"type": "withdrawal", "tier": "vip", "flat": 0.1},
+        {"type": "transfer", "bands": [{"up_to": 5.0, "flat": 0.25}, {"percent": 10.0}]},
+        {"type": "chargeback", "flat": 15.0}
+    ]
+}"#;
+
+fn options() -> ProcessOptions {
+    ProcessOptions {
+        paranoid: true,
+        fees: FeeSchedule::from_json(SCHEDULE.as_bytes()).unwrap(),
+        ..ProcessOptions::default()
+    }
+}
+
+#[test]
+fn test_fees_are_charged_per_type_and_tier() {
+    let input = "type, client, tx, amount, to_client
+deposit, 1, 1, 100.0,
+withdrawal, 1, 2, 10.0,
+withdrawal, 1, 3, 88.5,
+deposit, 2, 4, 10.0,
+withdrawal, 2, 5, 5.0,
+transfer, 1, 6, 4.0, 2
+transfer, 1, 7, 20.0, 2
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &options());
+    // Enough for the amount, not for the amount and its fee
+    assert_eq!(rejected, vec![(3, RejectionReason::InsufficientFunds)]);
+
+    assert_eq!(available(&client_account_db, 1, ""), Some(62.75));
+    assert_eq!(available(&client_account_db, 2, ""), Some(28.9));
+    assert!(transaction_db.get_unbalanced_entries().unwrap().is_empty());
+
+    let report = format_records(
+        &transaction_db.get_fee_totals().unwrap(),
+        &OutputOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        report,
+        "client,currency,type,count,total\n\
+         1,,transfer,2,2.2500\n\
+         1,,withdrawal,1,1.0000\n\
+         2,,withdrawal,1,0.1000\n"
+    );
+
+    // The logged fees are replayed, without the fee schedule
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_fees_are_capped_to_available_funds_and_rounded_to_currency() {
+    let input = "type, client, tx, amount, currency
+deposit, 3, 10, 50.0,
+deposit, 3, 11, 5.0,
+dispute, 3, 10, ,
+chargeback, 3, 10, ,
+deposit, 4, 12, 1000, JPY
+withdrawal, 4, 13, 333, JPY
+";
+    let (sink, receiver) = ChannelSink::new();
+    let mut options = options();
+    options.events.add_sink(Arc::new(sink));
+    let (transaction_db, client_account_db, rejected) = process(input, &options);
+    assert_eq!(rejected, vec![]);
+
+    // The chargeback fee only takes what is left
+    let account = client_account_db.get_account(3).unwrap();
+    assert_eq!((account.available(), account.total()), (0.0, 0.0));
+    assert!(account.is_locked());
+    // 0.5 + 3.33, rounded to whole yen
+    assert_eq!(available(&client_account_db, 4, "JPY"), Some(663.0));
+
+    let charged: Vec<DomainEvent> = receiver
+        .try_iter()
+        .filter(|event| matches!(event, DomainEvent::FeeCharged { .. }))
+        .collect();
+    assert_eq!(
+        charged,
+        vec![
+            DomainEvent::FeeCharged {
+                client: 3,
+                tx: 10,
+                amount: 5.0,
+                currency: String::new(),
+            },
+            DomainEvent::FeeCharged {
+                client: 4,
+                tx: 13,
+                amount: 4.0,
+                currency: "JPY".to_string(),
+            },
+        ]
+    );
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_partial_operations_are_charged_once_per_type() {
+    let input = "type, client, tx, amount
+deposit, 1, 1, 100.0
+dispute, 1, 1, 30.0
+chargeback, 1, 1, 10.0
+chargeback, 1, 1, 20.0
+";
+    let mut options = options();
+    options.lock_policy = "chargeback".parse().unwrap();
+    let (transaction_db, client_account_db, rejected) = process(input, &options);
+    assert_eq!(rejected, vec![]);
+
+    // Only the first chargeback pays the 15.0 fee
+    let account = client_account_db.get_account(1).unwrap();
+    assert_eq!((account.available(), account.held()), (55.0, 0.0));
+    let totals = transaction_db.get_fee_totals().unwrap();
+    assert_eq!(totals.len(), 1);
+    assert_eq!((totals[0].count, totals[0].total), (1, 15.0));
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
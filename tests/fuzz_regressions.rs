@@ -0,0 +1,88 @@
+use rust_payment_engine::csv_processor::{ProcessOptions, process_csv_with_options};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::{Rejection, RejectionReason};
+use rust_payment_engine::verify::verify;
+use std::error::Error;
+use std::fs::{self, File};
+
+// Same checks as the process_csv fuzz target: errors are fine, panics and broken invariants are not
+fn run_fixture(
+    path: &str,
+) -> (
+    Result<Vec<Rejection>, Box<dyn Error>>,
+    TransactionDB,
+    ClientAccountDB,
+) {
+    let file = File::open(path).expect("Failed to open fixture");
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    let options = ProcessOptions {
+        paranoid: true,
+        ..ProcessOptions::default()
+    };
+    let result = process_csv_with_options(file, &transaction_db, &client_account_db, &options);
+    verify(&transaction_db, &client_account_db)
+        .unwrap_or_else(|err| panic!("{}: invariant violated: {}", path, err));
+    (result, transaction_db, client_account_db)
+}
+
+fn reasons(path: &str) -> Vec<RejectionReason> {
+    let (result, _, _) = run_fixture(path);
+    result
+        .expect("Failed to process CSV")
+        .iter()
+        .map(|rejection| rejection.reason)
+        .collect()
+}
+
+#[test]
+fn test_all_fuzz_fixtures_keep_invariants() {
+    for entry in fs::read_dir("tests/resources/fuzz").unwrap() {
+        let path = entry.unwrap().path();
+        let _ = run_fixture(path.to_str().unwrap());
+    }
+}
+
+#[test]
+fn test_duplicate_tx_ids_are_rejected() {
+    let (result, _, client_account_db) = run_fixture("tests/resources/fuzz/duplicate_tx.csv");
+    let rejections = result.expect("Failed to process CSV");
+    assert_eq!(
+        rejections
+            .iter()
+            .map(|rejection| rejection.reason)
+            .collect::<Vec<_>>(),
+        vec![
+            RejectionReason::DuplicateTransaction,
+            RejectionReason::DuplicateTransaction
+        ]
+    );
+    // The original deposit is still the one disputed
+    assert_eq!(client_account_db.get_account(1).unwrap().held(), 10.0);
+}
+
+#[test]
+fn test_invalid_amounts_are_rejected() {
+    assert_eq!(
+        reasons("tests/resources/fuzz/non_finite_amount.csv"),
+        vec![RejectionReason::InvalidAmount; 3]
+    );
+    assert_eq!(
+        reasons("tests/resources/fuzz/non_positive_amount.csv"),
+        vec![RejectionReason::InvalidAmount; 4]
+    );
+    assert_eq!(
+        reasons("tests/resources/fuzz/huge_amount.csv"),
+        vec![RejectionReason::InvalidAmount; 3]
+    );
+}
+
+#[test]
+fn test_malformed_row_is_an_error_not_a_panic() {
+    let (result, _, client_account_db) =
+        run_fixture("tests/resources/fuzz/client_out_of_range.csv");
+    assert!(result.is_err());
+    // Rows before the malformed one were applied
+    assert_eq!(client_account_db.get_account(1).unwrap().total(), 1.0);
+}
@@ -0,0 +1,202 @@
+//! Golden-file tests, one case per directory in tests/resources
+//!
+//! # Case files:
+//!
+//! - input.csv: Regular input, required. Directories without it are not cases
+//! - expected_accounts.csv: Final accounts, in the default CSV output
+//! - expected_rejections.csv: Optional, rows that were not applied
+//! - admin.csv: Optional, administrative operations applied after the input
+//! - config.json: Optional, processing options, e.g. {"paranoid": true, "locked_allow": "resolve,chargeback"}
+//!
+//! Run with BLESS=1 to regenerate the expected files from the current engine output
+mod common;
+
+use common::split_csv;
+use rust_payment_engine::csv_processor::{
+    ProcessOptions, get_all_accounts_formatted, process_admin_csv, process_csv_with_options,
+};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::LockPolicy;
+use rust_payment_engine::output::{OutputOptions, format_records};
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+const RESOURCES: &str = "tests/resources";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CaseConfig {
+    paranoid: bool,
+    locked_allow: Option<String>,
+}
+
+impl CaseConfig {
+    fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = dir.join("config.json");
+        if !path.exists() {
+            return Ok(CaseConfig::default());
+        }
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    fn process_options(&self) -> Result<ProcessOptions, Box<dyn Error>> {
+        let lock_policy = match &self.locked_allow {
+            Some(types) => types.parse::<LockPolicy>()?,
+            None => LockPolicy::default(),
+        };
+        Ok(ProcessOptions {
+            paranoid: self.paranoid,
+            lock_policy,
+            ..ProcessOptions::default()
+        })
+    }
+}
+
+/// Every case runs on each storage backend, and must produce the same output on all of them
+#[derive(Debug, Clone, Copy)]
+enum Backend {
+    Memory,
+    File,
+}
+
+const BACKENDS: [Backend; 2] = [Backend::Memory, Backend::File];
+
+struct Output {
+    accounts: String,
+    rejections: String,
+    rejection_count: usize,
+}
+
+// Databases of the file backend live in a per-case temporary directory, removed after the run
+fn db_dir(case: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("golden_{}_{}", std::process::id(), case))
+}
+
+fn open_dbs(
+    backend: Backend,
+    case: &str,
+) -> Result<(TransactionDB, ClientAccountDB), Box<dyn Error>> {
+    match backend {
+        Backend::Memory => Ok((
+            TransactionDB::new(":memory:")?,
+            ClientAccountDB::new(":memory:")?,
+        )),
+        Backend::File => {
+            let dir = db_dir(case);
+            if dir.exists() {
+                fs::remove_dir_all(&dir)?;
+            }
+            fs::create_dir_all(&dir)?;
+            let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+            Ok((
+                TransactionDB::new(&path("transactions.db"))?,
+                ClientAccountDB::new(&path("client_accounts.db"))?,
+            ))
+        }
+    }
+}
+
+fn run_case(dir: &Path, backend: Backend) -> Result<Output, Box<dyn Error>> {
+    let case = dir.file_name().unwrap().to_string_lossy().into_owned();
+    let options = CaseConfig::load(dir)?.process_options()?;
+    let (transaction_db, client_account_db) = open_dbs(backend, &case)?;
+
+    let input = File::open(dir.join("input.csv"))?;
+    let rejections =
+        process_csv_with_options(input, &transaction_db, &client_account_db, &options)?;
+    let admin_path = dir.join("admin.csv");
+    if admin_path.exists() {
+        process_admin_csv(File::open(admin_path)?, &transaction_db, &client_account_db)?;
+    }
+
+    let output_options = OutputOptions::default();
+    let output = Output {
+        accounts: get_all_accounts_formatted(&client_account_db, &output_options)?,
+        rejections: format_records(&rejections, &output_options)?,
+        rejection_count: rejections.len(),
+    };
+
+    drop((transaction_db, client_account_db));
+    if let Backend::File = backend {
+        fs::remove_dir_all(db_dir(&case))?;
+    }
+    Ok(output)
+}
+
+fn compare(dir: &Path, name: &str, actual: &str) -> Result<(), String> {
+    let path = dir.join(name);
+    let expected =
+        fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if split_csv(actual) != split_csv(&expected) {
+        return Err(format!(
+            "{} does not match\n--- expected\n{}\n--- actual\n{}",
+            path.display(),
+            expected.trim(),
+            actual.trim()
+        ));
+    }
+    Ok(())
+}
+
+fn check_case(dir: &Path, output: &Output) -> Result<(), String> {
+    compare(dir, "expected_accounts.csv", &output.accounts)?;
+    if dir.join("expected_rejections.csv").exists() {
+        compare(dir, "expected_rejections.csv", &output.rejections)?;
+    }
+    Ok(())
+}
+
+// Rejections are only written when there are any, so cases without them stay a plain input/output pair
+fn bless_case(dir: &Path, output: &Output) -> Result<(), Box<dyn Error>> {
+    fs::write(dir.join("expected_accounts.csv"), &output.accounts)?;
+    let rejections_path = dir.join("expected_rejections.csv");
+    if output.rejection_count > 0 {
+        fs::write(rejections_path, &output.rejections)?;
+    } else if rejections_path.exists() {
+        fs::remove_file(rejections_path)?;
+    }
+    Ok(())
+}
+
+fn case_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(RESOURCES)
+        .expect("Failed to read tests/resources")
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .filter(|path| path.join("input.csv").is_file())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+#[test]
+fn test_golden_cases() {
+    let bless = std::env::var("BLESS").is_ok_and(|value| value == "1");
+    let dirs = case_dirs();
+    assert!(!dirs.is_empty(), "No golden cases found in {}", RESOURCES);
+
+    let mut failures = Vec::new();
+    for dir in &dirs {
+        for backend in BACKENDS {
+            let result = run_case(dir, backend)
+                .map_err(|err| err.to_string())
+                .and_then(|output| {
+                    if bless {
+                        bless_case(dir, &output).map_err(|err| err.to_string())
+                    } else {
+                        check_case(dir, &output)
+                    }
+                });
+            if let Err(err) = result {
+                failures.push(format!("[{} on {:?}] {}", dir.display(), backend, err));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} golden case(s) failed, run with BLESS=1 to accept the new output:\n\n{}",
+        failures.len(),
+        failures.join("\n\n")
+    );
+}
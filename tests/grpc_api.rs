@@ -0,0 +1,220 @@
+use rust_payment_engine::csv_processor::ProcessOptions;
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::grpc::proto::{
+    ClientAccount, RejectionReason, Transaction, TransactionType, WatchAccountsRequest,
+};
+use rust_payment_engine::grpc::{PaymentEngineClient, PaymentEngineServer, PaymentEngineService};
+use rust_payment_engine::server::Engine;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+
+// Serve the gRPC API on a loopback port picked by the OS, with in-memory storage
+async fn spawn_server() -> PaymentEngineClient<Channel> {
+    serve(PaymentEngineService::new(in_memory_engine())).await
+}
+
+fn in_memory_engine() -> Engine {
+    Engine {
+        transaction_db: TransactionDB::new(":memory:").expect("Failed to create TransactionDB"),
+        client_account_db: ClientAccountDB::new(":memory:")
+            .expect("Failed to create ClientAccountDB"),
+        options: ProcessOptions::default(),
+    }
+}
+
+async fn serve(service: PaymentEngineService) -> PaymentEngineClient<Channel> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        tonic::transport::Server::builder()
+            .add_service(PaymentEngineServer::new(service))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .unwrap();
+    });
+    PaymentEngineClient::connect(format!("http://{}", address))
+        .await
+        .unwrap()
+}
+
+fn transaction(
+    transaction_type: TransactionType,
+    client: u32,
+    tx: u32,
+    amount: Option<f64>,
+) -> Transaction {
+    Transaction {
+        r#type: transaction_type.into(),
+        client,
+        tx,
+        amount,
+        timestamp: None,
+        currency: None,
+        to_currency: None,
+        to_client: None,
+        operator: None,
+        reason: None,
+    }
+}
+
+fn with_token(tx: Transaction, token: &str) -> Request<Transaction> {
+    let mut request = Request::new(tx);
+    request.metadata_mut().insert(
+        "authorization",
+        format!("Bearer {}", token).parse().unwrap(),
+    );
+    request
+}
+
+#[tokio::test]
+async fn test_submit_applies_and_rejects_transactions() {
+    let mut client = spawn_server().await;
+
+    let response = client
+        .submit(transaction(TransactionType::Deposit, 1, 1, Some(10.0)))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.accepted);
+    assert_eq!(response.rejection(), RejectionReason::Unspecified);
+
+    let response = client
+        .submit(transaction(TransactionType::Withdrawal, 1, 2, Some(15.0)))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!response.accepted);
+    assert_eq!(response.rejection(), RejectionReason::InsufficientFunds);
+    assert_eq!(response.message, "insufficient funds");
+
+    let status = client
+        .submit(transaction(TransactionType::Unspecified, 1, 3, None))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let status = client
+        .submit(transaction(TransactionType::Deposit, 70_000, 3, Some(1.0)))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_bulk_submit_applies_stream_in_order() {
+    let mut client = spawn_server().await;
+
+    let transactions = vec![
+        transaction(TransactionType::Deposit, 1, 1, Some(5.0)),
+        transaction(TransactionType::Deposit, 2, 2, Some(3.0)),
+        transaction(TransactionType::Dispute, 1, 1, None),
+        transaction(TransactionType::Chargeback, 1, 1, None),
+        transaction(TransactionType::Deposit, 1, 3, Some(1.0)),
+        transaction(TransactionType::Lock, 2, 4, None),
+    ];
+    let summary = client
+        .bulk_submit(tokio_stream::iter(transactions))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(summary.accepted, 4);
+    assert_eq!(summary.rejected, 2);
+    let rejections: Vec<(u32, RejectionReason)> = summary
+        .rejections
+        .iter()
+        .map(|response| (response.tx, response.rejection()))
+        .collect();
+    assert_eq!(
+        rejections,
+        vec![
+            (3, RejectionReason::AccountLocked),
+            (4, RejectionReason::AdminOnly)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_submit_admin_requires_the_admin_token() {
+    let lock = Transaction {
+        operator: Some("ops-42".to_string()),
+        reason: Some("fraud review".to_string()),
+        ..transaction(TransactionType::Lock, 1, 100, None)
+    };
+
+    let mut client = spawn_server().await;
+    let status = client
+        .submit_admin(with_token(lock.clone(), "secret"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let mut client =
+        serve(PaymentEngineService::new(in_memory_engine()).with_admin_token("secret")).await;
+    let status = client
+        .submit_admin(with_token(lock.clone(), "wrong"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    let response = client
+        .submit_admin(with_token(lock.clone(), "secret"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.accepted);
+
+    // The locked account rejects deposits, and a reused tx id is an invalid operation
+    let response = client
+        .submit(transaction(TransactionType::Deposit, 1, 1, Some(1.0)))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.rejection(), RejectionReason::AccountLocked);
+    let status = client
+        .submit_admin(with_token(lock, "secret"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_watch_accounts_streams_changes_of_selected_clients() {
+    let mut client = spawn_server().await;
+
+    let mut feed = client
+        .watch_accounts(WatchAccountsRequest { clients: vec![1] })
+        .await
+        .unwrap()
+        .into_inner();
+    for tx in [
+        transaction(TransactionType::Deposit, 2, 1, Some(3.0)),
+        transaction(TransactionType::Deposit, 1, 2, Some(10.0)),
+        transaction(TransactionType::Withdrawal, 1, 3, Some(50.0)),
+        transaction(TransactionType::Dispute, 1, 2, None),
+    ] {
+        client.submit(tx).await.unwrap();
+    }
+
+    // Client 2 is filtered out and the rejected withdrawal changes nothing
+    assert_eq!(
+        feed.message().await.unwrap(),
+        Some(ClientAccount {
+            client: 1,
+            available: 10.0,
+            held: 0.0,
+            total: 10.0,
+            locked: false,
+            currency: String::new(),
+        })
+    );
+    assert_eq!(
+        feed.message().await.unwrap(),
+        Some(ClientAccount {
+            client: 1,
+            available: 0.0,
+            held: 10.0,
+            total: 10.0,
+            locked: false,
+            currency: String::new(),
+        })
+    );
+}
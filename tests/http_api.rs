@@ -0,0 +1,281 @@
+use reqwest::StatusCode;
+use rust_payment_engine::csv_processor::ProcessOptions;
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::DomainEvent;
+use rust_payment_engine::events::WebhookSink;
+use rust_payment_engine::server::{AppState, Engine, SubmissionResult, SubmissionStatus, router};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+// Serve the API on a loopback port picked by the OS, with in-memory storage
+async fn spawn_server(options: ProcessOptions) -> String {
+    serve(AppState::new(in_memory_engine(options))).await
+}
+
+fn in_memory_engine(options: ProcessOptions) -> Engine {
+    Engine {
+        transaction_db: TransactionDB::new(":memory:").expect("Failed to create TransactionDB"),
+        client_account_db: ClientAccountDB::new(":memory:")
+            .expect("Failed to create ClientAccountDB"),
+        options,
+    }
+}
+
+async fn serve(state: AppState) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router(state)).await.unwrap();
+    });
+    format!("http://{}", address)
+}
+
+async fn post_admin(base: &str, token: Option<&str>, body: Value) -> (StatusCode, Value) {
+    let mut request = reqwest::Client::new()
+        .post(format!("{}/admin/operations", base))
+        .json(&body);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await.unwrap();
+    (response.status(), response.json().await.unwrap())
+}
+
+async fn post(base: &str, body: Value) -> (StatusCode, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("{}/transactions", base))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    (response.status(), response.json().await.unwrap())
+}
+
+async fn get(base: &str, path: &str) -> (StatusCode, Value) {
+    let response = reqwest::get(format!("{}{}", base, path)).await.unwrap();
+    (response.status(), response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn test_single_transactions_update_accounts() {
+    let base = spawn_server(ProcessOptions::default()).await;
+
+    let (status, body) = post(
+        &base,
+        json!({"type": "deposit", "client": 1, "tx": 1, "amount": 10.5}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"tx": 1, "client": 1, "status": "accepted"}));
+    let (status, _) = post(&base, json!({"type": "dispute", "client": 1, "tx": 1})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, account) = get(&base, "/accounts/1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        account,
+        json!({"client": 1, "currency": "", "available": "0.0000", "held": "10.5000", "total": "10.5000", "locked": false})
+    );
+
+    let (status, tx) = get(&base, "/transactions/1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        tx,
+        json!({"tx": 1, "client": 1, "type": "deposit", "amount": "10.5000", "currency": "", "dispute_state": "disputed"})
+    );
+
+    // Amounts have the decimal places of their currency, more are rejected
+    let (status, body) = post(
+        &base,
+        json!({"type": "deposit", "client": 2, "tx": 2, "amount": 1500.6, "currency": "JPY"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_amount");
+    post(
+        &base,
+        json!({"type": "deposit", "client": 2, "tx": 3, "amount": 1500, "currency": "JPY"}),
+    )
+    .await;
+    let (_, account) = get(&base, "/accounts/2?currency=JPY").await;
+    assert_eq!(
+        (&account["available"], &account["total"]),
+        (&json!("1500"), &json!("1500"))
+    );
+}
+
+#[tokio::test]
+async fn test_rejections_are_typed_errors() {
+    let base = spawn_server(ProcessOptions::default()).await;
+    post(
+        &base,
+        json!({"type": "deposit", "client": 1, "tx": 1, "amount": 1.0}),
+    )
+    .await;
+
+    let cases = [
+        (
+            json!({"type": "withdrawal", "client": 1, "tx": 2, "amount": 5.0}),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "insufficient_funds",
+        ),
+        (
+            json!({"type": "dispute", "client": 1, "tx": 99}),
+            StatusCode::NOT_FOUND,
+            "transaction_not_found",
+        ),
+        (
+            json!({"type": "deposit", "client": 1, "tx": 1, "amount": 1.0}),
+            StatusCode::CONFLICT,
+            "duplicate_transaction",
+        ),
+        (
+            json!({"type": "deposit", "client": 1, "tx": 3}),
+            StatusCode::BAD_REQUEST,
+            "missing_amount",
+        ),
+        (
+            json!({"type": "unlock", "client": 1, "tx": 4}),
+            StatusCode::FORBIDDEN,
+            "admin_only",
+        ),
+        (
+            json!({"type": "teleport", "client": 1, "tx": 5}),
+            StatusCode::BAD_REQUEST,
+            "invalid_request",
+        ),
+    ];
+    for (body, expected_status, expected_code) in cases {
+        let (status, error) = post(&base, body.clone()).await;
+        assert_eq!(status, expected_status, "{}", body);
+        assert_eq!(error["code"], expected_code, "{}", body);
+        assert!(error["message"].is_string());
+    }
+
+    let (status, error) = get(&base, "/accounts/7").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error["code"], "account_not_found");
+    let (status, error) = get(&base, "/accounts/not-a-client").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["code"], "invalid_request");
+}
+
+#[tokio::test]
+async fn test_admin_operations_require_the_admin_token() {
+    let unlock = json!({"type": "unlock", "client": 1, "tx": 100, "operator": "ops-42", "reason": "reviewed"});
+
+    // Disabled on servers without an admin token
+    let base = spawn_server(ProcessOptions::default()).await;
+    let (status, error) = post_admin(&base, Some("secret"), unlock.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(error["code"], "admin_only");
+
+    let base = serve(
+        AppState::new(in_memory_engine(ProcessOptions::default())).with_admin_token("secret"),
+    )
+    .await;
+    post(
+        &base,
+        json!({"type": "deposit", "client": 1, "tx": 1, "amount": 10.0}),
+    )
+    .await;
+    post(&base, json!({"type": "dispute", "client": 1, "tx": 1})).await;
+    post(&base, json!({"type": "chargeback", "client": 1, "tx": 1})).await;
+    for token in [None, Some("wrong")] {
+        let (status, error) = post_admin(&base, token, unlock.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(error["code"], "unauthorized");
+    }
+
+    let (status, body) = post_admin(&base, Some("secret"), unlock.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"tx": 100, "client": 1, "status": "accepted"}));
+    let (_, account) = get(&base, "/accounts/1").await;
+    assert_eq!(account["locked"], false);
+
+    // Same checks as the --admin file
+    let (status, error) = post_admin(&base, Some("secret"), unlock).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["code"], "invalid_request");
+    let (status, _) = post_admin(
+        &base,
+        Some("secret"),
+        json!({"type": "deposit", "client": 1, "tx": 101, "amount": 1.0, "operator": "ops-42"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_batch_is_applied_in_order_and_reports_each_row() {
+    let base = spawn_server(ProcessOptions::default()).await;
+
+    let (status, body) = post(
+        &base,
+        json!([
+            {"type": "deposit", "client": 1, "tx": 1, "amount": 100.0},
+            {"type": "deposit", "client": 2, "tx": 2, "amount": 50.0},
+            {"type": "dispute", "client": 1, "tx": 1},
+            {"type": "chargeback", "client": 1, "tx": 1},
+            {"type": "deposit", "client": 1, "tx": 3, "amount": 1.0}
+        ]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let results: Vec<SubmissionResult> = serde_json::from_value(body).unwrap();
+    let statuses: Vec<SubmissionStatus> = results.iter().map(|result| result.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            SubmissionStatus::Accepted,
+            SubmissionStatus::Accepted,
+            SubmissionStatus::Accepted,
+            SubmissionStatus::Accepted,
+            SubmissionStatus::Rejected
+        ]
+    );
+    assert_eq!(results[4].error.as_ref().unwrap().code, "account_locked");
+
+    let (status, accounts) = get(&base, "/accounts?sort=total").await;
+    assert_eq!(status, StatusCode::OK);
+    let clients: Vec<&Value> = accounts
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|account| &account["client"])
+        .collect();
+    assert_eq!(clients, vec![&json!(2), &json!(1)]);
+    assert_eq!(accounts[1]["locked"], true);
+}
+
+#[tokio::test]
+async fn test_engine_calls_do_not_block_the_runtime() {
+    // The webhook is served by the same single threaded runtime as the API
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let store = received.clone();
+    let app = axum::Router::new().route(
+        "/events",
+        axum::routing::post(
+            move |axum::Json(event): axum::Json<DomainEvent>| async move {
+                store.lock().unwrap().push(event);
+            },
+        ),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let mut options = ProcessOptions::default();
+    options.events.add_sink(Arc::new(WebhookSink::new(&format!(
+        "http://{}/events",
+        address
+    ))));
+    let base = spawn_server(options).await;
+    let (status, _) = post(
+        &base,
+        json!({"type": "deposit", "client": 1, "tx": 1, "amount": 2.5}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
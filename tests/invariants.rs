@@ -0,0 +1,26 @@
+use rust_payment_engine::csv_processor::{ProcessOptions, process_csv_with_options};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use std::fs::File;
+
+fn run_paranoid(input_path: &str) {
+    let file = File::open(input_path).expect("Failed to open input CSV");
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    let options = ProcessOptions {
+        paranoid: true,
+        ..ProcessOptions::default()
+    };
+    process_csv_with_options(file, &transaction_db, &client_account_db, &options)
+        .expect("Invariant violated");
+}
+
+#[test]
+fn test_big_csv_holds_invariants_after_every_row() {
+    run_paranoid("tests/resources/big/input.csv");
+}
+
+#[test]
+fn test_lock_csv_holds_invariants_after_every_row() {
+    run_paranoid("tests/resources/lock/input.csv");
+}
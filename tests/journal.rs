@@ -0,0 +1,28 @@
+use rust_payment_engine::csv_processor::process_csv;
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::JournalEntry;
+use rust_payment_engine::verify::reconcile_journal;
+use std::fs::File;
+
+#[test]
+fn test_journal_matches_account_balances() {
+    let file = File::open("tests/resources/lock/input.csv").expect("Failed to open input CSV");
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    process_csv(file, &transaction_db, &client_account_db).expect("Failed to process CSV");
+
+    reconcile_journal(&transaction_db, &client_account_db).expect("Journal does not reconcile");
+    let (available, held) = transaction_db.get_journal_balances(2, "").unwrap();
+    assert_eq!((available, held), (100.0, 1000.0));
+    assert!(transaction_db.get_unbalanced_entries().unwrap().is_empty());
+}
+
+#[test]
+fn test_unbalanced_journal_entry_is_rejected() {
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let mut entry = JournalEntry::deposit(1, 1, 10.0);
+    entry.postings[1].amount = 11.0;
+    assert!(transaction_db.record_journal_entry(&entry).is_err());
+    assert_eq!(transaction_db.get_journal_balances(1, "").unwrap(), (0.0, 0.0));
+}
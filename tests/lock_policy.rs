@@ -0,0 +1,60 @@
+use rust_payment_engine::csv_processor::{ProcessOptions, process_csv_with_options};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::{LockPolicy, Rejection, RejectionReason, TransactionType};
+use std::fs::File;
+
+fn run(options: &ProcessOptions) -> (ClientAccountDB, Vec<Rejection>) {
+    let file =
+        File::open("tests/resources/lock_policy/input.csv").expect("Failed to open input CSV");
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    let rejections = process_csv_with_options(file, &transaction_db, &client_account_db, options)
+        .expect("Failed to process CSV");
+    (client_account_db, rejections)
+}
+
+#[test]
+fn test_default_policy_blocks_everything_and_reports_rejections() {
+    let (client_account_db, rejections) = run(&ProcessOptions::default());
+    let account = client_account_db.get_account(1).unwrap();
+    assert!(account.is_locked());
+    assert_eq!(account.held(), 50.0, "Held funds of tx 2 are stranded");
+
+    let reasons: Vec<(Option<u64>, TransactionType, RejectionReason)> = rejections
+        .iter()
+        .map(|rejection| (rejection.line, rejection.transaction_type, rejection.reason))
+        .collect();
+    assert_eq!(
+        reasons,
+        vec![
+            (
+                Some(7),
+                TransactionType::Resolve,
+                RejectionReason::AccountLocked
+            ),
+            (
+                Some(8),
+                TransactionType::Deposit,
+                RejectionReason::AccountLocked
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_policy_can_allow_resolve_on_locked_account() {
+    let options = ProcessOptions {
+        paranoid: true,
+        lock_policy: LockPolicy::allow(&[TransactionType::Resolve, TransactionType::Chargeback]),
+        ..ProcessOptions::default()
+    };
+    let (client_account_db, rejections) = run(&options);
+    let account = client_account_db.get_account(1).unwrap();
+    assert!(account.is_locked());
+    assert_eq!(account.held(), 0.0);
+    assert_eq!(account.available(), 50.0);
+    assert_eq!(rejections.len(), 1);
+    assert_eq!(rejections[0].reason, RejectionReason::AccountLocked);
+    assert_eq!(rejections[0].id, 3);
+}
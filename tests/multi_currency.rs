@@ -0,0 +1,88 @@
+mod common;
+
+use common::{paranoid_options, process, try_process};
+use rust_payment_engine::csv_processor::{ProcessOptions, get_all_accounts_formatted};
+use rust_payment_engine::domain::RejectionReason;
+use rust_payment_engine::output::OutputOptions;
+use rust_payment_engine::replay::{replay, verify_replay};
+
+#[test]
+fn test_accounts_per_client_and_currency() {
+    let input = "type, client, tx, amount, currency
+deposit, 1, 1, 10.005, usd
+deposit, 1, 2, 1500.6, JPY
+deposit, 1, 3, 2.5,
+deposit, 1, 4, 10.01, usd
+deposit, 1, 5, 1501, JPY
+withdrawal, 1, 6, 20.0, EUR
+withdrawal, 1, 7, 500, JPY
+deposit, 2, 8, 1.2345, KWD
+deposit, 2, 9, 1.235, KWD
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &paranoid_options());
+    // More decimals than the currency has are not rounded, and there is no EUR account to withdraw from
+    assert_eq!(
+        rejected,
+        vec![
+            (1, RejectionReason::InvalidAmount),
+            (2, RejectionReason::InvalidAmount),
+            (6, RejectionReason::InsufficientFunds),
+            (8, RejectionReason::InvalidAmount),
+        ]
+    );
+
+    // Amounts have the decimal places of their currency
+    let output = get_all_accounts_formatted(&client_account_db, &OutputOptions::default()).unwrap();
+    assert_eq!(
+        output,
+        "client,currency,available,held,total,locked\n\
+         1,,2.5000,0.0000,2.5000,false\n\
+         1,EUR,0.00,0.00,0.00,false\n\
+         1,JPY,1001,0,1001,false\n\
+         1,USD,10.01,0.00,10.01,false\n\
+         2,KWD,1.235,0.000,1.235,false\n"
+    );
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_disputes_use_currency_of_referred_transaction() {
+    let input = "type, client, tx, amount, currency
+deposit, 1, 1, 10.0, USD
+deposit, 1, 2, 7.0, EUR
+dispute, 1, 2, ,
+resolve, 1, 2, , USD
+dispute, 1, 1, , EUR
+chargeback, 1, 1, ,
+deposit, 1, 3, 1.0, EUR
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &paranoid_options());
+    assert_eq!(rejected, vec![(3, RejectionReason::AccountLocked)]);
+
+    let usd = client_account_db
+        .get_currency_account(1, "USD")
+        .unwrap()
+        .unwrap();
+    assert_eq!((usd.available(), usd.held(), usd.total()), (0.0, 0.0, 0.0));
+    // The chargeback of the USD deposit locks the EUR account too
+    let eur = client_account_db
+        .get_currency_account(1, "EUR")
+        .unwrap()
+        .unwrap();
+    assert_eq!((eur.available(), eur.held()), (7.0, 0.0));
+    assert!(usd.is_locked() && eur.is_locked());
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_invalid_currency_stops_processing() {
+    let input = "type, client, tx, amount, currency
+deposit, 1, 1, 10.0, DOLLARS
+";
+    let (_, _, result) = try_process(input, &ProcessOptions::default());
+    assert!(result.is_err());
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::{balances, process};
+use rust_payment_engine::csv_processor::ProcessOptions;
+use rust_payment_engine::db::AccountOrder;
+use rust_payment_engine::domain::{DisputePolicy, RejectionReason, TransactionType};
+use rust_payment_engine::output::{OutputOptions, format_accounts};
+use rust_payment_engine::replay::{AsOf, balance_as_of, replay, verify_replay};
+
+fn options(allow_negative: bool) -> ProcessOptions {
+    let mut options = ProcessOptions {
+        paranoid: true,
+        disputes: DisputePolicy {
+            allow_negative,
+            ..DisputePolicy::default()
+        },
+        ..ProcessOptions::default()
+    };
+    options
+        .fees
+        .set_flat_fee(TransactionType::Chargeback, 15.0)
+        .unwrap();
+    options
+}
+
+#[test]
+fn test_dispute_of_spent_funds_leaves_a_debt() {
+    let input = "type, client, tx, amount
+deposit, 1, 1, 10.0
+withdrawal, 1, 2, 8.0
+dispute, 1, 1,
+chargeback, 1, 1,
+deposit, 2, 3, 5.0
+withdrawal, 1, 4, 1.0
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &options(true));
+    assert_eq!(rejected, vec![(4, RejectionReason::AccountLocked)]);
+
+    // The dispute holds the full deposit, with Available covering what was already withdrawn
+    let disputed = balance_as_of(&transaction_db, 1, AsOf::Seq(3)).unwrap();
+    assert_eq!((disputed[0].available(), disputed[0].held()), (-8.0, 10.0));
+    // The chargeback fee adds to the debt
+    assert_eq!(balances(&client_account_db, 1), (-23.0, 0.0, -23.0, true));
+    assert!(transaction_db.get_unbalanced_entries().unwrap().is_empty());
+
+    let report = format_accounts(
+        &client_account_db
+            .get_negative_accounts(AccountOrder::ClientId)
+            .unwrap(),
+        &OutputOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        report,
+        "client,available,held,total,locked\n\
+         1,-23.0000,0.0000,-23.0000,true\n"
+    );
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_without_policy_disputes_and_fees_stay_within_available() {
+    let input = "type, client, tx, amount
+deposit, 1, 1, 10.0
+withdrawal, 1, 2, 8.0
+dispute, 1, 1,
+deposit, 1, 3, 4.0
+dispute, 1, 3,
+chargeback, 1, 3,
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &options(false));
+    assert_eq!(rejected, vec![(1, RejectionReason::InsufficientFunds)]);
+    // Only the 2.0 left are taken by the chargeback fee
+    assert_eq!(balances(&client_account_db, 1), (0.0, 0.0, 0.0, true));
+    assert!(
+        client_account_db
+            .get_negative_accounts(AccountOrder::ClientId)
+            .unwrap()
+            .is_empty()
+    );
+
+    // The capped fee is logged as charged, replays allowing negative balances charge the same
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
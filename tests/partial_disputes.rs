@@ -0,0 +1,181 @@
+mod common;
+
+use common::{paranoid_options, process};
+use rust_payment_engine::csv_processor::{ProcessOptions, process_csv_with_options};
+use rust_payment_engine::domain::{DisputeState, LockPolicy, RejectionReason, TransactionType};
+use rust_payment_engine::replay::{replay, verify_replay};
+
+#[test]
+fn test_partial_steps_track_cumulative_amounts() {
+    let input = "type, client, tx, amount
+deposit, 1, 1, 10.0
+refund, 1, 1, 1.0
+dispute, 1, 1, 2.5
+resolve, 1, 1, 1.0
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &paranoid_options());
+    assert_eq!(rejected, vec![]);
+
+    // The partial resolve keeps the rest under dispute
+    let account = client_account_db.get_account(1).unwrap();
+    assert_eq!((account.available(), account.held()), (7.5, 1.5));
+    let state = transaction_db.get_transaction_state(1).unwrap().unwrap();
+    assert_eq!(state.dispute_state, DisputeState::Disputed);
+    assert_eq!(state.in_dispute(), 1.5);
+
+    // A chargeback without amount takes what is left under dispute
+    let input = "type, client, tx, amount
+chargeback, 1, 1,
+";
+    process_csv_with_options(
+        input.as_bytes(),
+        &transaction_db,
+        &client_account_db,
+        &ProcessOptions::default(),
+    )
+    .unwrap();
+    let account = client_account_db.get_account(1).unwrap();
+    assert_eq!((account.available(), account.held()), (7.5, 0.0));
+    assert!(account.is_locked());
+
+    let state = transaction_db.get_transaction_state(1).unwrap().unwrap();
+    assert_eq!(state.dispute_state, DisputeState::ChargedBack);
+    assert_eq!(
+        (
+            state.disputed,
+            state.resolved,
+            state.charged_back,
+            state.refunded
+        ),
+        (2.5, 1.0, 1.5, 1.0)
+    );
+    assert_eq!(state.remaining(), 7.5);
+
+    // The refund and each dispute step are part of the history of the client, after the deposit they refer to
+    let history: Vec<(u32, TransactionType, Option<f64>, f64)> = transaction_db
+        .get_client_history(1)
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            (
+                entry.id,
+                entry.transaction_type,
+                entry.amount,
+                entry.available,
+            )
+        })
+        .collect();
+    assert_eq!(
+        history,
+        vec![
+            (1, TransactionType::Deposit, Some(10.0), 10.0),
+            (1, TransactionType::Refund, Some(1.0), 9.0),
+            (1, TransactionType::Dispute, Some(2.5), 6.5),
+            (1, TransactionType::Resolve, Some(1.0), 7.5),
+            (1, TransactionType::Chargeback, Some(1.5), 7.5),
+        ]
+    );
+
+    assert!(transaction_db.get_unbalanced_entries().unwrap().is_empty());
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_partial_disputes_overlap_until_nothing_is_left() {
+    let input = "type, client, tx, amount
+deposit, 3, 30, 10.0
+dispute, 3, 30, 2.0
+dispute, 3, 30, 3.0
+resolve, 3, 30, 1.0
+chargeback, 3, 30,
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &paranoid_options());
+    assert_eq!(rejected, vec![]);
+    let account = client_account_db.get_account(3).unwrap();
+    assert_eq!(
+        (account.available(), account.held(), account.total()),
+        (6.0, 0.0, 6.0)
+    );
+    let state = transaction_db.get_transaction_state(30).unwrap().unwrap();
+    assert_eq!(state.dispute_state, DisputeState::ChargedBack);
+    assert_eq!(state.remaining(), 6.0);
+
+    // What was not charged back can still be disputed, here on the locked account as the lock policy allows it.
+    // Once all of it was charged back, the transaction is final
+    let input = "type, client, tx, amount
+dispute, 3, 30,
+chargeback, 3, 30,
+dispute, 3, 30, 1.0
+";
+    let options = ProcessOptions {
+        lock_policy: LockPolicy::allow(&[TransactionType::Dispute, TransactionType::Chargeback]),
+        ..ProcessOptions::default()
+    };
+    let rejections = process_csv_with_options(
+        input.as_bytes(),
+        &transaction_db,
+        &client_account_db,
+        &options,
+    )
+    .unwrap();
+    let rejected: Vec<_> = rejections
+        .iter()
+        .map(|rejection| rejection.reason)
+        .collect();
+    assert_eq!(rejected, vec![RejectionReason::InvalidDisputeState]);
+
+    let account = client_account_db.get_account(3).unwrap();
+    assert_eq!(
+        (account.available(), account.held(), account.total()),
+        (0.0, 0.0, 0.0)
+    );
+    let state = transaction_db.get_transaction_state(30).unwrap().unwrap();
+    assert_eq!(
+        (state.disputed, state.resolved, state.charged_back),
+        (11.0, 1.0, 10.0)
+    );
+    assert_eq!(state.remaining(), 0.0);
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_amounts_are_limited_to_what_is_left() {
+    let input = "type, client, tx, amount
+deposit, 2, 2, 5.0
+withdrawal, 2, 3, 1.0
+dispute, 2, 2, 6.0
+dispute, 2, 2, 4.0
+resolve, 2, 2, 4.5
+refund, 2, 2, 2.0
+refund, 2, 3,
+resolve, 2, 2,
+refund, 2, 2,
+refund, 2, 2, 3.0
+refund, 2, 2, 2.5
+dispute, 2, 2, 1.0
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &paranoid_options());
+    assert_eq!(
+        rejected,
+        vec![
+            (2, RejectionReason::ExceedsRemainingAmount),
+            (2, RejectionReason::ExceedsRemainingAmount),
+            (2, RejectionReason::ExceedsRemainingAmount),
+            (3, RejectionReason::NotRefundable),
+            // All of the deposit is left, but part of it was withdrawn
+            (2, RejectionReason::InsufficientFunds),
+            (2, RejectionReason::ExceedsRemainingAmount),
+        ]
+    );
+
+    let account = client_account_db.get_account(2).unwrap();
+    assert_eq!((account.available(), account.held()), (0.0, 1.0));
+    let state = transaction_db.get_transaction_state(2).unwrap().unwrap();
+    assert_eq!((state.refunded, state.in_dispute()), (3.0, 1.0));
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
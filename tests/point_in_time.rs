@@ -0,0 +1,146 @@
+use rust_payment_engine::csv_processor::{process_admin_csv, process_csv};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::{BALANCE_TOLERANCE, TransactionType};
+use rust_payment_engine::replay::{AsOf, balance_as_of};
+use std::fs::File;
+
+const INPUT: &str = "type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 5.0
+withdrawal, 1, 3, 4.0
+withdrawal, 1, 4, 100.0
+dispute, 1, 3,
+chargeback, 1, 3,
+";
+
+fn setup() -> (TransactionDB, ClientAccountDB) {
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    process_csv(INPUT.as_bytes(), &transaction_db, &client_account_db)
+        .expect("Failed to process CSV");
+    (transaction_db, client_account_db)
+}
+
+// (available, held, total, locked)
+fn balance(
+    transaction_db: &TransactionDB,
+    client_id: u16,
+    as_of: AsOf,
+) -> Option<(f64, f64, f64, bool)> {
+    balance_as_of(transaction_db, client_id, as_of)
+        .unwrap()
+        .into_iter()
+        .next()
+        .map(|account| {
+            (
+                account.available(),
+                account.held(),
+                account.total(),
+                account.is_locked(),
+            )
+        })
+}
+
+#[test]
+fn test_balance_as_of_tx() {
+    let (transaction_db, _) = setup();
+
+    assert_eq!(
+        balance(&transaction_db, 1, AsOf::Tx(1)),
+        Some((10.0, 0.0, 10.0, false))
+    );
+    // Client 1 did not change with tx 2
+    assert_eq!(
+        balance(&transaction_db, 1, AsOf::Tx(2)),
+        Some((10.0, 0.0, 10.0, false))
+    );
+    assert_eq!(
+        balance(&transaction_db, 1, AsOf::Tx(3)),
+        Some((6.0, 0.0, 6.0, false))
+    );
+    assert_eq!(balance(&transaction_db, 2, AsOf::Tx(1)), None);
+
+    // The rejected withdrawal was never applied
+    let err = balance_as_of(&transaction_db, 1, AsOf::Tx(4)).unwrap_err();
+    assert!(err.to_string().contains("tx 4"), "{}", err);
+}
+
+#[test]
+fn test_balance_as_of_line_and_seq() {
+    let (transaction_db, _) = setup();
+
+    // Line 1 is the header
+    assert_eq!(balance(&transaction_db, 1, AsOf::Line(1)), None);
+    assert_eq!(
+        balance(&transaction_db, 1, AsOf::Line(6)),
+        Some((2.0, 4.0, 6.0, false))
+    );
+    assert_eq!(
+        balance(&transaction_db, 1, AsOf::Line(7)),
+        Some((2.0, 0.0, 2.0, true))
+    );
+    assert_eq!(
+        balance(&transaction_db, 1, AsOf::Seq(3)),
+        Some((6.0, 0.0, 6.0, false))
+    );
+}
+
+#[test]
+fn test_live_state_is_not_changed() {
+    let (transaction_db, client_account_db) = setup();
+    let admin = "type, client, tx, amount, operator, reason\nunlock, 1, 100, , ops-42, reviewed\n";
+    process_admin_csv(admin.as_bytes(), &transaction_db, &client_account_db).unwrap();
+    let before = client_account_db.get_all_accounts().unwrap();
+    let log_length = transaction_db.get_event_log(None).unwrap().len();
+
+    assert_eq!(
+        balance(&transaction_db, 1, AsOf::Tx(100)),
+        Some((2.0, 0.0, 2.0, false))
+    );
+    assert_eq!(
+        balance(&transaction_db, 1, AsOf::Tx(3)),
+        Some((6.0, 0.0, 6.0, false))
+    );
+    assert_eq!(client_account_db.get_all_accounts().unwrap(), before);
+    assert_eq!(
+        transaction_db.get_event_log(None).unwrap().len(),
+        log_length
+    );
+}
+
+#[test]
+fn test_balance_matches_stored_history() {
+    let file = File::open("tests/resources/big/input.csv").unwrap();
+    let transaction_db = TransactionDB::new(":memory:").unwrap();
+    let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+    process_csv(file, &transaction_db, &client_account_db).unwrap();
+
+    // Balances stored with each deposit and withdrawal are the state right after it
+    for client_id in [1, 2, 3] {
+        for entry in transaction_db.get_client_history(client_id).unwrap() {
+            if !matches!(
+                entry.transaction_type,
+                TransactionType::Deposit | TransactionType::Withdrawal
+            ) {
+                continue;
+            }
+            let Some(account) = balance_as_of(&transaction_db, client_id, AsOf::Tx(entry.id))
+                .ok()
+                .and_then(|accounts| accounts.into_iter().next())
+            else {
+                continue;
+            };
+            assert!(
+                (account.available() - entry.available).abs() < BALANCE_TOLERANCE,
+                "tx {}",
+                entry.id
+            );
+            assert!(
+                (account.held() - entry.held).abs() < BALANCE_TOLERANCE,
+                "tx {}",
+                entry.id
+            );
+        }
+    }
+}
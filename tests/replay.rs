@@ -0,0 +1,132 @@
+use rust_payment_engine::csv_processor::{
+    ProcessOptions, process_admin_csv, process_csv, process_csv_with_options,
+};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::domain::{LockPolicy, RejectionReason, TransactionType};
+use rust_payment_engine::replay::{AsOf, balance_as_of, replay, verify_replay};
+use std::fs::File;
+
+fn setup(input_path: &str) -> (TransactionDB, ClientAccountDB) {
+    let file = File::open(input_path).expect("Failed to open input CSV");
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    process_csv(file, &transaction_db, &client_account_db).expect("Failed to process CSV");
+    (transaction_db, client_account_db)
+}
+
+#[test]
+fn test_full_replay_matches_accounts() {
+    for input_path in [
+        "tests/resources/simple/input.csv",
+        "tests/resources/lock/input.csv",
+        "tests/resources/big/input.csv",
+    ] {
+        let (transaction_db, client_account_db) = setup(input_path);
+        let replayed = replay(&transaction_db, None).unwrap();
+        verify_replay(&replayed.client_account_db, &client_account_db)
+            .unwrap_or_else(|err| panic!("{}: {}", input_path, err));
+    }
+}
+
+#[test]
+fn test_replay_includes_admin_operations_and_lock_policy() {
+    let (transaction_db, client_account_db) = setup("tests/resources/admin/input.csv");
+    let admin_file = File::open("tests/resources/admin/admin.csv").unwrap();
+    process_admin_csv(admin_file, &transaction_db, &client_account_db).unwrap();
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+
+    // Chargebacks accepted on an already locked account replay the same way
+    let transaction_db = TransactionDB::new(":memory:").unwrap();
+    let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+    let options = ProcessOptions {
+        lock_policy: LockPolicy::allow(&[TransactionType::Resolve, TransactionType::Chargeback]),
+        ..ProcessOptions::default()
+    };
+    let file = File::open("tests/resources/lock_policy/input.csv").unwrap();
+    process_csv_with_options(file, &transaction_db, &client_account_db, &options).unwrap();
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_replay_up_to_seq_stops_early() {
+    let transaction_db = TransactionDB::new(":memory:").unwrap();
+    let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+    let input = "type, client, tx, amount
+deposit, 1, 1, 10.0
+withdrawal, 1, 2, 50.0
+dispute, 1, 1,
+chargeback, 1, 1,
+";
+    process_csv(input.as_bytes(), &transaction_db, &client_account_db).unwrap();
+
+    // The rejected withdrawal is not logged, so the dispute is the second input
+    let replayed = replay(&transaction_db, Some(2)).unwrap();
+    assert_eq!(replayed.last_seq, Some(2));
+    let account = replayed.client_account_db.get_account(1).unwrap();
+    assert_eq!(account.held(), 10.0);
+    assert!(!account.is_locked());
+    assert!(verify_replay(&replayed.client_account_db, &client_account_db).is_err());
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    assert_eq!(replayed.last_seq, Some(3));
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+}
+
+#[test]
+fn test_rejected_withdrawals_cannot_be_disputed() {
+    let transaction_db = TransactionDB::new(":memory:").unwrap();
+    let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+    let input = "type, client, tx, amount
+deposit, 1, 1, 10.0
+withdrawal, 1, 2, 50.0
+dispute, 1, 2, 5.0
+";
+    let rejections = process_csv(input.as_bytes(), &transaction_db, &client_account_db).unwrap();
+    assert_eq!(rejections.len(), 2);
+    assert_eq!(rejections[1].reason, RejectionReason::TransactionNotFound);
+    assert_eq!(client_account_db.get_account(1).unwrap().held(), 0.0);
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+    let accounts = balance_as_of(&transaction_db, 1, AsOf::Line(3)).unwrap();
+    assert_eq!(accounts[0].available(), 10.0);
+}
+
+#[test]
+fn test_changed_account_is_reported() {
+    let (transaction_db, client_account_db) = setup("tests/resources/simple/input.csv");
+    let mut account = client_account_db.get_account(1).unwrap();
+    account.add_funds(1.0);
+    client_account_db.update_client_account(&account).unwrap();
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    let err = verify_replay(&replayed.client_account_db, &client_account_db).unwrap_err();
+    assert!(err.to_string().starts_with("client 1 "), "{}", err);
+}
+
+#[test]
+fn test_event_log_is_append_only() {
+    let path = std::env::temp_dir().join(format!("replay_event_log_{}.db", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    let _ = std::fs::remove_file(&path);
+    let transaction_db = TransactionDB::new(&path).unwrap();
+    let client_account_db = ClientAccountDB::new(":memory:").unwrap();
+    process_csv(
+        "type, client, tx, amount\ndeposit, 1, 1, 10.0\n".as_bytes(),
+        &transaction_db,
+        &client_account_db,
+    )
+    .unwrap();
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    assert!(
+        conn.execute("UPDATE event_log SET amount = 1000.0", [])
+            .is_err()
+    );
+    assert!(conn.execute("DELETE FROM event_log", []).is_err());
+    assert_eq!(transaction_db.get_event_log(None).unwrap().len(), 1);
+    let _ = std::fs::remove_file(&path);
+}
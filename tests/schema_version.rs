@@ -0,0 +1,34 @@
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+
+fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("{}_{}.db", name, std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_databases_of_the_current_version_can_be_reopened() {
+    let path = temp_path("schema_current");
+    TransactionDB::new(&path).unwrap();
+    assert!(TransactionDB::new(&path).is_ok());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_databases_with_an_older_schema_are_refused() {
+    // Accounts table from before multi-currency, without version
+    let path = temp_path("schema_stale");
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute(
+        "CREATE TABLE client_accounts (client INTEGER PRIMARY KEY, available REAL NOT NULL,
+         held REAL NOT NULL, total REAL NOT NULL, locked BOOL)",
+        [],
+    )
+    .unwrap();
+    drop(conn);
+
+    let err = ClientAccountDB::new(&path).err().unwrap();
+    assert!(err.to_string().contains("schema version 0, expected 1"));
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use common::{assert_csv_eq_ordered, read_file, run_csv};
+use rust_payment_engine::db::AccountOrder;
+use rust_payment_engine::output::OutputOptions;
+
+#[test]
+fn test_accounts_are_sorted_by_client_by_default() {
+    let actual = run_csv("tests/resources/big/input.csv", &OutputOptions::default());
+    let expected = read_file("tests/resources/big/expected_accounts.csv");
+    assert_csv_eq_ordered(&actual, &expected);
+}
+
+#[test]
+fn test_accounts_can_be_sorted_by_total_descending() {
+    let options = OutputOptions {
+        order: AccountOrder::TotalDesc,
+        ..OutputOptions::default()
+    };
+    let actual = run_csv("tests/resources/big/input.csv", &options);
+    let totals: Vec<f64> = actual
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(3).unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(totals.len(), 10);
+    assert!(
+        totals.windows(2).all(|pair| pair[0] >= pair[1]),
+        "Totals are not descending: {:?}",
+        totals
+    );
+}
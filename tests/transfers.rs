@@ -0,0 +1,109 @@
+mod common;
+
+use common::{balances, paranoid_options, process};
+use rust_payment_engine::domain::RejectionReason;
+use rust_payment_engine::replay::{AsOf, balance_as_of, replay, verify_replay};
+
+#[test]
+fn test_transfer_moves_funds_between_clients() {
+    let input = "type, client, tx, amount, to_client
+deposit, 1, 1, 10.0,
+transfer, 1, 2, 4.0, 2
+transfer, 1, 3, 7.0, 2
+transfer, 1, 4, 1.0,
+transfer, 1, 5, 1.0, 1
+transfer, 2, 2, 1.0, 1
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &paranoid_options());
+    assert_eq!(
+        rejected,
+        vec![
+            (3, RejectionReason::InsufficientFunds),
+            (4, RejectionReason::InvalidTransfer),
+            (5, RejectionReason::InvalidTransfer),
+            (2, RejectionReason::DuplicateTransaction),
+        ]
+    );
+    assert_eq!(balances(&client_account_db, 1), (6.0, 0.0, 6.0, false));
+    assert_eq!(balances(&client_account_db, 2), (4.0, 0.0, 4.0, false));
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+    // The receiving client has no input of its own before the transfer
+    let accounts = balance_as_of(&transaction_db, 2, AsOf::Tx(2)).unwrap();
+    assert_eq!(accounts[0].available(), 4.0);
+
+    // Both clients list the applied transfer with their own balances, the rejected one is left out
+    let history = |client_id| -> Vec<(u32, f64)> {
+        transaction_db
+            .get_client_history(client_id)
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.id, entry.available))
+            .collect()
+    };
+    assert_eq!(history(1), vec![(1, 10.0), (2, 6.0)]);
+    assert_eq!(history(2), vec![(2, 4.0)]);
+}
+
+#[test]
+fn test_transfers_respect_locks_on_both_sides() {
+    let input = "type, client, tx, amount, to_client
+deposit, 1, 1, 10.0,
+deposit, 2, 2, 5.0,
+deposit, 3, 3, 5.0,
+dispute, 2, 2, ,
+chargeback, 2, 2, ,
+transfer, 1, 4, 1.0, 2
+transfer, 2, 5, 1.0, 1
+transfer, 1, 6, 1.0, 3
+";
+    let (_, client_account_db, rejected) = process(input, &paranoid_options());
+    assert_eq!(
+        rejected,
+        vec![
+            (4, RejectionReason::AccountLocked),
+            (5, RejectionReason::AccountLocked),
+        ]
+    );
+    assert_eq!(balances(&client_account_db, 1), (9.0, 0.0, 9.0, false));
+    assert_eq!(balances(&client_account_db, 3), (6.0, 0.0, 6.0, false));
+}
+
+#[test]
+fn test_disputed_transfer_is_held_by_receiver() {
+    let input = "type, client, tx, amount, to_client
+deposit, 1, 1, 10.0,
+transfer, 1, 2, 4.0, 2
+dispute, 2, 2, ,
+dispute, 1, 2, ,
+resolve, 1, 2, ,
+dispute, 1, 2, ,
+chargeback, 1, 2, ,
+";
+    let (transaction_db, client_account_db, rejected) = process(input, &paranoid_options());
+    // Only the sender can dispute its transfer
+    assert_eq!(rejected, vec![(2, RejectionReason::TransactionNotFound)]);
+
+    // The chargeback returns the held funds to the sender and locks the receiver
+    assert_eq!(balances(&client_account_db, 1), (10.0, 0.0, 10.0, false));
+    assert_eq!(balances(&client_account_db, 2), (0.0, 0.0, 0.0, true));
+
+    let replayed = replay(&transaction_db, None).unwrap();
+    verify_replay(&replayed.client_account_db, &client_account_db).unwrap();
+    let accounts = balance_as_of(&transaction_db, 2, AsOf::Seq(3)).unwrap();
+    assert_eq!((accounts[0].available(), accounts[0].held()), (0.0, 4.0));
+}
+
+#[test]
+fn test_dispute_fails_when_receiver_spent_the_funds() {
+    let input = "type, client, tx, amount, to_client
+deposit, 1, 1, 10.0,
+transfer, 1, 2, 4.0, 2
+withdrawal, 2, 3, 3.0,
+dispute, 1, 2, ,
+";
+    let (_, client_account_db, rejected) = process(input, &paranoid_options());
+    assert_eq!(rejected, vec![(2, RejectionReason::InsufficientFunds)]);
+    assert_eq!(balances(&client_account_db, 2), (1.0, 0.0, 1.0, false));
+}
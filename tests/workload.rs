@@ -0,0 +1,56 @@
+use rust_payment_engine::csv_processor::{get_all_accounts_formatted, process_csv};
+use rust_payment_engine::db::{ClientAccountDB, TransactionDB};
+use rust_payment_engine::model::ReferenceModel;
+use rust_payment_engine::output::{OutputOptions, format_records};
+use rust_payment_engine::workload::{WorkloadConfig, WorkloadGenerator, WorkloadWriter};
+
+#[test]
+fn test_engine_matches_reference_model_on_generated_workload() {
+    let config = WorkloadConfig {
+        seed: 42,
+        rows: 3000,
+        clients: 20,
+        mix: "deposit=45,withdrawal=25,dispute=15,resolve=10,chargeback=5"
+            .parse()
+            .unwrap(),
+        duplicate_rate: 0.02,
+        invalid_rate: 0.03,
+        unknown_tx_rate: 0.05,
+        ..WorkloadConfig::default()
+    };
+
+    let mut writer = WorkloadWriter::new(vec![]);
+    let mut model = ReferenceModel::default();
+    let mut expected_rejections = Vec::new();
+    for tx in WorkloadGenerator::new(config) {
+        writer.write(&tx).unwrap();
+        if let Some(reason) = model.apply(&tx) {
+            expected_rejections.push((tx.id, reason));
+        }
+    }
+    let input = writer.finish().unwrap();
+
+    let transaction_db = TransactionDB::new(":memory:").expect("Failed to create TransactionDB");
+    let client_account_db =
+        ClientAccountDB::new(":memory:").expect("Failed to create ClientAccountDB");
+    let rejections = process_csv(input.as_slice(), &transaction_db, &client_account_db)
+        .expect("Failed to process CSV");
+
+    let rejections: Vec<_> = rejections
+        .iter()
+        .map(|rejection| (rejection.id, rejection.reason))
+        .collect();
+    assert_eq!(rejections, expected_rejections);
+    // Every kind of rejection the generator aims for shows up
+    assert!(
+        rejections.len() > 100,
+        "only {} rejections",
+        rejections.len()
+    );
+
+    let options = OutputOptions::default();
+    assert_eq!(
+        get_all_accounts_formatted(&client_account_db, &options).unwrap(),
+        format_records(&model.accounts(), &options).unwrap()
+    );
+}